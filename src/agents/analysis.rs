@@ -32,19 +32,16 @@ impl Agent for AnalysisAgent {
         &self.name
     }
 
+    fn description(&self) -> &str {
+        "Explain and analyze code, architecture or project structure"
+    }
+
     fn capabilities(&self) -> Vec<AgentCapability> {
         vec![AgentCapability::Analysis]
     }
 
     fn can_handle(&self, task: &str) -> bool {
-        let keywords = [
-            "analyze",
-            "explain",
-            "review",
-            "understand",
-            "investigate",
-            "examine",
-        ];
+        let keywords = ["analyze", "explain", "understand", "investigate", "examine"];
         keywords.iter().any(|kw| task.to_lowercase().contains(kw))
     }
 
@@ -64,7 +61,8 @@ impl Agent for AnalysisAgent {
             match tools.read_file(&file_path).await {
                 Ok(content) => {
                     analysis_context = format!("File: {}\n\n{}", file_path, content);
-                    context.add_message(format!("Analyzing file: {}", file_path));
+                    context
+                        .add_message(Message::assistant(format!("Analyzing file: {}", file_path)));
                 }
                 Err(e) => {
                     debug!("Could not read file {}: {}", file_path, e);
@@ -91,12 +89,12 @@ When analyzing code or projects:
             )
         };
 
-        let messages = vec![Message::system(system_prompt), Message::user(user_message)];
+        let messages = context.messages(system_prompt, user_message);
 
         let response = llm.chat_with_history(messages, "default").await?;
 
-        context.add_message(format!("Analysis task: {}", task));
-        context.add_message(format!("Analysis result: {}", response));
+        context.add_message(Message::assistant(format!("Analysis task: {}", task)));
+        context.add_message(Message::assistant(format!("Analysis result: {}", response)));
 
         Ok(AgentResult::success(response))
     }
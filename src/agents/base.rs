@@ -5,10 +5,10 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::context::ContextManager;
-use crate::llm::LlmClient;
+use crate::llm::types::Role;
+use crate::llm::{LlmClient, Message};
 use crate::tools::ToolExecutor;
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum AgentCapability {
     CodeGeneration,
@@ -16,6 +16,7 @@ pub enum AgentCapability {
     ShellExecution,
     FileOperations,
     Analysis,
+    Testing,
     TaskOrchestration,
     ContextManagement,
 }
@@ -23,7 +24,8 @@ pub enum AgentCapability {
 #[derive(Debug, Clone)]
 pub struct AgentContext {
     pub working_directory: String,
-    pub conversation_history: Vec<String>,
+    /// Earlier tasks of the session and what agents noted while working
+    pub conversation_history: Vec<Message>,
     pub metadata: HashMap<String, String>,
 }
 
@@ -36,14 +38,57 @@ impl AgentContext {
         }
     }
 
-    pub fn add_message(&mut self, message: impl Into<String>) {
-        self.conversation_history.push(message.into());
+    /// Start from an earlier conversation
+    pub fn with_history(mut self, history: Vec<Message>) -> Self {
+        self.conversation_history = history;
+        self
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.conversation_history.push(message);
+    }
+
+    /// The messages for an LLM call: the system prompt, the conversation so
+    /// far and the request. Summaries of older turns join the system prompt,
+    /// as not every chat template accepts a second system message.
+    pub fn messages(
+        &self,
+        system_prompt: impl Into<String>,
+        request: impl Into<String>,
+    ) -> Vec<Message> {
+        let mut system_prompt = system_prompt.into();
+        let mut history = Vec::new();
+        for message in &self.conversation_history {
+            match message.role {
+                Role::System => {
+                    system_prompt.push_str("\n\n");
+                    system_prompt.push_str(&message.content);
+                }
+                _ => history.push(message.clone()),
+            }
+        }
+
+        let mut messages = vec![Message::system(system_prompt)];
+        messages.extend(history);
+        messages.push(Message::user(request));
+        messages
     }
 
-    #[allow(dead_code)]
     pub fn set_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
         self.metadata.insert(key.into(), value.into());
     }
+
+    /// Copy of this context for a subtask that runs alongside others
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Fold a fork back in: messages added after `forked_at` and all metadata
+    pub fn merge(&mut self, forked: AgentContext, forked_at: usize) {
+        self.conversation_history
+            .extend(forked.conversation_history.into_iter().skip(forked_at));
+        self.metadata.extend(forked.metadata);
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -80,7 +125,9 @@ impl AgentResult {
 pub trait Agent: Send + Sync {
     fn name(&self) -> &str;
 
-    #[allow(dead_code)]
+    /// One-line summary of what the agent is for, used when routing tasks
+    fn description(&self) -> &str;
+
     fn capabilities(&self) -> Vec<AgentCapability>;
 
     fn can_handle(&self, task: &str) -> bool;
@@ -94,10 +141,7 @@ pub trait Agent: Send + Sync {
         context_mgr: Arc<ContextManager>,
     ) -> Result<AgentResult>;
 
-    #[allow(dead_code)]
     async fn plan(&self, task: &str, llm: Arc<dyn LlmClient>) -> Result<Vec<String>> {
-        use crate::llm::Message;
-
         let system_prompt = format!(
             "You are a {} agent. Break down the following task into actionable steps. \
              Return a JSON array of step descriptions.",
@@ -108,10 +152,16 @@ pub trait Agent: Send + Sync {
 
         let response = llm.chat_with_history(messages, "default").await?;
 
-        let steps: Vec<String> =
-            serde_json::from_str(&response).unwrap_or_else(|_| vec![task.to_string()]);
+        let steps = match (response.find('['), response.rfind(']')) {
+            (Some(start), Some(end)) if start < end => {
+                serde_json::from_str::<Vec<String>>(&response[start..=end]).ok()
+            }
+            _ => None,
+        };
 
-        Ok(steps)
+        Ok(steps
+            .filter(|steps| !steps.is_empty())
+            .unwrap_or_else(|| vec![task.to_string()]))
     }
 }
 
@@ -134,6 +184,17 @@ impl AgentRegistry {
         self.agents.get(name).cloned()
     }
 
+    /// All registered agents, sorted by name
+    pub fn agents(&self) -> Vec<Arc<dyn Agent>> {
+        let mut agents: Vec<Arc<dyn Agent>> = self.agents.values().cloned().collect();
+        agents.sort_by(|a, b| a.name().cmp(b.name()));
+        agents
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.agents().iter().map(|a| a.name().to_string()).collect()
+    }
+
     pub fn find_capable(&self, task: &str) -> Vec<Arc<dyn Agent>> {
         self.agents
             .values()
@@ -32,6 +32,10 @@ impl Agent for CodeAgent {
         &self.name
     }
 
+    fn description(&self) -> &str {
+        "Generate new code or edit existing code"
+    }
+
     fn capabilities(&self) -> Vec<AgentCapability> {
         vec![
             AgentCapability::CodeGeneration,
@@ -76,22 +80,12 @@ Format your response as:
 ```
 Explanation: <your explanation>"#;
 
-        let history_context = context.conversation_history.join("\n");
-        let full_task = if history_context.is_empty() {
-            task.to_string()
-        } else {
-            format!(
-                "Previous context:\n{}\n\nCurrent task: {}",
-                history_context, task
-            )
-        };
-
-        let messages = vec![Message::system(system_prompt), Message::user(full_task)];
+        let messages = context.messages(system_prompt, task);
 
         let response = llm.chat_with_history(messages, "default").await?;
 
-        context.add_message(format!("Code task: {}", task));
-        context.add_message(format!("Response: {}", response));
+        context.add_message(Message::assistant(format!("Code task: {}", task)));
+        context.add_message(Message::assistant(format!("Response: {}", response)));
 
         if response.contains("```") {
             if let Some(file_path) = self.extract_file_path(&response) {
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::base::{Agent, AgentCapability, AgentContext, AgentResult};
+use crate::context::ContextManager;
+use crate::llm::{ConfiguredModel, LlmClient, ModelSettings};
+use crate::tools::ToolExecutor;
+
+/// An agent with its own model settings from `[agents.<name>]`, applied to
+/// whichever client it is run with
+pub struct ConfiguredAgent {
+    inner: Arc<dyn Agent>,
+    settings: ModelSettings,
+}
+
+impl ConfiguredAgent {
+    /// Wrap `agent` only when it has settings of its own
+    pub fn wrap(agent: Arc<dyn Agent>, settings: Option<&ModelSettings>) -> Arc<dyn Agent> {
+        match settings.filter(|s| !s.is_empty()) {
+            Some(settings) => Arc::new(Self {
+                inner: agent,
+                settings: settings.clone(),
+            }),
+            None => agent,
+        }
+    }
+}
+
+#[async_trait]
+impl Agent for ConfiguredAgent {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn capabilities(&self) -> Vec<AgentCapability> {
+        self.inner.capabilities()
+    }
+
+    fn can_handle(&self, task: &str) -> bool {
+        self.inner.can_handle(task)
+    }
+
+    async fn execute(
+        &self,
+        task: &str,
+        context: &mut AgentContext,
+        llm: Arc<dyn LlmClient>,
+        tools: Arc<ToolExecutor>,
+        context_mgr: Arc<ContextManager>,
+    ) -> Result<AgentResult> {
+        let llm = ConfiguredModel::wrap(llm, &self.settings);
+        self.inner
+            .execute(task, context, llm, tools, context_mgr)
+            .await
+    }
+
+    async fn plan(&self, task: &str, llm: Arc<dyn LlmClient>) -> Result<Vec<String>> {
+        let llm = ConfiguredModel::wrap(llm, &self.settings);
+        self.inner.plan(task, llm).await
+    }
+}
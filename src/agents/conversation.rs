@@ -0,0 +1,217 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+use super::coordinator::truncate;
+use crate::llm::types::Role;
+use crate::llm::{ConfiguredModel, LlmClient, Message, ModelSettings};
+
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+/// The messages of a session, carried from one task to the next so a task can
+/// refer to earlier ones. Once the history is longer than `max_chars`, all but
+/// the `keep_recent` latest messages are replaced by a summary from the model.
+pub struct Conversation {
+    messages: Mutex<Vec<Message>>,
+    max_chars: usize,
+    keep_recent: usize,
+    /// Longest a single message is kept, so one large file or command output
+    /// doesn't crowd out everything else
+    max_message_chars: usize,
+    /// Settings the summaries are written with, over the session's own
+    settings: ModelSettings,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self {
+            messages: Mutex::new(Vec::new()),
+            max_chars: Self::DEFAULT_MAX_CHARS,
+            keep_recent: Self::DEFAULT_KEEP_RECENT,
+            max_message_chars: 4_000,
+            settings: ModelSettings::default(),
+        }
+    }
+}
+
+impl Conversation {
+    pub const DEFAULT_MAX_CHARS: usize = 16_000;
+    pub const DEFAULT_KEEP_RECENT: usize = 6;
+
+    pub fn with_limits(mut self, max_chars: usize, keep_recent: usize) -> Self {
+        self.max_chars = max_chars;
+        self.keep_recent = keep_recent;
+        self.max_message_chars = self.max_message_chars.min(max_chars);
+        self
+    }
+
+    /// Summarize with `model` instead of the session's model
+    pub fn with_model(mut self, model: Option<String>) -> Self {
+        self.settings.model = model;
+        self
+    }
+
+    pub fn messages(&self) -> Vec<Message> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    /// Continue a saved conversation instead of the current one
+    pub fn replace(&self, messages: Vec<Message>) {
+        *self.messages.lock().unwrap() = messages;
+    }
+
+    pub fn clear(&self) {
+        self.messages.lock().unwrap().clear();
+    }
+
+    /// Add a finished task: the request, the notes the agents added while
+    /// working on it and the answer, unless the last note already holds it
+    pub fn record_turn(&self, task: &str, notes: Vec<Message>, output: &str) {
+        let mut turn = vec![Message::user(task)];
+        let answered = notes.last().is_some_and(|m| m.content.contains(output));
+        turn.extend(notes);
+        if !answered {
+            turn.push(Message::assistant(output));
+        }
+
+        let mut messages = self.messages.lock().unwrap();
+        for mut message in turn {
+            message.content = truncate(&message.content, self.max_message_chars);
+            messages.push(message);
+        }
+    }
+
+    /// Summarize older messages if the history has grown past its limit. When
+    /// the model can't be reached they are dropped instead.
+    pub async fn compact(&self, llm: Arc<dyn LlmClient>) {
+        let older = {
+            let messages = self.messages.lock().unwrap();
+            let chars: usize = messages.iter().map(|m| m.content.len()).sum();
+            if chars <= self.max_chars || messages.len() <= self.keep_recent {
+                return;
+            }
+            messages[..messages.len() - self.keep_recent].to_vec()
+        };
+
+        debug!("Summarizing {} earlier messages", older.len());
+        let llm = ConfiguredModel::wrap(llm, &self.settings);
+        let summary = match summarize(&older, llm).await {
+            Ok(summary) => summary,
+            Err(e) => {
+                warn!(
+                    "Could not summarize the conversation, dropping older messages: {:#}",
+                    e
+                );
+                "(earlier messages were dropped)".to_string()
+            }
+        };
+
+        let mut messages = self.messages.lock().unwrap();
+        // The conversation may have been cleared or replaced meanwhile
+        if messages.len() >= older.len() && messages[..older.len()] == older[..] {
+            messages.splice(
+                ..older.len(),
+                [Message::system(format!("{}{}", SUMMARY_PREFIX, summary))],
+            );
+        }
+    }
+}
+
+async fn summarize(messages: &[Message], llm: Arc<dyn LlmClient>) -> Result<String> {
+    let transcript = messages
+        .iter()
+        .map(|message| {
+            let speaker = match message.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+                Role::System => "Earlier summary",
+            };
+            let content = message
+                .content
+                .strip_prefix(SUMMARY_PREFIX)
+                .unwrap_or(&message.content);
+            format!("{}: {}", speaker, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let system_prompt = "Summarize this conversation between a developer and a coding assistant \
+        so the assistant can continue it. Keep the file, function and command names, what was \
+        changed, the decisions made and anything left to do. Reply with the summary only.";
+    let summary = llm
+        .chat_with_history(
+            vec![Message::system(system_prompt), Message::user(transcript)],
+            "default",
+        )
+        .await?;
+    Ok(summary.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::AgentContext;
+    use crate::llm::types::ChatRequest;
+    use async_trait::async_trait;
+
+    struct SummaryLlm;
+
+    #[async_trait]
+    impl LlmClient for SummaryLlm {
+        async fn chat(&self, request: ChatRequest) -> Result<String> {
+            assert_eq!(request.model, "small-model");
+            assert!(request.messages[1].content.starts_with("User: first task"));
+            Ok("The user asked for two things.".to_string())
+        }
+
+        async fn chat_with_history(&self, messages: Vec<Message>, model: &str) -> Result<String> {
+            self.chat(ChatRequest::new(model, messages)).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_long_conversations_are_summarized() {
+        let conversation = Conversation::default()
+            .with_limits(100, 2)
+            .with_model(Some("small-model".to_string()));
+        conversation.record_turn("first task", vec![], &"a".repeat(80));
+        conversation.compact(Arc::new(SummaryLlm)).await;
+        assert_eq!(conversation.messages().len(), 2);
+
+        conversation.record_turn(
+            "second task",
+            vec![Message::assistant("Response: done")],
+            "done",
+        );
+        assert_eq!(conversation.messages().len(), 4);
+
+        conversation.compact(Arc::new(SummaryLlm)).await;
+        let messages = conversation.messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].role, Role::System);
+        assert!(messages[0].content.ends_with("asked for two things."));
+        assert_eq!(messages[1].content, "second task");
+        assert_eq!(messages[2].content, "Response: done");
+    }
+
+    #[test]
+    fn test_history_reaches_the_prompt() {
+        let conversation = Conversation::default();
+        conversation.replace(vec![Message::system(format!(
+            "{}Added parse_config",
+            SUMMARY_PREFIX
+        ))]);
+        conversation.record_turn("write parse_config in config.rs", vec![], "Done");
+
+        let context = AgentContext::new(".").with_history(conversation.messages());
+        let messages = context.messages("You write code.", "Now make that function async");
+        let roles: Vec<Role> = messages.iter().map(|m| m.role.clone()).collect();
+        assert_eq!(
+            roles,
+            vec![Role::System, Role::User, Role::Assistant, Role::User]
+        );
+        assert!(messages[0].content.starts_with("You write code."));
+        assert!(messages[0].content.ends_with("Added parse_config"));
+        assert_eq!(messages[1].content, "write parse_config in config.rs");
+    }
+}
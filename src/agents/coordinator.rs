@@ -1,16 +1,120 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::Arc;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use super::base::{Agent, AgentCapability, AgentContext, AgentRegistry, AgentResult};
+use super::scheduler::{validate_subtasks, DagState, SubtaskStatus};
 use crate::context::ContextManager;
+use crate::events::Event;
 use crate::llm::{LlmClient, Message};
 use crate::tools::ToolExecutor;
 
+/// How many times the model is asked to fix an invalid plan
+const MAX_PLAN_CORRECTIONS: usize = 2;
+
+/// What the coordinator does once a subtask has failed all of its attempts
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailurePolicy {
+    /// Start nothing new and report the remaining subtasks as not run
+    FailFast,
+    /// Skip the failed subtask's dependents and keep running everything else
+    Continue,
+    /// Ask the LLM to revise the pending part of the plan
+    Replan,
+}
+
+impl std::str::FromStr for FailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail-fast" | "failfast" => Ok(Self::FailFast),
+            "continue" => Ok(Self::Continue),
+            "replan" => Ok(Self::Replan),
+            _ => Err(format!(
+                "Unknown failure policy '{}' (expected fail-fast, continue or replan)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CoordinatorOptions {
+    /// Maximum number of independent subtasks running at once
+    pub max_parallel: usize,
+    /// How much of each dependency's output is passed to a dependent subtask
+    pub max_dependency_chars: usize,
+    /// Extra attempts for a failed subtask, each told why the previous one failed
+    pub max_retries: usize,
+    /// How many times failures may trigger replanning in one run
+    pub max_replans: usize,
+    pub failure_policy: FailurePolicy,
+}
+
+impl Default for CoordinatorOptions {
+    fn default() -> Self {
+        Self {
+            max_parallel: 4,
+            max_dependency_chars: 4000,
+            max_retries: 1,
+            max_replans: 2,
+            failure_policy: FailurePolicy::Continue,
+        }
+    }
+}
+
+/// One run of a subtask's agent
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Attempt {
+    pub success: bool,
+    pub output: String,
+}
+
+/// Everything a subtask produced, kept in the coordinator result's `subtasks` metadata
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubtaskReport {
+    pub index: usize,
+    pub description: String,
+    pub agent_type: String,
+    pub status: String,
+    pub output: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub attempts: Vec<Attempt>,
+}
+
+impl SubtaskReport {
+    /// Read the reports back out of a coordinator result
+    pub fn from_result(result: &AgentResult) -> Option<Vec<Self>> {
+        serde_json::from_str(result.metadata.get("subtasks")?).ok()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "── Subtask {} ({}): {} ──\n{}",
+            self.index, self.agent_type, self.status, self.description
+        );
+        if self.attempts.len() > 1 {
+            out.push_str(&format!("\n({} attempts)", self.attempts.len()));
+        }
+        if !self.output.is_empty() {
+            out.push_str(&format!("\n\n{}", self.output));
+        }
+        out
+    }
+}
+
 pub struct CoordinatorAgent {
     name: String,
     registry: Arc<AgentRegistry>,
+    options: CoordinatorOptions,
 }
 
 impl CoordinatorAgent {
@@ -18,101 +122,167 @@ impl CoordinatorAgent {
         Self {
             name: "coordinator".to_string(),
             registry,
+            options: CoordinatorOptions::default(),
         }
     }
 
-    async fn decompose_task(&self, task: &str, llm: Arc<dyn LlmClient>) -> Result<Vec<SubTask>> {
-        let system_prompt = r#"You are a task decomposition expert. Analyze the user's task and break it down into subtasks.
+    pub fn with_options(mut self, options: CoordinatorOptions) -> Self {
+        self.options = CoordinatorOptions {
+            max_parallel: options.max_parallel.max(1),
+            ..options
+        };
+        self
+    }
+
+    /// Split `task` into subtasks, reading it in the light of the conversation in `context`
+    pub async fn decompose_task(
+        &self,
+        task: &str,
+        context: &AgentContext,
+        llm: Arc<dyn LlmClient>,
+    ) -> Result<Vec<SubTask>> {
+        let names = self.registry.names();
+        let agents = self
+            .registry
+            .agents()
+            .iter()
+            .map(|agent| format!("- {}: {}", agent.name(), agent.description()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let system_prompt = format!(
+            r#"You are a task decomposition expert. Analyze the user's task and break it down into subtasks.
 
 Available agent types:
-- code: Generate or edit code
-- shell: Execute shell commands
-- file: Read, write, search files
-- analysis: Analyze code or provide insights
-- mcp: Use external MCP tools
+{}
 
 Guidelines:
 1. Keep subtasks atomic and focused
 2. Identify dependencies between subtasks
 3. Choose the most appropriate agent for each subtask
 4. Consider parallel execution when possible
+5. Each subtask receives the output of its dependencies. To place a dependency's
+   output at a specific spot, write {{{{N.output}}}} or {{{{N.metadata.KEY}}}} in the description
 
 Return ONLY a valid JSON array of subtasks in this exact format:
 [
-  {
+  {{
     "description": "what needs to be done",
-    "agent_type": "code|shell|file|analysis|mcp",
+    "agent_type": "{}",
     "dependencies": [0, 1]
-  }
+  }}
 ]
 
 Example:
 [
-  {"description": "Read the configuration file", "agent_type": "file", "dependencies": []},
-  {"description": "Analyze the configuration structure", "agent_type": "analysis", "dependencies": [0]},
-  {"description": "Generate updated configuration", "agent_type": "code", "dependencies": [1]}
-]"#;
+  {{"description": "Read the configuration file", "agent_type": "file", "dependencies": []}},
+  {{"description": "Analyze the configuration structure", "agent_type": "analysis", "dependencies": [0]}},
+  {{"description": "Generate updated configuration", "agent_type": "code", "dependencies": [1]}}
+]"#,
+            agents,
+            names.join("|")
+        );
 
-        let messages = vec![
-            Message::system(system_prompt),
-            Message::user(format!("Task: {}\n\nBreak this down into subtasks:", task)),
-        ];
+        let mut messages = context.messages(
+            system_prompt,
+            format!("Task: {}\n\nBreak this down into subtasks:", task),
+        );
+        let mut corrections = 0;
 
-        let response = llm.chat_with_history(messages, "default").await?;
+        loop {
+            let response = llm.chat_with_history(messages.clone(), "default").await?;
+            debug!("Task decomposition response: {}", response);
 
-        debug!("Task decomposition response: {}", response);
+            let Some(subtasks) = self.parse_subtasks(&response) else {
+                return Ok(vec![SubTask {
+                    description: task.to_string(),
+                    agent_type: self.infer_agent_type(task).to_string(),
+                    dependencies: vec![],
+                }]);
+            };
 
-        let subtasks: Vec<SubTask> = self.parse_subtasks(&response, task);
+            let problems = validate_subtasks(&subtasks, &names);
+            if problems.is_empty() {
+                return Ok(subtasks);
+            }
 
-        if subtasks.is_empty() {
-            return Ok(vec![SubTask {
-                description: task.to_string(),
-                agent_type: self.infer_agent_type(task).to_string(),
-                dependencies: vec![],
-            }]);
-        }
+            if corrections >= MAX_PLAN_CORRECTIONS {
+                anyhow::bail!(
+                    "The model kept returning an invalid plan:\n{}",
+                    problems.join("\n")
+                );
+            }
+            corrections += 1;
+            warn!("Invalid plan, asking for a correction: {:?}", problems);
 
-        Ok(subtasks)
+            messages.push(Message::assistant(response));
+            messages.push(Message::user(format!(
+                "That plan cannot run:\n{}\n\nThe only agent types that exist are: {}.\n\
+                 Dependencies must refer to other subtasks by index and must not form cycles.\n\
+                 Return the corrected JSON array only.",
+                problems.join("\n"),
+                names.join(", ")
+            )));
+        }
     }
 
-    fn parse_subtasks(&self, response: &str, fallback_task: &str) -> Vec<SubTask> {
-        let json_start = response.find('[');
-        let json_end = response.rfind(']');
+    fn parse_subtasks(&self, response: &str) -> Option<Vec<SubTask>> {
+        let start = response.find('[')?;
+        let end = response.rfind(']')?;
+        if start > end {
+            return None;
+        }
 
-        if let (Some(start), Some(end)) = (json_start, json_end) {
-            let json_str = &response[start..=end];
+        serde_json::from_str::<Vec<SubTask>>(&response[start..=end])
+            .ok()
+            .filter(|subtasks| !subtasks.is_empty())
+    }
 
-            if let Ok(subtasks) = serde_json::from_str::<Vec<SubTask>>(json_str) {
-                if !subtasks.is_empty() {
-                    return subtasks;
-                }
-            }
+    fn infer_agent_type(&self, task: &str) -> &'static str {
+        let inferred = self.infer_agent_keyword(task);
+        if self.registry.get(inferred).is_some() {
+            inferred
+        } else {
+            "analysis"
         }
-
-        vec![SubTask {
-            description: fallback_task.to_string(),
-            agent_type: self.infer_agent_type(fallback_task).to_string(),
-            dependencies: vec![],
-        }]
     }
 
-    fn infer_agent_type(&self, task: &str) -> &str {
+    fn infer_agent_keyword(&self, task: &str) -> &'static str {
+        const TEST_WORDS: [&str; 8] = [
+            "test", "tests", "spec", "specs", "suite", "pytest", "jest", "vitest",
+        ];
         let task_lower = task.to_lowercase();
+        let words: Vec<&str> = task_lower
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+        let is_test_word = |word: &&str| TEST_WORDS.contains(word);
+        // "run the test suite" is for the test runner, "write a test" is not
+        let runs_tests = words.iter().enumerate().any(|(i, word)| {
+            ["run", "rerun", "execute"].contains(word)
+                && words[i + 1..].iter().take(3).any(is_test_word)
+        });
 
-        if task_lower.contains("code")
+        if runs_tests {
+            "test"
+        } else if task_lower.contains("code")
             || task_lower.contains("implement")
             || task_lower.contains("write")
         {
             "code"
+        } else if words.iter().any(is_test_word) {
+            "test"
         } else if task_lower.contains("run")
             || task_lower.contains("execute")
             || task_lower.contains("command")
         {
             "shell"
-        } else if task_lower.contains("read")
-            || task_lower.contains("file")
-            || task_lower.contains("search")
+        } else if task_lower.contains("search")
+            || task_lower.contains("grep")
+            || task_lower.contains("find")
         {
+            "search"
+        } else if task_lower.contains("read") || task_lower.contains("file") {
             "file"
         } else if task_lower.contains("mcp")
             || task_lower.contains("tool")
@@ -123,29 +293,6 @@ Example:
             "analysis"
         }
     }
-
-    async fn execute_subtask(
-        &self,
-        subtask: &SubTask,
-        context: &mut AgentContext,
-        llm: Arc<dyn LlmClient>,
-        tools: Arc<ToolExecutor>,
-        context_mgr: Arc<ContextManager>,
-    ) -> Result<AgentResult> {
-        info!(
-            "Executing subtask: {} with {} agent",
-            subtask.description, subtask.agent_type
-        );
-
-        let agent = self
-            .registry
-            .get(&subtask.agent_type)
-            .ok_or_else(|| anyhow::anyhow!("Agent type '{}' not found", subtask.agent_type))?;
-
-        agent
-            .execute(&subtask.description, context, llm, tools, context_mgr)
-            .await
-    }
 }
 
 #[async_trait]
@@ -154,6 +301,10 @@ impl Agent for CoordinatorAgent {
         &self.name
     }
 
+    fn description(&self) -> &str {
+        "Break multi-step tasks into subtasks for other agents"
+    }
+
     fn capabilities(&self) -> Vec<AgentCapability> {
         vec![AgentCapability::TaskOrchestration]
     }
@@ -172,64 +323,898 @@ impl Agent for CoordinatorAgent {
     ) -> Result<AgentResult> {
         debug!("Coordinator analyzing task: {}", task);
 
-        let subtasks = self.decompose_task(task, llm.clone()).await?;
+        let subtasks = self.decompose_task(task, context, llm.clone()).await?;
+
+        self.execute_subtasks(task, &subtasks, context, llm, tools, context_mgr)
+            .await
+    }
+}
+
+impl CoordinatorAgent {
+    /// Run an already decomposed (and possibly user-edited) list of subtasks.
+    ///
+    /// Subtasks start as soon as their dependencies have succeeded, up to
+    /// `max_parallel` at a time. Each runs against a fork of `context` that is
+    /// merged back when it finishes. Failed subtasks are retried, then handled
+    /// according to the failure policy. The output is an answer to `task`
+    /// written from the subtask results, falling back to a status summary.
+    pub async fn execute_subtasks(
+        &self,
+        task: &str,
+        subtasks: &[SubTask],
+        context: &mut AgentContext,
+        llm: Arc<dyn LlmClient>,
+        tools: Arc<ToolExecutor>,
+        context_mgr: Arc<ContextManager>,
+    ) -> Result<AgentResult> {
+        let problems = validate_subtasks(subtasks, &self.registry.names());
+        if !problems.is_empty() {
+            return Ok(AgentResult::failure(format!(
+                "Invalid plan, nothing was run:\n{}",
+                problems.join("\n")
+            )));
+        }
+
+        let options = &self.options;
+        let mut subtasks = subtasks.to_vec();
+        let mut dag = DagState::new(&subtasks);
+        let mut results: Vec<Option<AgentResult>> = subtasks.iter().map(|_| None).collect();
+        let mut attempts: Vec<Vec<Attempt>> = vec![Vec::new(); subtasks.len()];
+        // For failed subtasks handled by a revised plan, the subtasks it added
+        let mut replanned: Vec<Option<Range<usize>>> = vec![None; subtasks.len()];
+        let mut replans = 0;
+        let mut stopped_at = None;
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            for (idx, dep_idx) in dag.skip_blocked() {
+                info!("Dependency {} failed, skipping subtask {}", dep_idx, idx);
+                results[idx] = Some(AgentResult::failure(format!(
+                    "Skipped: dependency {} did not succeed",
+                    dep_idx
+                )));
+            }
+
+            for idx in dag.ready() {
+                if stopped_at.is_some() || running.len() >= options.max_parallel {
+                    break;
+                }
+
+                let subtask = &subtasks[idx];
+                dag.start(idx);
+
+                info!(
+                    "Executing subtask {}: {} with {} agent",
+                    idx, subtask.description, subtask.agent_type
+                );
+                tools.activity().emit(Event::SubtaskStarted {
+                    index: idx,
+                    agent: subtask.agent_type.clone(),
+                    description: subtask.description.clone(),
+                });
+
+                let input = self.subtask_input(idx, &subtasks, &results);
+                let forked_at = context.conversation_history.len();
+                let mut forked = context.fork();
+                let (llm, tools, context_mgr) = (llm.clone(), tools.clone(), context_mgr.clone());
+                let (max_retries, max_chars) = (options.max_retries, options.max_dependency_chars);
+
+                let agent = self.registry.get(&subtask.agent_type);
+                let agent_type = subtask.agent_type.clone();
+
+                running.push(async move {
+                    let Some(agent) = agent else {
+                        let result =
+                            AgentResult::failure(format!("Agent type '{}' not found", agent_type));
+                        let attempts = vec![Attempt {
+                            success: false,
+                            output: result.output.clone(),
+                        }];
+                        return (idx, forked, forked_at, attempts, result);
+                    };
+
+                    let mut attempts = Vec::new();
+                    let mut attempt_input = input.clone();
+
+                    loop {
+                        let result = agent
+                            .execute(
+                                &attempt_input,
+                                &mut forked,
+                                llm.clone(),
+                                tools.clone(),
+                                context_mgr.clone(),
+                            )
+                            .await
+                            .unwrap_or_else(|e| AgentResult::failure(format!("Error: {}", e)));
+
+                        attempts.push(Attempt {
+                            success: result.success,
+                            output: result.output.clone(),
+                        });
+
+                        if result.success || attempts.len() > max_retries {
+                            break (idx, forked, forked_at, attempts, result);
+                        }
+
+                        warn!(
+                            "Subtask {} failed (attempt {}), retrying",
+                            idx,
+                            attempts.len()
+                        );
+                        attempt_input = format!(
+                            "{}\n\nThe previous attempt failed with:\n{}\n\nAddress this failure in this attempt.",
+                            input,
+                            truncate(&result.output, max_chars)
+                        );
+                    }
+                });
+            }
+
+            let Some((idx, forked, forked_at, subtask_attempts, result)) = running.next().await
+            else {
+                break;
+            };
+
+            debug!("Subtask {} finished (success: {})", idx, result.success);
+            tools.activity().emit(Event::SubtaskFinished {
+                index: idx,
+                agent: subtasks[idx].agent_type.clone(),
+                success: result.success,
+            });
+            context.merge(forked, forked_at);
+            dag.finish(idx, result.success);
+            attempts[idx] = subtask_attempts;
+            let failed = !result.success;
+            results[idx] = Some(result);
+
+            if !failed {
+                continue;
+            }
+
+            match options.failure_policy {
+                FailurePolicy::Continue => {}
+                FailurePolicy::FailFast => {
+                    info!("Subtask {} failed, not starting further subtasks", idx);
+                    stopped_at.get_or_insert(idx);
+                }
+                FailurePolicy::Replan if replans >= options.max_replans => {
+                    warn!("Replan limit reached, continuing without replanning");
+                }
+                FailurePolicy::Replan => {
+                    replans += 1;
+                    match self.replan(&subtasks, &dag, &results, llm.clone()).await {
+                        Ok(revised) => {
+                            info!(
+                                "Replanned after subtask {} failed: {} new subtask(s)",
+                                idx,
+                                revised.len()
+                            );
+                            for replaced in dag.replace_pending() {
+                                results[replaced] =
+                                    Some(AgentResult::failure("Replaced by a revised plan"));
+                            }
+                            let first_added = subtasks.len();
+                            for subtask in revised {
+                                dag.push(subtask.dependencies.clone());
+                                subtasks.push(subtask);
+                                results.push(None);
+                                attempts.push(Vec::new());
+                                replanned.push(None);
+                            }
+                            replanned[idx] = Some(first_added..subtasks.len());
+                        }
+                        Err(e) => warn!("Replanning failed, continuing: {}", e),
+                    }
+                }
+            }
+        }
+
+        for idx in dag.unresolved() {
+            results[idx] = Some(AgentResult::failure(match stopped_at {
+                Some(failed) => format!("Not run: stopped after subtask {} failed", failed),
+                None => "Not run: dependency cycle or unknown dependency".to_string(),
+            }));
+        }
+
+        // A replanned failure is made good only by a revision that added work
+        // and all of it succeeded. Added subtasks come later, so go backwards.
+        let mut made_good = vec![false; subtasks.len()];
+        for idx in (0..subtasks.len()).rev() {
+            made_good[idx] = match dag.status(idx) {
+                SubtaskStatus::Succeeded | SubtaskStatus::Replaced => true,
+                SubtaskStatus::Failed => replanned[idx]
+                    .clone()
+                    .is_some_and(|mut added| !added.is_empty() && added.all(|i| made_good[i])),
+                _ => false,
+            };
+        }
 
-        let mut results: Vec<AgentResult> = Vec::new();
         let mut all_success = true;
+        let mut summary = Vec::new();
+        let mut reports = Vec::new();
 
-        for (idx, subtask) in subtasks.iter().enumerate() {
-            for dep_idx in &subtask.dependencies {
-                if *dep_idx >= idx {
-                    return Ok(AgentResult::failure(
-                        "Invalid dependency: forward reference",
+        for (idx, (result, subtask_attempts)) in results.into_iter().zip(attempts).enumerate() {
+            let status = match dag.status(idx) {
+                SubtaskStatus::Succeeded => "SUCCESS",
+                SubtaskStatus::Skipped => "SKIPPED",
+                SubtaskStatus::Replaced => "REPLACED",
+                SubtaskStatus::Pending => "NOT RUN",
+                SubtaskStatus::Failed if replanned[idx].is_some() => "FAILED (replanned)",
+                _ => "FAILED",
+            };
+            all_success &= made_good[idx];
+
+            let mut line = format!("Subtask {}: {}", idx, status);
+            if subtask_attempts.len() > 1 {
+                line.push_str(&format!(" after {} attempts", subtask_attempts.len()));
+                for (n, attempt) in subtask_attempts.iter().enumerate() {
+                    line.push_str(&format!(
+                        "\n  attempt {}: {} - {}",
+                        n + 1,
+                        if attempt.success { "ok" } else { "failed" },
+                        attempt.output.lines().next().unwrap_or_default()
                     ));
                 }
-                if !results[*dep_idx].success {
-                    return Ok(AgentResult::failure(format!(
-                        "Dependency {} failed, skipping subtask {}",
-                        dep_idx, idx
-                    )));
-                }
             }
+            summary.push(line);
 
-            let result = self
-                .execute_subtask(
-                    subtask,
-                    context,
-                    llm.clone(),
-                    tools.clone(),
-                    context_mgr.clone(),
+            let (output, metadata) = result.map(|r| (r.output, r.metadata)).unwrap_or_default();
+            reports.push(SubtaskReport {
+                index: idx,
+                description: subtasks[idx].description.clone(),
+                agent_type: subtasks[idx].agent_type.clone(),
+                status: status.to_string(),
+                output,
+                metadata,
+                attempts: subtask_attempts,
+            });
+        }
+
+        let summary = summary.join("\n");
+        let output = match self.synthesize(task, &reports, llm).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                warn!("Could not synthesize a final answer: {}", e);
+                summary.clone()
+            }
+        };
+
+        let result = AgentResult {
+            success: all_success,
+            output,
+            metadata: HashMap::new(),
+        };
+
+        Ok(result
+            .with_metadata("summary", summary)
+            .with_metadata("subtasks", serde_json::to_string(&reports)?)
+            .with_metadata("replans", replans.to_string()))
+    }
+
+    /// Write the final answer for `task` from what the subtasks produced. A single
+    /// successful subtask's output is already the answer.
+    async fn synthesize(
+        &self,
+        task: &str,
+        reports: &[SubtaskReport],
+        llm: Arc<dyn LlmClient>,
+    ) -> Result<String> {
+        if let [report] = reports {
+            if report.status == "SUCCESS" {
+                return Ok(report.output.clone());
+            }
+        }
+
+        let results = reports
+            .iter()
+            .map(|report| {
+                format!(
+                    "[{}] ({}) {} - {}\n{}",
+                    report.index,
+                    report.agent_type,
+                    report.description,
+                    report.status,
+                    truncate(&report.output, self.options.max_dependency_chars)
                 )
-                .await?;
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
 
-            all_success &= result.success;
-            results.push(result);
+        let system_prompt = r#"You write the final answer for a task that several agents worked on.
+Answer the task directly using the subtask results:
+- Include the relevant outputs (code, file contents, command output) rather than describing them
+- List any files that were created or changed
+- Say plainly which parts failed or were skipped and why
+- Suggest next steps when something is left to do
+Only use information from the results. Do not mention subtask numbers or agents unless it helps."#;
+
+        let messages = vec![
+            Message::system(system_prompt),
+            Message::user(format!("Task: {}\n\nSubtask results:\n\n{}", task, results)),
+        ];
+
+        let answer = llm.chat_with_history(messages, "default").await?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            anyhow::bail!("Empty synthesis response");
         }
 
-        let summary = results
+        Ok(answer.to_string())
+    }
+
+    /// Ask the LLM for subtasks to replace the pending part of the plan, given
+    /// what has run so far
+    async fn replan(
+        &self,
+        subtasks: &[SubTask],
+        dag: &DagState,
+        results: &[Option<AgentResult>],
+        llm: Arc<dyn LlmClient>,
+    ) -> Result<Vec<SubTask>> {
+        let progress = subtasks
             .iter()
             .enumerate()
-            .map(|(idx, r)| {
-                format!(
-                    "Subtask {}: {}",
+            .map(|(idx, subtask)| {
+                let mut line = format!(
+                    "[{}] ({}) {} - {:?}",
                     idx,
-                    if r.success { "SUCCESS" } else { "FAILED" }
-                )
+                    subtask.agent_type,
+                    subtask.description,
+                    dag.status(idx)
+                );
+                if let Some(result) = &results[idx] {
+                    line.push_str(&format!("\n    output: {}", truncate(&result.output, 1000)));
+                }
+                line
             })
             .collect::<Vec<_>>()
             .join("\n");
 
-        Ok(AgentResult {
-            success: all_success,
-            output: summary,
-            metadata: std::collections::HashMap::new(),
-        })
+        let system_prompt = format!(
+            r#"You revise task plans after a subtask has failed.
+
+Available agent types: {}
+
+Subtasks marked Pending have not started yet and will be replaced by the subtasks you return.
+New subtasks are numbered from {} in the order you return them. Their dependencies may refer
+to subtasks that Succeeded or to other new subtasks.
+
+Return ONLY a valid JSON array in this exact format (an empty array drops the remaining work):
+[{{"description": "what needs to be done", "agent_type": "file", "dependencies": [0]}}]"#,
+            self.registry.names().join(", "),
+            subtasks.len()
+        );
+
+        let messages = vec![
+            Message::system(system_prompt),
+            Message::user(format!("Progress so far:\n{}", progress)),
+        ];
+
+        let response = llm.chat_with_history(messages, "default").await?;
+        debug!("Replan response: {}", response);
+
+        let (Some(start), Some(end)) = (response.find('['), response.rfind(']')) else {
+            anyhow::bail!("No JSON array in replan response");
+        };
+        if start > end {
+            anyhow::bail!("No JSON array in replan response");
+        }
+
+        let revised: Vec<SubTask> = serde_json::from_str(&response[start..=end])?;
+
+        let mut combined = subtasks.to_vec();
+        combined.extend(revised.iter().cloned());
+        let problems = validate_subtasks(&combined, &self.registry.names());
+        if !problems.is_empty() {
+            anyhow::bail!("Revised plan is invalid: {}", problems.join("; "));
+        }
+
+        Ok(revised)
+    }
+
+    /// Build the input for subtask `idx`: its description with `{{N.output}}` and
+    /// `{{N.metadata.KEY}}` placeholders filled in, followed by the results of any
+    /// dependencies the description did not reference explicitly
+    fn subtask_input(
+        &self,
+        idx: usize,
+        subtasks: &[SubTask],
+        results: &[Option<AgentResult>],
+    ) -> String {
+        let subtask = &subtasks[idx];
+        let dependency = |n: usize| {
+            subtask
+                .dependencies
+                .contains(&n)
+                .then(|| results.get(n).and_then(Option::as_ref))
+                .flatten()
+        };
+
+        let mut referenced = Vec::new();
+        let mut input = render_templates(&subtask.description, |n, field| {
+            let result = dependency(n)?;
+            referenced.push(n);
+            let value = match field {
+                "output" => result.output.clone(),
+                field => result
+                    .metadata
+                    .get(field.strip_prefix("metadata.")?)?
+                    .clone(),
+            };
+            Some(truncate(&value, self.options.max_dependency_chars))
+        });
+
+        let mut unreferenced: Vec<usize> = subtask
+            .dependencies
+            .iter()
+            .copied()
+            .filter(|n| !referenced.contains(n))
+            .collect();
+        unreferenced.sort_unstable();
+        unreferenced.dedup();
+
+        let sections: Vec<String> = unreferenced
+            .into_iter()
+            .filter_map(|n| {
+                let result = dependency(n)?;
+                let mut section = format!(
+                    "[Subtask {}: {}]\n{}",
+                    n,
+                    subtasks[n].description.lines().next().unwrap_or_default(),
+                    result.output
+                );
+                let mut keys: Vec<&String> = result.metadata.keys().collect();
+                keys.sort();
+                for key in keys {
+                    section.push_str(&format!("\n{}: {}", key, result.metadata[key]));
+                }
+                Some(truncate(&section, self.options.max_dependency_chars))
+            })
+            .collect();
+
+        if !sections.is_empty() {
+            input.push_str("\n\nResults from the subtasks this depends on:\n\n");
+            input.push_str(&sections.join("\n\n"));
+        }
+
+        input
     }
 }
 
-#[derive(Debug, serde::Deserialize)]
-struct SubTask {
-    description: String,
-    agent_type: String,
-    dependencies: Vec<usize>,
+/// Replace `{{N.field}}` placeholders using `lookup(N, field)`.
+/// Placeholders the lookup cannot resolve are left as they are.
+fn render_templates(text: &str, mut lookup: impl FnMut(usize, &str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 2];
+        let inner = placeholder[2..placeholder.len() - 2].trim();
+
+        let value = inner
+            .split_once('.')
+            .and_then(|(n, field)| lookup(n.trim().parse().ok()?, field.trim()));
+
+        out.push_str(&rest[..start]);
+        out.push_str(value.as_deref().unwrap_or(placeholder));
+        rest = &rest[start + placeholder.len()..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+pub(crate) fn truncate(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n... (truncated {} bytes)",
+        &text[..end],
+        text.len() - end
+    )
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubTask {
+    pub description: String,
+    pub agent_type: String,
+    #[serde(default)]
+    pub dependencies: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::ChatRequest;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Returns the given responses in order, then fails
+    struct ScriptedLlm(std::sync::Mutex<Vec<&'static str>>);
+
+    impl ScriptedLlm {
+        fn new(responses: &[&'static str]) -> Self {
+            Self(std::sync::Mutex::new(
+                responses.iter().rev().copied().collect(),
+            ))
+        }
+    }
+
+    #[async_trait]
+    impl LlmClient for ScriptedLlm {
+        async fn chat(&self, _request: ChatRequest) -> Result<String> {
+            self.chat_with_history(Vec::new(), "default").await
+        }
+
+        async fn chat_with_history(&self, _messages: Vec<Message>, _model: &str) -> Result<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .pop()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("no LLM response left"))
+        }
+    }
+
+    /// Sleeps briefly and records peak concurrency. Fails tasks starting with "fail",
+    /// and tasks containing "flaky" until told about the previous failure.
+    struct SleepAgent {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Agent for SleepAgent {
+        fn name(&self) -> &str {
+            "sleep"
+        }
+
+        fn description(&self) -> &str {
+            "Test agent"
+        }
+
+        fn capabilities(&self) -> Vec<AgentCapability> {
+            vec![]
+        }
+
+        fn can_handle(&self, _task: &str) -> bool {
+            true
+        }
+
+        async fn execute(
+            &self,
+            task: &str,
+            context: &mut AgentContext,
+            _llm: Arc<dyn LlmClient>,
+            _tools: Arc<ToolExecutor>,
+            _context_mgr: Arc<ContextManager>,
+        ) -> Result<AgentResult> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            context.add_message(Message::assistant(format!("ran {}", task)));
+            let flaky = task.contains("flaky") && !task.contains("previous attempt failed");
+            if task.starts_with("fail") || flaky {
+                Ok(AgentResult::failure(task))
+            } else {
+                Ok(AgentResult::success(task))
+            }
+        }
+    }
+
+    fn subtask(description: &str, dependencies: Vec<usize>) -> SubTask {
+        SubTask {
+            description: description.to_string(),
+            agent_type: "sleep".to_string(),
+            dependencies,
+        }
+    }
+
+    fn options(
+        max_parallel: usize,
+        max_retries: usize,
+        policy: FailurePolicy,
+    ) -> CoordinatorOptions {
+        CoordinatorOptions {
+            max_parallel,
+            max_retries,
+            failure_policy: policy,
+            ..Default::default()
+        }
+    }
+
+    async fn run_with(
+        options: CoordinatorOptions,
+        llm: ScriptedLlm,
+        subtasks: &[SubTask],
+    ) -> (AgentResult, AgentContext, usize) {
+        let agent = Arc::new(SleepAgent {
+            running: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        });
+        let mut registry = AgentRegistry::new();
+        registry.register(agent.clone());
+        let coordinator = CoordinatorAgent::new(Arc::new(registry)).with_options(options);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut context = AgentContext::new(".");
+        let result = coordinator
+            .execute_subtasks(
+                "test task",
+                subtasks,
+                &mut context,
+                Arc::new(llm),
+                Arc::new(ToolExecutor::new(dir.path())),
+                Arc::new(ContextManager::new(dir.path().join("db")).unwrap()),
+            )
+            .await
+            .unwrap();
+
+        (result, context, agent.peak.load(Ordering::SeqCst))
+    }
+
+    async fn run(max_parallel: usize, subtasks: &[SubTask]) -> (AgentResult, AgentContext, usize) {
+        let options = options(max_parallel, 0, FailurePolicy::Continue);
+        run_with(options, ScriptedLlm::new(&[]), subtasks).await
+    }
+
+    #[test]
+    fn test_infer_agent_keyword() {
+        let coordinator = CoordinatorAgent::new(Arc::new(AgentRegistry::new()));
+        let infer = |task| coordinator.infer_agent_keyword(task);
+        assert_eq!(infer("Run the test suite"), "test");
+        assert_eq!(infer("run cargo test and report failures"), "test");
+        assert_eq!(infer("Check that the specs still pass"), "test");
+        assert_eq!(infer("write a test for parse_args"), "code");
+        assert_eq!(infer("implement the spec in README"), "code");
+        assert_eq!(infer("inspect the latest config"), "analysis");
+        assert_eq!(infer("find the specific handler"), "search");
+        assert_eq!(infer("run the migrations"), "shell");
+    }
+
+    #[tokio::test]
+    async fn test_independent_subtasks_run_in_parallel_up_to_limit() {
+        let subtasks = [
+            subtask("a", vec![]),
+            subtask("b", vec![]),
+            subtask("c", vec![]),
+            subtask("d", vec![0, 1, 2]),
+        ];
+
+        let (result, context, peak) = run(2, &subtasks).await;
+        assert!(result.success);
+        assert_eq!(peak, 2);
+        assert_eq!(context.conversation_history.len(), 4);
+        assert!(context.conversation_history[3].content.starts_with("ran d"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_dependency_skips_dependents() {
+        let subtasks = [
+            subtask("fail first", vec![]),
+            subtask("needs first", vec![0]),
+            subtask("independent", vec![]),
+        ];
+
+        let (result, context, _) = run(4, &subtasks).await;
+        assert!(!result.success);
+        assert_eq!(
+            result.output,
+            "Subtask 0: FAILED\nSubtask 1: SKIPPED\nSubtask 2: SUCCESS"
+        );
+        assert_eq!(context.conversation_history.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_plan_runs_nothing() {
+        let mut unknown = subtask("push the branch", vec![]);
+        unknown.agent_type = "git".to_string();
+        let subtasks = [subtask("read", vec![]), unknown, subtask("cycle", vec![2])];
+
+        let (result, context, _) = run(4, &subtasks).await;
+        assert!(!result.success);
+        assert_eq!(
+            result.output,
+            "Invalid plan, nothing was run:\nSubtask 1: unknown agent 'git'\nSubtask 2: depends on itself"
+        );
+        assert!(context.conversation_history.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_decompose_asks_for_corrections() {
+        let mut registry = AgentRegistry::new();
+        registry.register(Arc::new(SleepAgent {
+            running: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }));
+        let coordinator = CoordinatorAgent::new(Arc::new(registry));
+
+        let llm = Arc::new(ScriptedLlm::new(&[
+            r#"[{"description": "commit", "agent_type": "git", "dependencies": []}]"#,
+            r#"[{"description": "commit", "agent_type": "sleep", "dependencies": []}]"#,
+        ]));
+        let subtasks = coordinator
+            .decompose_task("commit", &AgentContext::new("."), llm)
+            .await
+            .unwrap();
+        assert_eq!(subtasks[0].agent_type, "sleep");
+
+        let invalid = r#"[{"description": "a", "agent_type": "sleep", "dependencies": [0]}]"#;
+        let llm = Arc::new(ScriptedLlm::new(&[invalid, invalid, invalid]));
+        let err = coordinator
+            .decompose_task("a", &AgentContext::new("."), llm)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Subtask 0: depends on itself"));
+    }
+
+    #[tokio::test]
+    async fn test_dependency_outputs_reach_dependents() {
+        let subtasks = [
+            subtask("read config", vec![]),
+            subtask("analyze {{0.output}} and {{0.metadata.missing}}", vec![0]),
+            subtask("update config", vec![0, 1]),
+        ];
+
+        let (result, context, _) = run(4, &subtasks).await;
+        assert!(result.success);
+        assert_eq!(
+            context.conversation_history[1].content,
+            "ran analyze read config and {{0.metadata.missing}}"
+        );
+        assert!(context.conversation_history[2]
+            .content
+            .contains("[Subtask 0: read config]\nread config"));
+        assert!(context.conversation_history[2]
+            .content
+            .contains("[Subtask 1: analyze"));
+    }
+
+    #[tokio::test]
+    async fn test_retries_feed_back_the_failure() {
+        let subtasks = [
+            subtask("flaky step", vec![]),
+            subtask("fail always", vec![]),
+        ];
+        let options = options(4, 1, FailurePolicy::Continue);
+
+        let (result, _, _) = run_with(options, ScriptedLlm::new(&[]), &subtasks).await;
+        assert!(!result.success);
+        assert!(
+            result.output.starts_with(
+                "Subtask 0: SUCCESS after 2 attempts\n  attempt 1: failed - flaky step"
+            ),
+            "{}",
+            result.output
+        );
+        assert!(result.output.contains("Subtask 1: FAILED after 2 attempts"));
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_stops_scheduling() {
+        let subtasks = [
+            subtask("fail first", vec![]),
+            subtask("later", vec![0]),
+            subtask("other", vec![1]),
+        ];
+        let fail_fast = options(1, 0, FailurePolicy::FailFast);
+
+        let (result, _, _) = run_with(fail_fast, ScriptedLlm::new(&[]), &subtasks).await;
+        assert!(!result.success);
+        assert_eq!(
+            result.output,
+            "Subtask 0: FAILED\nSubtask 1: SKIPPED\nSubtask 2: SKIPPED"
+        );
+
+        let subtasks = [
+            subtask("fail first", vec![]),
+            subtask("independent", vec![]),
+        ];
+        let (result, context, _) = run_with(
+            options(1, 0, FailurePolicy::FailFast),
+            ScriptedLlm::new(&[]),
+            &subtasks,
+        )
+        .await;
+        assert_eq!(result.output, "Subtask 0: FAILED\nSubtask 1: NOT RUN");
+        assert_eq!(context.conversation_history.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replan_replaces_pending_subtasks() {
+        let subtasks = [
+            subtask("read config", vec![]),
+            subtask("fail to parse", vec![0]),
+            subtask("update config", vec![1]),
+        ];
+        let llm = ScriptedLlm::new(&[
+            r#"[{"description": "parse leniently", "agent_type": "sleep", "dependencies": [0]},
+                {"description": "update config", "agent_type": "sleep", "dependencies": [3]}]"#,
+        ]);
+
+        let (result, context, _) =
+            run_with(options(4, 0, FailurePolicy::Replan), llm, &subtasks).await;
+        assert!(result.success, "{}", result.output);
+        assert_eq!(
+            result.output,
+            "Subtask 0: SUCCESS\nSubtask 1: FAILED (replanned)\nSubtask 2: REPLACED\nSubtask 3: SUCCESS\nSubtask 4: SUCCESS"
+        );
+        assert_eq!(result.metadata["replans"], "1");
+        assert_eq!(context.conversation_history.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_replan_that_drops_the_work_is_a_failure() {
+        let subtasks = [
+            subtask("fail to parse", vec![]),
+            subtask("update config", vec![0]),
+        ];
+        let llm = ScriptedLlm::new(&["[]"]);
+
+        let (result, _, _) = run_with(options(4, 0, FailurePolicy::Replan), llm, &subtasks).await;
+        assert!(!result.success);
+        assert_eq!(
+            result.output,
+            "Subtask 0: FAILED (replanned)\nSubtask 1: REPLACED"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_final_answer_is_synthesized_from_subtask_results() {
+        let subtasks = [
+            subtask("read config", vec![]),
+            subtask("fail parse", vec![0]),
+        ];
+        let options = options(4, 0, FailurePolicy::Continue);
+
+        let llm = ScriptedLlm::new(&["  The config was read but could not be parsed.\n"]);
+        let (result, _, _) = run_with(options.clone(), llm, &subtasks).await;
+        assert!(!result.success);
+        assert_eq!(
+            result.output,
+            "The config was read but could not be parsed."
+        );
+        assert_eq!(
+            result.metadata["summary"],
+            "Subtask 0: SUCCESS\nSubtask 1: FAILED"
+        );
+
+        let reports = SubtaskReport::from_result(&result).unwrap();
+        assert_eq!(reports[0].output, "read config");
+        assert_eq!(reports[1].status, "FAILED");
+        assert!(reports[1]
+            .render()
+            .starts_with("── Subtask 1 (sleep): FAILED ──\nfail parse"));
+
+        let (single, _, _) = run_with(
+            options,
+            ScriptedLlm::new(&[]),
+            &[subtask("read config", vec![])],
+        )
+        .await;
+        assert_eq!(single.output, "read config");
+    }
+
+    #[test]
+    fn test_render_templates_and_truncate() {
+        let rendered =
+            render_templates("a {{0.output}} b {{ 1.metadata.path }} {{x}} {{", |n, f| {
+                Some(format!("<{} {}>", n, f))
+            });
+        assert_eq!(rendered, "a <0 output> b <1 metadata.path> {{x}} {{");
+
+        assert_eq!(truncate("héllo", 2), "h\n... (truncated 5 bytes)");
+        assert_eq!(truncate("short", 10), "short");
+    }
 }
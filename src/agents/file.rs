@@ -32,14 +32,16 @@ impl Agent for FileAgent {
         &self.name
     }
 
+    fn description(&self) -> &str {
+        "Read, write, list, copy or delete individual files"
+    }
+
     fn capabilities(&self) -> Vec<AgentCapability> {
         vec![AgentCapability::FileOperations]
     }
 
     fn can_handle(&self, task: &str) -> bool {
-        let keywords = [
-            "read", "write", "file", "create", "delete", "copy", "move", "search",
-        ];
+        let keywords = ["read", "write", "file", "create", "delete", "copy", "move"];
         keywords.iter().any(|kw| task.to_lowercase().contains(kw))
     }
 
@@ -65,13 +67,13 @@ PATH: <file or directory path>
 CONTENT: <for write operations only>
 PATTERN: <for search operations only>"#;
 
-        let messages = vec![
-            Message::system(system_prompt),
-            Message::user(format!(
+        let messages = context.messages(
+            system_prompt,
+            format!(
                 "Task: {}\nWorking directory: {}",
                 task, context.working_directory
-            )),
-        ];
+            ),
+        );
 
         let response = llm.chat_with_history(messages, "default").await?;
 
@@ -81,25 +83,25 @@ PATTERN: <for search operations only>"#;
         match operation.to_lowercase().as_str() {
             "read" => {
                 let content = tools.read_file(&path).await?;
-                context.add_message(format!("Read file: {}", path));
+                context.add_message(Message::assistant(format!("Read file: {}", path)));
                 Ok(AgentResult::success(content).with_metadata("path", path))
             }
             "write" => {
                 let content = self.extract_field(&response, "CONTENT");
                 tools.write_file(&path, &content).await?;
-                context.add_message(format!("Wrote file: {}", path));
+                context.add_message(Message::assistant(format!("Wrote file: {}", path)));
                 Ok(AgentResult::success(format!("File written to {}", path))
                     .with_metadata("path", path))
             }
             "search" => {
                 let pattern = self.extract_field(&response, "PATTERN");
                 let results = tools.search_files(&path, &pattern).await?;
-                context.add_message(format!("Searched in: {}", path));
+                context.add_message(Message::assistant(format!("Searched in: {}", path)));
                 Ok(AgentResult::success(results.join("\n")).with_metadata("pattern", pattern))
             }
             "list" => {
                 let files = tools.list_files(&path).await?;
-                context.add_message(format!("Listed directory: {}", path));
+                context.add_message(Message::assistant(format!("Listed directory: {}", path)));
                 Ok(AgentResult::success(files.join("\n")).with_metadata("path", path))
             }
             _ => Ok(AgentResult::failure(format!(
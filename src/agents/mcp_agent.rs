@@ -1,22 +1,29 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use super::base::{Agent, AgentCapability, AgentContext, AgentResult};
 use crate::context::ContextManager;
 use crate::llm::{LlmClient, Message};
-use crate::mcp::McpClient;
+use crate::mcp::client::{qualified_name, resolve_tool};
+use crate::mcp::protocol::Tool;
+use crate::mcp::{schema, McpClient};
 use crate::tools::ToolExecutor;
 
-#[allow(dead_code)]
+/// How many times the model is asked to fix invalid tool calls
+const MAX_ARGUMENT_CORRECTIONS: usize = 2;
+
+/// How many rounds of `RESOURCE:` reads the model gets before it must call tools
+const MAX_RESOURCE_ROUNDS: usize = 2;
+
 pub struct McpAgent {
     name: String,
     mcp_client: Arc<McpClient>,
 }
 
-#[allow(dead_code)]
 impl McpAgent {
     pub fn new(mcp_client: Arc<McpClient>) -> Self {
         Self {
@@ -32,6 +39,10 @@ impl Agent for McpAgent {
         &self.name
     }
 
+    fn description(&self) -> &str {
+        "Call tools exposed by external MCP servers"
+    }
+
     fn capabilities(&self) -> Vec<AgentCapability> {
         vec![AgentCapability::TaskOrchestration]
     }
@@ -52,106 +63,344 @@ impl Agent for McpAgent {
         debug!("MCP agent executing: {}", task);
 
         let all_tools = self.mcp_client.list_all_tools().await?;
+        let all_resources = self.mcp_client.list_all_resources().await;
+        let unavailable_servers = self.mcp_client.unavailable_servers().await;
+        let mut unavailable: Vec<String> = unavailable_servers
+            .iter()
+            .map(|(server, error)| format!("MCP server '{}' is unavailable: {}", server, error))
+            .collect();
+        unavailable.sort();
+
+        if all_tools.values().all(|tools| tools.is_empty())
+            && all_resources.values().all(|resources| resources.is_empty())
+        {
+            let mut message = "No MCP tools or resources are available".to_string();
+            if !unavailable.is_empty() {
+                message.push_str(&format!("\n{}", unavailable.join("\n")));
+            }
+            return Ok(AgentResult::failure(message));
+        }
+
+        let mut servers: Vec<&String> = all_tools.keys().collect();
+        servers.sort();
 
         let mut tools_description = String::new();
-        for (server_name, tools) in &all_tools {
+        for server_name in servers {
             tools_description.push_str(&format!("\nServer '{}':\n", server_name));
-            for tool in tools {
-                tools_description.push_str(&format!("  - {}: {}\n", tool.name, tool.description));
+            for tool in &all_tools[server_name] {
+                tools_description.push_str(&format!(
+                    "\n  {}: {}\n",
+                    qualified_name(server_name, &tool.name),
+                    tool.description
+                ));
+                for line in schema::render(&tool.input_schema).lines() {
+                    tools_description.push_str(&format!("    {}\n", line));
+                }
+            }
+            for resource in all_resources.get(server_name).into_iter().flatten() {
+                tools_description.push_str(&format!(
+                    "\n  Resource {} ({}){}\n",
+                    resource.uri,
+                    resource.name,
+                    resource
+                        .description
+                        .as_deref()
+                        .map(|d| format!(": {}", d))
+                        .unwrap_or_default()
+                ));
             }
         }
 
         let system_prompt = format!(
             r#"You are an MCP tool orchestration agent.
-You have access to the following MCP tools:
+You have access to the following MCP tools, each with its arguments, and resources:
 {}
 
 When asked to perform a task:
 1. Determine which MCP tool(s) to use
-2. Provide the tool name and arguments in JSON format
+2. Provide the tool name and its arguments as a JSON object matching the listed arguments
 
 Response format:
 TOOL: <tool_name>
-ARGUMENTS: <json_arguments>
+ARGUMENTS: <json_object>
+
+If multiple tools are needed, repeat the TOOL/ARGUMENTS pair for each.
 
-If multiple tools are needed, provide them on separate lines."#,
+To read resources before choosing tools, reply with only RESOURCE: <uri> lines;
+their contents will be sent back to you."#,
             tools_description
         );
 
-        let messages = vec![
-            Message::system(system_prompt),
-            Message::user(format!("Task: {}", task)),
-        ];
+        let mut messages = context.messages(system_prompt, format!("Task: {}", task));
+        let mut corrections = 0;
+        let mut resource_rounds = 0;
+        let mut attached = Vec::new();
+
+        let tool_calls = loop {
+            let response = llm.chat_with_history(messages.clone(), "default").await?;
+            let tool_calls = self.parse_tool_calls(&response);
+
+            let uris = parse_resource_requests(&response);
+            if tool_calls.is_empty() && !uris.is_empty() && resource_rounds < MAX_RESOURCE_ROUNDS {
+                resource_rounds += 1;
+                let mut contents = Vec::new();
+                for uri in uris {
+                    info!("Reading MCP resource: {}", uri);
+                    let section = match self.mcp_client.read_resource(&uri).await {
+                        Ok(result) => format!("[Resource {}]\n{}", uri, result.text()),
+                        Err(e) => format!("[Resource {}]\nCould not be read: {}", uri, e),
+                    };
+                    contents.push(section);
+                }
+
+                messages.push(Message::assistant(response));
+                messages.push(Message::user(format!(
+                    "{}\n\nNow reply with the TOOL/ARGUMENTS pairs for the task, if any are needed.",
+                    contents.join("\n\n")
+                )));
+                attached.extend(contents);
+                continue;
+            }
+
+            let problems = self.check_tool_calls(&tool_calls, &all_tools);
+            if problems.is_empty() {
+                break tool_calls;
+            }
 
-        let response = llm.chat_with_history(messages, "default").await?;
+            if corrections >= MAX_ARGUMENT_CORRECTIONS {
+                return Ok(AgentResult::failure(format!(
+                    "Tool calls were still invalid after {} corrections:\n{}",
+                    corrections,
+                    problems.join("\n")
+                )));
+            }
+            corrections += 1;
+            warn!(
+                "Invalid MCP tool calls, asking for a correction: {:?}",
+                problems
+            );
 
-        let tool_calls = self.parse_tool_calls(&response);
+            messages.push(Message::assistant(response));
+            messages.push(Message::user(format!(
+                "These tool calls cannot be sent:\n{}\n\n\
+                 Reply with all TOOL/ARGUMENTS pairs again, corrected.",
+                problems.join("\n")
+            )));
+        };
 
         let mut results = Vec::new();
-        for (tool_name, args) in tool_calls {
-            info!("Calling MCP tool: {} with args: {:?}", tool_name, args);
+        for call in tool_calls {
+            let Ok(args) = call.arguments else {
+                continue;
+            };
+            info!("Calling MCP tool: {} with args: {}", call.name, args);
 
-            match self.mcp_client.call_tool(&tool_name, args).await {
+            match self.mcp_client.call_tool(&call.name, args).await {
+                Ok(result) if result.is_error => {
+                    results.push(format!("Tool '{}' failed: {}", call.name, result.text()));
+                }
                 Ok(result) => {
-                    let result_str = serde_json::to_string_pretty(&result)?;
-                    results.push(format!("Tool '{}' result:\n{}", tool_name, result_str));
+                    results.push(format!("Tool '{}' result:\n{}", call.name, result.text()));
                 }
                 Err(e) => {
-                    results.push(format!("Tool '{}' failed: {}", tool_name, e));
+                    results.push(format!("Tool '{}' failed: {}", call.name, e));
                 }
             }
         }
 
-        context.add_message(format!("MCP task: {}", task));
-        context.add_message(format!("Results: {}", results.join("\n\n")));
+        context.add_message(Message::assistant(format!("MCP task: {}", task)));
+        context.add_message(Message::assistant(format!(
+            "Results: {}",
+            results.join("\n\n")
+        )));
 
-        if results.is_empty() {
-            Ok(AgentResult::failure("No MCP tools were called"))
+        if results.is_empty() && !attached.is_empty() {
+            // The task only needed resource contents
+            attached.extend(unavailable);
+            Ok(AgentResult::success(attached.join("\n\n")))
+        } else if results.is_empty() {
+            results.push("No MCP tools were called".to_string());
+            results.extend(unavailable);
+            Ok(AgentResult::failure(results.join("\n")))
         } else {
+            results.extend(unavailable);
             Ok(AgentResult::success(results.join("\n\n")))
         }
     }
 }
 
-#[allow(dead_code)]
 impl McpAgent {
-    fn parse_tool_calls(
-        &self,
-        response: &str,
-    ) -> Vec<(String, HashMap<String, serde_json::Value>)> {
-        let mut calls = Vec::new();
-        let lines: Vec<&str> = response.lines().collect();
-
-        let mut i = 0;
-        while i < lines.len() {
-            if lines[i].starts_with("TOOL:") {
-                let tool_name = lines[i]
-                    .strip_prefix("TOOL:")
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
+    /// Read `TOOL:` / `ARGUMENTS:` pairs. The arguments may span several
+    /// lines, up to the next `TOOL:` line, and default to `{}`.
+    fn parse_tool_calls(&self, response: &str) -> Vec<ToolCallRequest> {
+        let mut calls: Vec<(String, String)> = Vec::new();
 
-                if i + 1 < lines.len() && lines[i + 1].starts_with("ARGUMENTS:") {
-                    let args_str = lines[i + 1].strip_prefix("ARGUMENTS:").unwrap_or("{}");
+        for line in response.lines() {
+            let trimmed = line.trim();
+            if let Some(name) = trimmed.strip_prefix("TOOL:") {
+                calls.push((name.trim().trim_matches('`').to_string(), String::new()));
+            } else if let Some((_, args)) = calls.last_mut() {
+                let text = trimmed.strip_prefix("ARGUMENTS:").unwrap_or(line);
+                args.push_str(text);
+                args.push('\n');
+            }
+        }
 
-                    if let Ok(args) =
-                        serde_json::from_str::<HashMap<String, serde_json::Value>>(args_str)
-                    {
-                        calls.push((tool_name, args));
-                    } else {
-                        calls.push((tool_name, HashMap::new()));
+        calls
+            .into_iter()
+            .map(|(name, args)| {
+                let args = args
+                    .trim()
+                    .trim_start_matches("```json")
+                    .trim_matches('`')
+                    .trim();
+                let arguments = if args.is_empty() {
+                    Ok(Value::Object(Default::default()))
+                } else {
+                    match serde_json::from_str::<Value>(args) {
+                        Ok(value) if value.is_object() => Ok(value),
+                        Ok(_) => Err("ARGUMENTS must be a JSON object".to_string()),
+                        Err(e) => Err(format!("ARGUMENTS is not valid JSON: {}", e)),
                     }
+                };
+                ToolCallRequest { name, arguments }
+            })
+            .collect()
+    }
 
-                    i += 2;
-                } else {
-                    calls.push((tool_name, HashMap::new()));
-                    i += 1;
+    /// Problems that would make the calls fail: unknown tools, unparseable
+    /// arguments and arguments that do not match the tool's input schema
+    fn check_tool_calls(
+        &self,
+        calls: &[ToolCallRequest],
+        all_tools: &HashMap<String, Vec<Tool>>,
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        for call in calls {
+            let tool = match resolve_tool(all_tools, &call.name) {
+                Ok((_, tool)) => tool,
+                Err(e) => {
+                    problems.push(format!("TOOL {}: {}", call.name, e));
+                    continue;
                 }
-            } else {
-                i += 1;
+            };
+
+            match &call.arguments {
+                Ok(args) => {
+                    for error in schema::validate(&tool.input_schema, args) {
+                        problems.push(format!("TOOL {}: {}", call.name, error));
+                    }
+                }
+                Err(e) => problems.push(format!("TOOL {}: {}", call.name, e)),
             }
         }
 
-        calls
+        problems
+    }
+}
+
+/// URIs from `RESOURCE:` lines
+fn parse_resource_requests(response: &str) -> Vec<String> {
+    response
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("RESOURCE:"))
+        .map(|uri| uri.trim().trim_matches('`').to_string())
+        .filter(|uri| !uri.is_empty())
+        .collect()
+}
+
+/// A tool call as written by the model, with its arguments parsed if possible
+struct ToolCallRequest {
+    name: String,
+    arguments: std::result::Result<Value, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn agent() -> McpAgent {
+        McpAgent::new(Arc::new(McpClient::new()))
+    }
+
+    fn tools() -> HashMap<String, Vec<Tool>> {
+        let read_file: Tool = serde_json::from_value(json!({
+            "name": "read_file",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"path": {"type": "string"}},
+                "required": ["path"]
+            }
+        }))
+        .unwrap();
+        HashMap::from([("files".to_string(), vec![read_file])])
+    }
+
+    #[tokio::test]
+    async fn test_parse_multiline_arguments() {
+        let calls = agent().parse_tool_calls(
+            "I'll read it.\nTOOL: read_file\nARGUMENTS: {\n  \"path\": \"a.txt\"\n}\n\
+             TOOL: list_files\nTOOL: read_file\nARGUMENTS: [1]",
+        );
+
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].arguments, Ok(json!({"path": "a.txt"})));
+        assert_eq!(calls[1].name, "list_files");
+        assert_eq!(calls[1].arguments, Ok(json!({})));
+        assert_eq!(
+            calls[2].arguments,
+            Err("ARGUMENTS must be a JSON object".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_resource_requests() {
+        assert_eq!(
+            parse_resource_requests(
+                "Let me look first.\nRESOURCE: file:///notes.md\nRESOURCE: `db://users`"
+            ),
+            vec!["file:///notes.md", "db://users"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_calls() {
+        let agent = agent();
+        let calls = agent.parse_tool_calls(
+            "TOOL: read_file\nARGUMENTS: {\"path\": 3}\nTOOL: delete_file\nARGUMENTS: {}",
+        );
+
+        assert_eq!(
+            agent.check_tool_calls(&calls, &tools()),
+            vec![
+                "TOOL read_file: $.path: expected string, got number",
+                "TOOL delete_file: no such tool",
+            ]
+        );
+
+        let calls = agent.parse_tool_calls("TOOL: read_file\nARGUMENTS: {\"path\": \"a\"}");
+        assert!(agent.check_tool_calls(&calls, &tools()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_calls_with_shared_names() {
+        let agent = agent();
+        let mut all_tools = tools();
+        all_tools.insert("backup".to_string(), all_tools["files"].clone());
+
+        let calls = agent.parse_tool_calls(
+            "TOOL: read_file\nARGUMENTS: {\"path\": \"a\"}\n\
+             TOOL: backup__read_file\nARGUMENTS: {\"path\": \"a\"}\n\
+             TOOL: files/read_file\nARGUMENTS: {\"path\": \"a\"}",
+        );
+        assert_eq!(
+            agent.check_tool_calls(&calls, &all_tools),
+            vec![
+                "TOOL read_file: offered by several servers, use one of: \
+                 backup__read_file, files__read_file"
+            ]
+        );
     }
 }
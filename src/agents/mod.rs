@@ -1,14 +1,29 @@
 pub mod analysis;
 pub mod base;
 pub mod code;
+pub mod configured;
+pub mod conversation;
 pub mod coordinator;
 pub mod file;
 pub mod mcp_agent;
+pub mod review;
+pub mod router;
+pub mod scheduler;
+pub mod search;
 pub mod shell;
+pub mod test_runner;
 
 pub use analysis::AnalysisAgent;
-pub use base::{Agent, AgentContext, AgentRegistry, AgentResult};
+pub use base::{Agent, AgentCapability, AgentContext, AgentRegistry, AgentResult};
 pub use code::CodeAgent;
-pub use coordinator::CoordinatorAgent;
+pub use configured::ConfiguredAgent;
+pub use conversation::Conversation;
+pub use coordinator::{
+    CoordinatorAgent, CoordinatorOptions, FailurePolicy, SubTask, SubtaskReport,
+};
 pub use file::FileAgent;
+pub use review::ReviewAgent;
+pub use router::AgentRouter;
+pub use search::SearchAgent;
 pub use shell::ShellAgent;
+pub use test_runner::TestAgent;
@@ -0,0 +1,598 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use super::base::{Agent, AgentCapability, AgentContext, AgentResult};
+use super::coordinator::truncate;
+use crate::context::ContextManager;
+use crate::llm::{LlmClient, Message};
+use crate::tools::ToolExecutor;
+
+/// Lines of unchanged code shown around each hunk
+const CONTEXT_LINES: usize = 15;
+/// Upper bound on the diff and context sent to the model
+const MAX_REVIEW_CHARS: usize = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "info" | "note" | "suggestion" => Some(Severity::Info),
+            "warning" | "warn" | "minor" => Some(Severity::Warning),
+            "error" | "critical" | "major" | "high" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub file: String,
+    pub line_start: usize,
+    #[serde(default)]
+    pub line_end: usize,
+    #[serde(deserialize_with = "deserialize_severity")]
+    pub severity: Severity,
+    #[serde(default)]
+    pub category: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+fn deserialize_severity<'de, D>(deserializer: D) -> std::result::Result<Severity, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    Ok(Severity::parse(&value).unwrap_or(Severity::Warning))
+}
+
+/// What to review: the working tree, the index, a commit range or a single file
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReviewTarget {
+    WorkingTree,
+    Staged,
+    Range(String),
+    File(String),
+}
+
+impl ReviewTarget {
+    /// Parse the `lca review` argument: `--staged`, `<base>..<head>`, a file path or nothing
+    pub fn from_spec(spec: &str) -> Self {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            ReviewTarget::WorkingTree
+        } else if spec == "--staged" || spec == "--cached" {
+            ReviewTarget::Staged
+        } else if spec.contains("..") && !std::path::Path::new(spec).exists() {
+            ReviewTarget::Range(spec.to_string())
+        } else {
+            ReviewTarget::File(spec.to_string())
+        }
+    }
+
+    pub fn spec(&self) -> String {
+        match self {
+            ReviewTarget::WorkingTree => String::new(),
+            ReviewTarget::Staged => "--staged".to_string(),
+            ReviewTarget::Range(range) => range.clone(),
+            ReviewTarget::File(path) => path.clone(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ReviewTarget::WorkingTree => "uncommitted changes".to_string(),
+            ReviewTarget::Staged => "staged changes".to_string(),
+            ReviewTarget::Range(range) => format!("commits {}", range),
+            ReviewTarget::File(path) => format!("file {}", path),
+        }
+    }
+}
+
+pub struct ReviewAgent {
+    name: String,
+}
+
+impl ReviewAgent {
+    pub fn new() -> Self {
+        Self {
+            name: "review".to_string(),
+        }
+    }
+}
+
+impl Default for ReviewAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Agent for ReviewAgent {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "Review a diff or file and report structured findings"
+    }
+
+    fn capabilities(&self) -> Vec<AgentCapability> {
+        vec![AgentCapability::Analysis]
+    }
+
+    fn can_handle(&self, task: &str) -> bool {
+        let keywords = ["review", "diff", "pull request", "staged", "changes"];
+        keywords.iter().any(|kw| task.to_lowercase().contains(kw))
+    }
+
+    async fn execute(
+        &self,
+        task: &str,
+        context: &mut AgentContext,
+        llm: Arc<dyn LlmClient>,
+        tools: Arc<ToolExecutor>,
+        _context_mgr: Arc<ContextManager>,
+    ) -> Result<AgentResult> {
+        debug!("Review agent executing: {}", task);
+
+        let target = match context.metadata.get("review_target") {
+            Some(spec) => ReviewTarget::from_spec(spec),
+            None if task.to_lowercase().contains("staged") => ReviewTarget::Staged,
+            None => ReviewTarget::WorkingTree,
+        };
+
+        let material = match self.collect_material(&target, &tools).await {
+            Ok(material) => material,
+            Err(e) => {
+                return Ok(AgentResult::failure(format!(
+                    "Could not collect {} for review: {}",
+                    target.describe(),
+                    e
+                )))
+            }
+        };
+
+        if material.trim().is_empty() {
+            return Ok(AgentResult::success(format!(
+                "Nothing to review: no {}",
+                target.describe()
+            ))
+            .with_metadata("findings", "[]")
+            .with_metadata("target", target.spec()));
+        }
+
+        let system_prompt = r#"You are a meticulous code reviewer.
+Review the provided changes for bugs, security problems, performance issues, error handling gaps and maintainability concerns.
+Only report real problems in the changed code; do not restate what the code does.
+
+Return ONLY a valid JSON array of findings in this exact format:
+[
+  {
+    "file": "path/relative/to/repo",
+    "line_start": 10,
+    "line_end": 12,
+    "severity": "error|warning|info",
+    "category": "bug|security|performance|error-handling|style|maintainability",
+    "message": "what is wrong and why it matters",
+    "suggestion": "how to fix it"
+  }
+]
+
+Use line numbers from the new version of the file. Return [] if there is nothing to report."#;
+
+        let messages = context.messages(
+            system_prompt,
+            format!(
+                "Task: {}\n\nReview {}:\n\n{}",
+                task,
+                target.describe(),
+                material
+            ),
+        );
+
+        let response = llm.chat_with_history(messages, "default").await?;
+        debug!("Review response: {}", response);
+
+        let mut findings = match parse_findings(&response) {
+            Ok(findings) => findings,
+            Err(e) => {
+                warn!("Could not parse review findings: {:#}", e);
+                // An unreadable answer must not pass as a clean review
+                return Ok(AgentResult::failure(format!(
+                    "The review could not be read from the model's response: {:#}",
+                    e
+                ))
+                .with_metadata("target", target.spec()));
+            }
+        };
+        findings.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.file.cmp(&b.file))
+                .then_with(|| a.line_start.cmp(&b.line_start))
+        });
+
+        context.add_message(Message::assistant(format!(
+            "Reviewed {}",
+            target.describe()
+        )));
+        context.add_message(Message::assistant(format!(
+            "Review findings: {}",
+            findings.len()
+        )));
+
+        let count = |severity: Severity| findings.iter().filter(|f| f.severity == severity).count();
+
+        Ok(AgentResult::success(render_findings(&findings))
+            .with_metadata("target", target.spec())
+            .with_metadata("errors", count(Severity::Error).to_string())
+            .with_metadata("warnings", count(Severity::Warning).to_string())
+            .with_metadata("infos", count(Severity::Info).to_string())
+            .with_metadata("findings", serde_json::to_string(&findings)?))
+    }
+}
+
+impl ReviewAgent {
+    async fn collect_material(
+        &self,
+        target: &ReviewTarget,
+        tools: &ToolExecutor,
+    ) -> Result<String> {
+        let diff = match target {
+            ReviewTarget::File(path) => {
+                let content = tools.read_file(path).await?;
+                return Ok(truncate(&number_lines(path, &content, 1), MAX_REVIEW_CHARS));
+            }
+            ReviewTarget::WorkingTree => tools.git_query(&["diff", "HEAD"]).await?,
+            ReviewTarget::Staged => tools.git_query(&["diff", "--staged"]).await?,
+            // The range comes from the user; it must not be read as an option
+            ReviewTarget::Range(range) => {
+                tools
+                    .git_query(&["diff", "--end-of-options", range])
+                    .await?
+            }
+        };
+
+        if diff.trim().is_empty() {
+            return Ok(String::new());
+        }
+
+        let mut material = format!("Diff:\n{}", diff);
+
+        for (path, hunks) in changed_hunks(&diff) {
+            let content = match self.new_version(target, &path, tools).await {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Could not load {} for review context: {}", path, e);
+                    continue;
+                }
+            };
+            let lines: Vec<&str> = content.lines().collect();
+
+            for (start, len) in hunks {
+                let from = start.saturating_sub(CONTEXT_LINES).max(1);
+                let to = (start + len + CONTEXT_LINES).min(lines.len());
+                if from > to {
+                    continue;
+                }
+                let excerpt = lines[from - 1..to].join("\n");
+                material.push_str(&format!(
+                    "\n\nContext {}:{}-{}:\n{}",
+                    path,
+                    from,
+                    to,
+                    number_lines(&path, &excerpt, from)
+                ));
+            }
+        }
+
+        Ok(truncate(&material, MAX_REVIEW_CHARS))
+    }
+
+    async fn new_version(
+        &self,
+        target: &ReviewTarget,
+        path: &str,
+        tools: &ToolExecutor,
+    ) -> Result<String> {
+        match target {
+            ReviewTarget::Staged => tools.git_query(&["show", &format!(":{}", path)]).await,
+            ReviewTarget::Range(range) => {
+                let head = range.rsplit("..").next().filter(|h| !h.is_empty());
+                let rev = head.unwrap_or("HEAD").trim_start_matches('.');
+                tools
+                    .git_query(&["show", "--end-of-options", &format!("{}:{}", rev, path)])
+                    .await
+            }
+            _ => tools.read_file(path).await,
+        }
+    }
+}
+
+/// Extract `(file, [(new_start, new_len)])` for each file touched by a unified diff
+fn changed_hunks(diff: &str) -> Vec<(String, Vec<(usize, usize)>)> {
+    let mut files: Vec<(String, Vec<(usize, usize)>)> = Vec::new();
+    // Index into `files`; `None` for a deleted file, whose hunks have no new lines
+    let mut current = None;
+
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            if path == "/dev/null" {
+                current = None;
+                continue;
+            }
+            let path = path.strip_prefix("b/").unwrap_or(path);
+            files.push((path.to_string(), Vec::new()));
+            current = Some(files.len() - 1);
+        } else if let Some(header) = line.strip_prefix("@@ ") {
+            let Some((_, hunks)) = current.and_then(|idx| files.get_mut(idx)) else {
+                continue;
+            };
+            // @@ -a,b +c,d @@
+            if let Some(new_range) = header.split_whitespace().find(|p| p.starts_with('+')) {
+                let mut parts = new_range[1..].split(',');
+                let start = parts.next().and_then(|s| s.parse().ok()).unwrap_or(1);
+                let len = parts.next().and_then(|s| s.parse().ok()).unwrap_or(1);
+                hunks.push((start, len));
+            }
+        }
+    }
+
+    files
+}
+
+fn number_lines(path: &str, content: &str, first_line: usize) -> String {
+    let mut out = format!("--- {}\n", path);
+    for (idx, line) in content.lines().enumerate() {
+        out.push_str(&format!("{:>5} | {}\n", first_line + idx, line));
+    }
+    out
+}
+
+/// Parse the JSON array of findings from a model response, dropping malformed
+/// entries. Fails unless the response holds an array, and if every entry in a
+/// non-empty array is malformed; only `[]` means there is nothing to report.
+pub fn parse_findings(response: &str) -> Result<Vec<Finding>> {
+    let (Some(start), Some(end)) = (response.find('['), response.rfind(']')) else {
+        anyhow::bail!("no JSON array of findings");
+    };
+    if start > end {
+        anyhow::bail!("no JSON array of findings");
+    }
+
+    let values: Vec<serde_json::Value> = serde_json::from_str(&response[start..=end])
+        .context("the findings are not a valid JSON array")?;
+
+    let total = values.len();
+    let findings: Vec<Finding> = values
+        .into_iter()
+        .filter_map(|value| serde_json::from_value::<Finding>(value).ok())
+        .map(|mut finding| {
+            finding.line_end = finding.line_end.max(finding.line_start);
+            finding
+        })
+        .collect();
+
+    if total > 0 && findings.is_empty() {
+        anyhow::bail!("none of the {} findings could be parsed", total);
+    }
+    Ok(findings)
+}
+
+/// Render findings for the terminal
+pub fn render_findings(findings: &[Finding]) -> String {
+    if findings.is_empty() {
+        return "No issues found.".to_string();
+    }
+
+    let mut out = String::new();
+    for finding in findings {
+        let lines = if finding.line_end > finding.line_start {
+            format!("{}-{}", finding.line_start, finding.line_end)
+        } else {
+            finding.line_start.to_string()
+        };
+        out.push_str(&format!(
+            "[{}] {}:{} ({})\n  {}\n",
+            finding.severity.as_str().to_uppercase(),
+            finding.file,
+            lines,
+            finding.category,
+            finding.message
+        ));
+        if let Some(suggestion) = &finding.suggestion {
+            out.push_str(&format!("  Suggestion: {}\n", suggestion));
+        }
+        out.push('\n');
+    }
+
+    let count = |severity: Severity| findings.iter().filter(|f| f.severity == severity).count();
+    out.push_str(&format!(
+        "{} error(s), {} warning(s), {} info",
+        count(Severity::Error),
+        count(Severity::Warning),
+        count(Severity::Info)
+    ));
+
+    out
+}
+
+/// Convert findings to a SARIF 2.1.0 log
+pub fn to_sarif(findings: &[Finding]) -> serde_json::Value {
+    let mut rule_ids: Vec<&str> = findings.iter().map(rule_id).collect();
+    rule_ids.sort();
+    rule_ids.dedup();
+
+    let rules: Vec<serde_json::Value> = rule_ids
+        .iter()
+        .map(|id| json!({ "id": id, "name": id }))
+        .collect();
+
+    let results: Vec<serde_json::Value> = findings
+        .iter()
+        .map(|f| {
+            let level = match f.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "note",
+            };
+            let mut text = f.message.clone();
+            if let Some(suggestion) = &f.suggestion {
+                text.push_str(&format!("\nSuggestion: {}", suggestion));
+            }
+            json!({
+                "ruleId": rule_id(f),
+                "level": level,
+                "message": { "text": text },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": f.file },
+                        "region": {
+                            "startLine": f.line_start.max(1),
+                            "endLine": f.line_end.max(f.line_start).max(1),
+                        }
+                    }
+                }]
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "lca-review",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+fn rule_id(finding: &Finding) -> &str {
+    if finding.category.is_empty() {
+        "general"
+    } else {
+        &finding.category
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_review_target_from_spec() {
+        assert_eq!(ReviewTarget::from_spec(""), ReviewTarget::WorkingTree);
+        assert_eq!(ReviewTarget::from_spec("--staged"), ReviewTarget::Staged);
+        assert_eq!(
+            ReviewTarget::from_spec("main..feature"),
+            ReviewTarget::Range("main..feature".to_string())
+        );
+        assert_eq!(
+            ReviewTarget::from_spec("src/main.rs"),
+            ReviewTarget::File("src/main.rs".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_range_is_never_read_as_an_option() {
+        let dir = tempfile::tempdir().unwrap();
+        std::process::Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir.path())
+            .status()
+            .unwrap();
+        let tools = ToolExecutor::new(dir.path());
+
+        let target = ReviewTarget::Range("--output=leak..HEAD".to_string());
+        assert!(ReviewAgent::new()
+            .collect_material(&target, &tools)
+            .await
+            .is_err());
+        assert!(std::fs::read_dir(dir.path())
+            .unwrap()
+            .all(|entry| entry.unwrap().file_name() == ".git"));
+    }
+
+    #[test]
+    fn test_parse_findings() {
+        let response = r#"Here are the findings:
+[
+  {"file": "src/a.rs", "line_start": 4, "severity": "critical", "category": "bug",
+   "message": "unwrap on user input", "suggestion": "return an error"},
+  {"file": "src/b.rs", "message": "missing line"}
+]"#;
+        let findings = parse_findings(response).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[0].line_end, 4);
+
+        assert!(parse_findings("Nothing to report: []").unwrap().is_empty());
+        assert!(parse_findings("Looks good to me!").is_err());
+        assert!(parse_findings(r#"[{"file": "src/a.rs", "line_start": 4,]"#).is_err());
+        assert!(parse_findings(r#"[{"note": "fine"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_changed_hunks() {
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,3 +1,4 @@ fn main\n+x\n@@ -20 +21,2 @@\n";
+        assert_eq!(
+            changed_hunks(diff),
+            vec![("src/a.rs".to_string(), vec![(1, 4), (21, 2)])]
+        );
+
+        let diff = "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1,3 +1,4 @@\n+x\n\
+                    --- a/src/old.rs\n+++ /dev/null\n@@ -1,10 +0,0 @@\n-gone\n";
+        assert_eq!(
+            changed_hunks(diff),
+            vec![("src/a.rs".to_string(), vec![(1, 4)])]
+        );
+    }
+
+    #[test]
+    fn test_to_sarif() {
+        let findings = vec![Finding {
+            file: "src/a.rs".to_string(),
+            line_start: 3,
+            line_end: 5,
+            severity: Severity::Info,
+            category: "style".to_string(),
+            message: "long function".to_string(),
+            suggestion: None,
+        }];
+        let sarif = to_sarif(&findings);
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["level"], "note");
+        assert_eq!(result["ruleId"], "style");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["region"]["endLine"],
+            5
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use super::base::AgentRegistry;
+use crate::llm::{LlmClient, Message};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteSource {
+    /// The LLM classified the task
+    Llm,
+    /// Keyword matching, used when the LLM is unavailable or unparseable
+    Keyword,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentChoice {
+    pub agent: String,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingDecision {
+    /// Candidate agents, best first
+    pub choices: Vec<AgentChoice>,
+    /// The task needs several agents and should go to the coordinator
+    pub multi_step: bool,
+    pub reason: String,
+    pub source: RouteSource,
+}
+
+impl RoutingDecision {
+    pub fn top(&self) -> Option<&AgentChoice> {
+        self.choices.first()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LlmRouting {
+    #[serde(default)]
+    multi_step: bool,
+    #[serde(default)]
+    choices: Vec<AgentChoice>,
+    #[serde(default)]
+    reason: String,
+}
+
+/// Picks the agent for a task by asking the LLM to classify it against the
+/// registered agents' descriptions and capabilities
+pub struct AgentRouter {
+    registry: Arc<AgentRegistry>,
+    min_confidence: f32,
+}
+
+impl AgentRouter {
+    pub fn new(registry: Arc<AgentRegistry>) -> Self {
+        Self {
+            registry,
+            min_confidence: 0.6,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    /// Minimum confidence for sending a task straight to the top agent
+    pub fn min_confidence(&self) -> f32 {
+        self.min_confidence
+    }
+
+    /// Classify `task`. Never fails: falls back to keyword matching when the
+    /// LLM cannot be reached or returns something unusable.
+    pub async fn route(&self, task: &str, llm: Arc<dyn LlmClient>) -> RoutingDecision {
+        match self.classify(task, llm).await {
+            Ok(decision) => decision,
+            Err(e) => {
+                warn!("LLM routing unavailable, falling back to keywords: {}", e);
+                self.keyword_route(task)
+            }
+        }
+    }
+
+    async fn classify(&self, task: &str, llm: Arc<dyn LlmClient>) -> Result<RoutingDecision> {
+        let agents = self
+            .registry
+            .agents()
+            .iter()
+            .map(|agent| {
+                let capabilities = agent
+                    .capabilities()
+                    .iter()
+                    .map(|c| format!("{:?}", c))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "- {}: {} (capabilities: {})",
+                    agent.name(),
+                    agent.description(),
+                    capabilities
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let system_prompt = format!(
+            r#"You route tasks to specialised agents.
+
+Available agents:
+{}
+
+Rank the agents that could handle the task on their own, best first, with a confidence between 0 and 1.
+Set "multi_step" to true when the task needs several different agents in sequence.
+
+Return ONLY valid JSON in this exact format:
+{{"multi_step": false, "choices": [{{"agent": "code", "confidence": 0.9}}], "reason": "short explanation"}}"#,
+            agents
+        );
+
+        let messages = vec![
+            Message::system(system_prompt),
+            Message::user(format!("Task: {}", task)),
+        ];
+
+        let response = llm.chat_with_history(messages, "default").await?;
+        debug!("Routing response: {}", response);
+
+        let decision = self
+            .parse_decision(&response)
+            .ok_or_else(|| anyhow::anyhow!("Could not parse routing response"))?;
+
+        info!(
+            "Routed task (multi_step: {}): {:?} - {}",
+            decision.multi_step, decision.choices, decision.reason
+        );
+
+        Ok(decision)
+    }
+
+    fn parse_decision(&self, response: &str) -> Option<RoutingDecision> {
+        let start = response.find('{')?;
+        let end = response.rfind('}')?;
+        if start > end {
+            return None;
+        }
+
+        let parsed: LlmRouting = serde_json::from_str(&response[start..=end]).ok()?;
+
+        let mut choices: Vec<AgentChoice> = parsed
+            .choices
+            .into_iter()
+            .filter(|c| self.registry.get(&c.agent).is_some())
+            .map(|c| AgentChoice {
+                agent: c.agent,
+                confidence: c.confidence.clamp(0.0, 1.0),
+            })
+            .collect();
+        choices.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        if choices.is_empty() && !parsed.multi_step {
+            return None;
+        }
+
+        Some(RoutingDecision {
+            choices,
+            multi_step: parsed.multi_step,
+            reason: parsed.reason,
+            source: RouteSource::Llm,
+        })
+    }
+
+    /// Route with each agent's `can_handle` keyword check
+    pub fn keyword_route(&self, task: &str) -> RoutingDecision {
+        let capable = self.registry.find_capable(task);
+        let confidence = if capable.len() == 1 { 1.0 } else { 0.5 };
+
+        let mut choices: Vec<AgentChoice> = capable
+            .iter()
+            .map(|agent| AgentChoice {
+                agent: agent.name().to_string(),
+                confidence,
+            })
+            .collect();
+        choices.sort_by(|a, b| a.agent.cmp(&b.agent));
+
+        RoutingDecision {
+            multi_step: choices.len() != 1,
+            reason: format!("{} agent(s) matched by keyword", choices.len()),
+            choices,
+            source: RouteSource::Keyword,
+        }
+    }
+}
+
+/// Split `@agent rest of task` into the agent name and the task
+pub fn parse_agent_mention(input: &str) -> Option<(&str, &str)> {
+    let rest = input.trim_start().strip_prefix('@')?;
+    let (name, task) = match rest.split_once(char::is_whitespace) {
+        Some((name, task)) => (name, task.trim()),
+        None => (rest, ""),
+    };
+
+    if name.is_empty() {
+        None
+    } else {
+        Some((name, task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{CodeAgent, FileAgent, ShellAgent};
+
+    fn router() -> AgentRouter {
+        let mut registry = AgentRegistry::new();
+        registry.register(Arc::new(CodeAgent::new()));
+        registry.register(Arc::new(FileAgent::new()));
+        registry.register(Arc::new(ShellAgent::new()));
+        AgentRouter::new(Arc::new(registry))
+    }
+
+    #[test]
+    fn test_parse_agent_mention() {
+        assert_eq!(
+            parse_agent_mention("@shell list files"),
+            Some(("shell", "list files"))
+        );
+        assert_eq!(parse_agent_mention("@code"), Some(("code", "")));
+        assert_eq!(parse_agent_mention("email me@example.com"), None);
+        assert_eq!(parse_agent_mention("@ nothing"), None);
+    }
+
+    #[test]
+    fn test_parse_decision_filters_unknown_agents_and_ranks() {
+        let router = router();
+        let response = r#"Sure: {"multi_step": false, "choices": [
+            {"agent": "file", "confidence": 0.4},
+            {"agent": "git", "confidence": 0.99},
+            {"agent": "code", "confidence": 1.7}
+        ], "reason": "writing code"}"#;
+
+        let decision = router.parse_decision(response).unwrap();
+        assert_eq!(decision.source, RouteSource::Llm);
+        assert_eq!(
+            decision.choices,
+            vec![
+                AgentChoice {
+                    agent: "code".to_string(),
+                    confidence: 1.0
+                },
+                AgentChoice {
+                    agent: "file".to_string(),
+                    confidence: 0.4
+                },
+            ]
+        );
+        assert!(router.parse_decision("no json here").is_none());
+    }
+
+    #[test]
+    fn test_keyword_route() {
+        let router = router();
+
+        let single = router.keyword_route("execute the build script");
+        assert!(!single.multi_step);
+        assert_eq!(single.top().unwrap().agent, "shell");
+
+        let ambiguous = router.keyword_route("write a test for the build script");
+        assert!(ambiguous.multi_step);
+        assert_eq!(ambiguous.source, RouteSource::Keyword);
+    }
+}
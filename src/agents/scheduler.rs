@@ -0,0 +1,281 @@
+use super::coordinator::SubTask;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtaskStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    /// Not run because a dependency failed or was skipped
+    Skipped,
+    /// Dropped in favour of a revised plan
+    Replaced,
+}
+
+/// Bookkeeping for running coordinator subtasks as a dependency DAG
+#[derive(Debug)]
+pub struct DagState {
+    dependencies: Vec<Vec<usize>>,
+    status: Vec<SubtaskStatus>,
+}
+
+impl DagState {
+    pub fn new(subtasks: &[SubTask]) -> Self {
+        Self {
+            dependencies: subtasks.iter().map(|s| s.dependencies.clone()).collect(),
+            status: vec![SubtaskStatus::Pending; subtasks.len()],
+        }
+    }
+
+    pub fn status(&self, idx: usize) -> SubtaskStatus {
+        self.status[idx]
+    }
+
+    /// Pending subtasks whose dependencies have all succeeded, in index order
+    pub fn ready(&self) -> Vec<usize> {
+        (0..self.status.len())
+            .filter(|idx| self.status[*idx] == SubtaskStatus::Pending)
+            .filter(|idx| {
+                self.dependencies[*idx]
+                    .iter()
+                    .all(|dep| self.status.get(*dep).copied() == Some(SubtaskStatus::Succeeded))
+            })
+            .collect()
+    }
+
+    /// Mark pending subtasks with a failed or skipped dependency as skipped.
+    /// Returns `(subtask, dependency)` pairs, propagating transitively.
+    pub fn skip_blocked(&mut self) -> Vec<(usize, usize)> {
+        let mut skipped = Vec::new();
+
+        loop {
+            let blocked: Vec<(usize, usize)> = (0..self.status.len())
+                .filter(|idx| self.status[*idx] == SubtaskStatus::Pending)
+                .filter_map(|idx| {
+                    self.dependencies[idx]
+                        .iter()
+                        .find(|dep| {
+                            matches!(
+                                self.status.get(**dep),
+                                Some(SubtaskStatus::Failed)
+                                    | Some(SubtaskStatus::Skipped)
+                                    | Some(SubtaskStatus::Replaced)
+                            )
+                        })
+                        .map(|dep| (idx, *dep))
+                })
+                .collect();
+
+            if blocked.is_empty() {
+                return skipped;
+            }
+
+            for (idx, _) in &blocked {
+                self.status[*idx] = SubtaskStatus::Skipped;
+            }
+            skipped.extend(blocked);
+        }
+    }
+
+    pub fn start(&mut self, idx: usize) {
+        self.status[idx] = SubtaskStatus::Running;
+    }
+
+    pub fn finish(&mut self, idx: usize, success: bool) {
+        self.status[idx] = if success {
+            SubtaskStatus::Succeeded
+        } else {
+            SubtaskStatus::Failed
+        };
+    }
+
+    /// Add a subtask to the graph and return its index
+    pub fn push(&mut self, dependencies: Vec<usize>) -> usize {
+        self.dependencies.push(dependencies);
+        self.status.push(SubtaskStatus::Pending);
+        self.status.len() - 1
+    }
+
+    /// Mark every pending subtask as replaced and return them
+    pub fn replace_pending(&mut self) -> Vec<usize> {
+        let pending = self.unresolved();
+        for idx in &pending {
+            self.status[*idx] = SubtaskStatus::Replaced;
+        }
+        pending
+    }
+
+    /// Subtasks still pending, e.g. because of a cycle or an out-of-range dependency
+    pub fn unresolved(&self) -> Vec<usize> {
+        (0..self.status.len())
+            .filter(|idx| self.status[*idx] == SubtaskStatus::Pending)
+            .collect()
+    }
+}
+
+/// Check a plan before anything runs. Returns one line per problem: empty
+/// descriptions, agents not in `agents`, out-of-range or self dependencies,
+/// and dependency cycles.
+pub fn validate_subtasks(subtasks: &[SubTask], agents: &[String]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let len = subtasks.len();
+
+    for (idx, subtask) in subtasks.iter().enumerate() {
+        if subtask.description.trim().is_empty() {
+            problems.push(format!("Subtask {}: description is empty", idx));
+        }
+        if !agents.contains(&subtask.agent_type) {
+            problems.push(format!(
+                "Subtask {}: unknown agent '{}'",
+                idx, subtask.agent_type
+            ));
+        }
+        for dep in &subtask.dependencies {
+            if *dep == idx {
+                problems.push(format!("Subtask {}: depends on itself", idx));
+            } else if *dep >= len {
+                problems.push(format!(
+                    "Subtask {}: depends on subtask {}, which does not exist",
+                    idx, dep
+                ));
+            }
+        }
+    }
+
+    let edges = |idx: usize| {
+        subtasks[idx]
+            .dependencies
+            .iter()
+            .copied()
+            .filter(move |dep| *dep != idx && *dep < len)
+    };
+
+    // Peel off subtasks that cannot be on a cycle: those with no remaining
+    // dependencies, then those nothing remaining depends on
+    let mut remaining = vec![true; len];
+    loop {
+        let peel: Vec<usize> = (0..len)
+            .filter(|idx| remaining[*idx])
+            .filter(|idx| {
+                !edges(*idx).any(|dep| remaining[dep])
+                    || !(0..len).any(|other| remaining[other] && edges(other).any(|d| d == *idx))
+            })
+            .collect();
+        if peel.is_empty() {
+            break;
+        }
+        for idx in peel {
+            remaining[idx] = false;
+        }
+    }
+
+    let cycle: Vec<String> = (0..len)
+        .filter(|idx| remaining[*idx])
+        .map(|idx| idx.to_string())
+        .collect();
+    if !cycle.is_empty() {
+        problems.push(format!(
+            "Subtasks {} form a dependency cycle",
+            cycle.join(", ")
+        ));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subtask(dependencies: Vec<usize>) -> SubTask {
+        SubTask {
+            description: "step".to_string(),
+            agent_type: "file".to_string(),
+            dependencies,
+        }
+    }
+
+    #[test]
+    fn test_ready_follows_dependencies() {
+        let mut dag = DagState::new(&[subtask(vec![]), subtask(vec![]), subtask(vec![0, 1])]);
+        assert_eq!(dag.ready(), vec![0, 1]);
+
+        dag.start(0);
+        dag.start(1);
+        dag.finish(0, true);
+        assert!(dag.ready().is_empty());
+
+        dag.finish(1, true);
+        assert_eq!(dag.ready(), vec![2]);
+    }
+
+    #[test]
+    fn test_failure_skips_dependents_transitively() {
+        let mut dag = DagState::new(&[
+            subtask(vec![]),
+            subtask(vec![0]),
+            subtask(vec![1]),
+            subtask(vec![]),
+        ]);
+        dag.start(0);
+        dag.finish(0, false);
+
+        assert_eq!(dag.skip_blocked(), vec![(1, 0), (2, 1)]);
+        assert_eq!(dag.ready(), vec![3]);
+    }
+
+    #[test]
+    fn test_replaced_subtasks_block_dependents() {
+        let mut dag = DagState::new(&[subtask(vec![]), subtask(vec![0]), subtask(vec![1])]);
+        dag.start(0);
+        dag.finish(0, false);
+
+        assert_eq!(dag.replace_pending(), vec![1, 2]);
+        let new = dag.push(vec![1]);
+        assert_eq!(dag.skip_blocked(), vec![(new, 1)]);
+        assert_eq!(dag.push(vec![]), 4);
+        assert_eq!(dag.ready(), vec![4]);
+    }
+
+    #[test]
+    fn test_validate_subtasks() {
+        let agents = vec!["file".to_string(), "code".to_string()];
+        assert!(validate_subtasks(&[subtask(vec![]), subtask(vec![0])], &agents).is_empty());
+
+        let mut unknown = subtask(vec![0, 9]);
+        unknown.agent_type = "git".to_string();
+        unknown.description = " ".to_string();
+        let problems = validate_subtasks(
+            &[
+                subtask(vec![]),
+                subtask(vec![3]),
+                subtask(vec![1]),
+                subtask(vec![2, 0]),
+                subtask(vec![3]),
+                unknown,
+            ],
+            &agents,
+        );
+
+        assert_eq!(
+            problems,
+            vec![
+                "Subtask 5: description is empty",
+                "Subtask 5: unknown agent 'git'",
+                "Subtask 5: depends on subtask 9, which does not exist",
+                "Subtasks 1, 2, 3 form a dependency cycle",
+            ]
+        );
+        assert_eq!(
+            validate_subtasks(&[subtask(vec![0])], &agents),
+            vec!["Subtask 0: depends on itself"]
+        );
+    }
+
+    #[test]
+    fn test_cycles_stay_unresolved() {
+        let dag = DagState::new(&[subtask(vec![1]), subtask(vec![0]), subtask(vec![7])]);
+        assert!(dag.ready().is_empty());
+        assert_eq!(dag.unresolved(), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::debug;
+
+use super::base::{Agent, AgentCapability, AgentContext, AgentResult};
+use crate::context::ContextManager;
+use crate::llm::{LlmClient, Message};
+use crate::tools::{SearchOptions, ToolExecutor};
+
+pub struct SearchAgent {
+    name: String,
+}
+
+impl SearchAgent {
+    pub fn new() -> Self {
+        Self {
+            name: "search".to_string(),
+        }
+    }
+}
+
+impl Default for SearchAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Agent for SearchAgent {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "Search file contents with regex, globs and line context"
+    }
+
+    fn capabilities(&self) -> Vec<AgentCapability> {
+        vec![AgentCapability::FileOperations, AgentCapability::Analysis]
+    }
+
+    fn can_handle(&self, task: &str) -> bool {
+        let keywords = ["search", "grep", "find", "occurrence", "usages", "where is"];
+        keywords.iter().any(|kw| task.to_lowercase().contains(kw))
+    }
+
+    async fn execute(
+        &self,
+        task: &str,
+        context: &mut AgentContext,
+        llm: Arc<dyn LlmClient>,
+        tools: Arc<ToolExecutor>,
+        _context_mgr: Arc<ContextManager>,
+    ) -> Result<AgentResult> {
+        debug!("Search agent executing: {}", task);
+
+        let system_prompt = r#"You are a code search expert.
+Translate the task into a search over the project files. Ignored files (.gitignore) and binaries are skipped automatically.
+
+Respond in this format:
+PATTERN: <text or regular expression to find>
+REGEX: <yes|no>
+CASE_INSENSITIVE: <yes|no>
+PATH: <directory to search, relative to the working directory>
+GLOB: <comma-separated globs such as *.rs,!tests/** or none>
+CONTEXT: <number of context lines, 0-5>"#;
+
+        let messages = context.messages(
+            system_prompt,
+            format!(
+                "Task: {}\nWorking directory: {}",
+                task, context.working_directory
+            ),
+        );
+
+        let response = llm.chat_with_history(messages, "default").await?;
+
+        let pattern = self.extract_field(&response, "PATTERN");
+        if pattern.is_empty() {
+            return Ok(AgentResult::failure("Could not determine a search pattern"));
+        }
+
+        let path = match self.extract_field(&response, "PATH") {
+            p if p.is_empty() => context.working_directory.clone(),
+            p => p,
+        };
+        let context_lines = self
+            .extract_field(&response, "CONTEXT")
+            .parse::<usize>()
+            .unwrap_or(2)
+            .min(5);
+
+        let mut options = SearchOptions::new(&pattern)
+            .with_regex(self.is_yes(&self.extract_field(&response, "REGEX")))
+            .with_case_insensitive(self.is_yes(&self.extract_field(&response, "CASE_INSENSITIVE")))
+            .with_context_lines(context_lines);
+
+        let globs = self.extract_field(&response, "GLOB");
+        if !globs.eq_ignore_ascii_case("none") {
+            for glob in globs.split(',').map(str::trim).filter(|g| !g.is_empty()) {
+                options = options.with_glob(glob);
+            }
+        }
+
+        let matches = match tools.search(&path, options).await {
+            Ok(matches) => matches,
+            Err(e) => return Ok(AgentResult::failure(format!("Search failed: {}", e))),
+        };
+
+        let mut files: Vec<&str> = matches.iter().map(|m| m.path.as_str()).collect();
+        files.dedup();
+        let file_count = files.len();
+
+        context.add_message(Message::assistant(format!(
+            "Searched for '{}' in {}",
+            pattern, path
+        )));
+        context.add_message(Message::assistant(format!(
+            "Found {} matches in {} files",
+            matches.len(),
+            file_count
+        )));
+
+        let output = if matches.is_empty() {
+            format!("No matches for '{}'", pattern)
+        } else {
+            matches
+                .iter()
+                .map(|m| m.to_grep_string())
+                .collect::<Vec<_>>()
+                .join(if context_lines > 0 { "\n--\n" } else { "\n" })
+        };
+
+        Ok(AgentResult::success(output)
+            .with_metadata("pattern", pattern)
+            .with_metadata("matches", matches.len().to_string())
+            .with_metadata("files", file_count.to_string())
+            .with_metadata("results", serde_json::to_string(&matches)?))
+    }
+}
+
+impl SearchAgent {
+    fn extract_field(&self, response: &str, field: &str) -> String {
+        let prefix = format!("{}:", field);
+        for line in response.lines() {
+            let line = line.trim();
+            if line.to_uppercase().starts_with(&prefix) {
+                return line[prefix.len()..].trim().trim_matches('`').to_string();
+            }
+        }
+        String::new()
+    }
+
+    fn is_yes(&self, value: &str) -> bool {
+        matches!(value.to_lowercase().as_str(), "yes" | "y" | "true")
+    }
+}
@@ -32,13 +32,17 @@ impl Agent for ShellAgent {
         &self.name
     }
 
+    fn description(&self) -> &str {
+        "Run shell commands, scripts, builds and installs"
+    }
+
     fn capabilities(&self) -> Vec<AgentCapability> {
         vec![AgentCapability::ShellExecution]
     }
 
     fn can_handle(&self, task: &str) -> bool {
         let keywords = [
-            "run", "execute", "command", "shell", "bash", "script", "install", "build", "test",
+            "run", "execute", "command", "shell", "bash", "script", "install", "build",
         ];
         keywords.iter().any(|kw| task.to_lowercase().contains(kw))
     }
@@ -80,13 +84,13 @@ IMPORTANT: Use printf for newlines, NOT echo -e (the -e flag causes errors on so
 NEVER use rm -rf / or other destructive commands.
 ALWAYS keep the entire command on ONE SINGLE LINE after "COMMAND:"."#;
 
-        let messages = vec![
-            Message::system(system_prompt),
-            Message::user(format!(
+        let messages = context.messages(
+            system_prompt,
+            format!(
                 "Task: {}\nWorking directory: {}",
                 task, context.working_directory
-            )),
-        ];
+            ),
+        );
 
         let response = llm.chat_with_history(messages, "default").await?;
 
@@ -105,8 +109,8 @@ ALWAYS keep the entire command on ONE SINGLE LINE after "COMMAND:"."#;
             .execute_shell(&command, &context.working_directory)
             .await?;
 
-        context.add_message(format!("Executed: {}", command));
-        context.add_message(format!("Output: {}", output));
+        context.add_message(Message::assistant(format!("Executed: {}", command)));
+        context.add_message(Message::assistant(format!("Output: {}", output)));
 
         // Check if we created a script and offer to test it
         let script_created = self.detect_script_creation(&command, &output);
@@ -115,7 +119,7 @@ ALWAYS keep the entire command on ONE SINGLE LINE after "COMMAND:"."#;
             let mut full_output = output.clone();
 
             // Prompt user if they want to test the script
-            if self.prompt_test_script(&script_path) {
+            if tools.is_interactive() && self.prompt_test_script(&script_path) {
                 full_output.push_str(&format!("\n\n>> Testing script: {}\n", script_path));
 
                 match tools
@@ -124,7 +128,10 @@ ALWAYS keep the entire command on ONE SINGLE LINE after "COMMAND:"."#;
                 {
                     Ok(test_output) => {
                         full_output.push_str(&format!("SUCCESS - Script output:\n{}", test_output));
-                        context.add_message(format!("Tested script: {}", script_path));
+                        context.add_message(Message::assistant(format!(
+                            "Tested script: {}",
+                            script_path
+                        )));
                     }
                     Err(e) => {
                         full_output.push_str(&format!("FAILED - Script test failed: {}", e));
@@ -220,6 +227,8 @@ impl ShellAgent {
     fn prompt_test_script(&self, script_path: &str) -> bool {
         use std::io::{self, Write};
 
+        let _prompt = crate::permissions::prompt_lock();
+
         println!("\n┌─────────────────────────────────────────────────────────────┐");
         println!("│ SCRIPT TEST PROMPT                                         │");
         println!("└─────────────────────────────────────────────────────────────┘");
@@ -0,0 +1,790 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use super::base::{Agent, AgentCapability, AgentContext, AgentResult};
+use crate::context::ContextManager;
+use crate::llm::{LlmClient, Message};
+use crate::tools::ToolExecutor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestFramework {
+    Cargo,
+    Pytest,
+    Jest,
+    Vitest,
+    Go,
+}
+
+impl TestFramework {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestFramework::Cargo => "cargo",
+            TestFramework::Pytest => "pytest",
+            TestFramework::Jest => "jest",
+            TestFramework::Vitest => "vitest",
+            TestFramework::Go => "go",
+        }
+    }
+
+    /// Detect the test framework from the marker files in a project directory
+    pub fn detect(dir: &Path) -> Option<Self> {
+        if dir.join("Cargo.toml").exists() {
+            return Some(TestFramework::Cargo);
+        }
+
+        if dir.join("go.mod").exists() {
+            return Some(TestFramework::Go);
+        }
+
+        if let Ok(package_json) = std::fs::read_to_string(dir.join("package.json")) {
+            if package_json.contains("\"vitest\"") {
+                return Some(TestFramework::Vitest);
+            }
+            if package_json.contains("\"jest\"") {
+                return Some(TestFramework::Jest);
+            }
+        }
+
+        let python_markers = [
+            "pytest.ini",
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "tox.ini",
+            "requirements.txt",
+        ];
+        if python_markers.iter().any(|m| dir.join(m).exists()) {
+            return Some(TestFramework::Pytest);
+        }
+
+        None
+    }
+
+    /// Build the command line that runs the suite, optionally filtered to a subset
+    pub fn command(&self, filter: Option<&str>) -> String {
+        let base = match self {
+            TestFramework::Cargo => "cargo test".to_string(),
+            TestFramework::Pytest => "python -m pytest -rfEs".to_string(),
+            TestFramework::Jest => "npx jest".to_string(),
+            TestFramework::Vitest => "npx vitest run".to_string(),
+            TestFramework::Go => "go test -v ./...".to_string(),
+        };
+
+        let command = match filter {
+            Some(f) => {
+                let quoted = shell_quote(f);
+                match self {
+                    TestFramework::Cargo => format!("{} {}", base, quoted),
+                    TestFramework::Pytest => format!("{} -k {}", base, quoted),
+                    TestFramework::Jest | TestFramework::Vitest => {
+                        format!("{} -t {}", base, quoted)
+                    }
+                    TestFramework::Go => format!("{} -run {}", base, quoted),
+                }
+            }
+            None => base,
+        };
+
+        // Several runners (jest, vitest) report on stderr, which execute_shell drops on success
+        format!("{} 2>&1", command)
+    }
+
+    pub fn parse(&self, output: &str) -> TestReport {
+        match self {
+            TestFramework::Cargo => parse_cargo(output),
+            TestFramework::Pytest => parse_pytest(output),
+            TestFramework::Jest => parse_jest(output),
+            TestFramework::Vitest => parse_vitest(output),
+            TestFramework::Go => parse_go(output),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestFailure {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Source location as `file:line`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub failures: Vec<TestFailure>,
+}
+
+impl TestReport {
+    fn failure_mut(&mut self, name: &str) -> &mut TestFailure {
+        let idx = match self.failures.iter().position(|f| f.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.failures.push(TestFailure {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                self.failures.len() - 1
+            }
+        };
+        &mut self.failures[idx]
+    }
+
+    /// True when the output contained any recognisable test results
+    pub fn has_results(&self) -> bool {
+        self.passed + self.failed + self.skipped > 0 || !self.failures.is_empty()
+    }
+}
+
+pub struct TestAgent {
+    name: String,
+}
+
+impl TestAgent {
+    pub fn new() -> Self {
+        Self {
+            name: "test".to_string(),
+        }
+    }
+}
+
+impl Default for TestAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Agent for TestAgent {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "Run the project's test suite and report pass/fail results"
+    }
+
+    fn capabilities(&self) -> Vec<AgentCapability> {
+        vec![AgentCapability::Testing, AgentCapability::ShellExecution]
+    }
+
+    fn can_handle(&self, task: &str) -> bool {
+        let keywords = ["test", "pytest", "jest", "vitest", "spec", "suite"];
+        keywords.iter().any(|kw| task.to_lowercase().contains(kw))
+    }
+
+    async fn execute(
+        &self,
+        task: &str,
+        context: &mut AgentContext,
+        llm: Arc<dyn LlmClient>,
+        tools: Arc<ToolExecutor>,
+        _context_mgr: Arc<ContextManager>,
+    ) -> Result<AgentResult> {
+        debug!("Test agent executing: {}", task);
+
+        let project_dir = tools.resolve_path(&context.working_directory);
+        let framework = match TestFramework::detect(&project_dir) {
+            Some(framework) => framework,
+            None => {
+                return Ok(AgentResult::failure(format!(
+                    "Could not detect a test framework in {}",
+                    project_dir.display()
+                )))
+            }
+        };
+
+        // Callers such as a fix loop can pin the filter directly instead of going via the model
+        let filter = match context.metadata.get("test_filter") {
+            Some(filter) => Some(filter.clone()).filter(|f| !f.is_empty()),
+            None => self.extract_filter(task, framework, context, llm).await?,
+        };
+
+        let command = framework.command(filter.as_deref());
+        info!("Running {} tests: {}", framework.as_str(), command);
+
+        let output = tools
+            .execute_shell(&command, &context.working_directory)
+            .await?;
+
+        let report = framework.parse(&output);
+        let command_failed = output.starts_with("Command failed:");
+
+        context.add_message(Message::assistant(format!("Ran tests: {}", command)));
+        context.add_message(Message::assistant(format!(
+            "Test results: {} passed, {} failed, {} skipped",
+            report.passed, report.failed, report.skipped
+        )));
+
+        let success = !command_failed && report.failed == 0;
+        let summary = self.format_report(framework, &report, &output);
+
+        let result = if success {
+            AgentResult::success(summary)
+        } else {
+            AgentResult::failure(summary)
+        };
+
+        Ok(result
+            .with_metadata("framework", framework.as_str())
+            .with_metadata("command", command)
+            .with_metadata("filter", filter.unwrap_or_default())
+            .with_metadata("passed", report.passed.to_string())
+            .with_metadata("failed", report.failed.to_string())
+            .with_metadata("skipped", report.skipped.to_string())
+            .with_metadata("failures", serde_json::to_string(&report.failures)?))
+    }
+}
+
+impl TestAgent {
+    async fn extract_filter(
+        &self,
+        task: &str,
+        framework: TestFramework,
+        context: &AgentContext,
+        llm: Arc<dyn LlmClient>,
+    ) -> Result<Option<String>> {
+        let system_prompt = format!(
+            r#"You decide which tests to run for a {} test suite.
+If the task asks for specific tests, reply with the name filter to pass to the runner.
+If the task asks to run all tests, reply with none.
+
+Respond in this format:
+FILTER: <test name filter|none>"#,
+            framework.as_str()
+        );
+
+        let messages = context.messages(system_prompt, format!("Task: {}", task));
+
+        let response = llm.chat_with_history(messages, "default").await?;
+
+        for line in response.lines() {
+            if let Some(value) = line.trim().strip_prefix("FILTER:") {
+                let value = value.trim().trim_matches('`').trim_matches('"');
+                if value.is_empty() || value.eq_ignore_ascii_case("none") {
+                    return Ok(None);
+                }
+                return Ok(Some(value.to_string()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn format_report(&self, framework: TestFramework, report: &TestReport, raw: &str) -> String {
+        if !report.has_results() {
+            return format!(
+                "No {} test results found in output:\n{}",
+                framework.as_str(),
+                raw
+            );
+        }
+
+        let mut out = format!(
+            "{} tests: {} passed, {} failed, {} skipped",
+            framework.as_str(),
+            report.passed,
+            report.failed,
+            report.skipped
+        );
+
+        for failure in &report.failures {
+            out.push_str(&format!("\n\nFAILED {}", failure.name));
+            if let Some(location) = &failure.location {
+                out.push_str(&format!(" ({})", location));
+            }
+            if let Some(message) = &failure.message {
+                for line in message.lines() {
+                    out.push_str(&format!("\n  {}", line));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r#"'\''"#))
+}
+
+/// Find `<n> <label>` inside a summary line, e.g. `count_of("1 failed, 2 passed", "passed")`
+fn count_of(line: &str, label: &str) -> usize {
+    let mut total = 0;
+    let mut rest = line;
+    while let Some(pos) = rest.find(label) {
+        let before = rest[..pos].trim_end();
+        let digits: String = before
+            .chars()
+            .rev()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        if let Ok(n) = digits.parse::<usize>() {
+            total += n;
+        }
+        rest = &rest[pos + label.len()..];
+    }
+    total
+}
+
+/// Extract a trailing `path:line[:col]` from text like `at Object.<anonymous> (src/a.test.js:10:5)`
+fn find_location(text: &str) -> Option<String> {
+    for token in text.split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',') {
+        let token = token.trim_matches(|c| c == '\'' || c == '"');
+        let mut parts = token.split(':');
+        let (Some(file), Some(line)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        if file.contains('.') && !file.is_empty() && line.parse::<usize>().is_ok() {
+            return Some(format!("{}:{}", file, line));
+        }
+    }
+    None
+}
+
+fn parse_cargo(output: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let mut current: Option<String> = None;
+    let mut message = Vec::new();
+
+    let flush = |report: &mut TestReport, current: &mut Option<String>, message: &mut Vec<&str>| {
+        if let Some(name) = current.take() {
+            let failure = report.failure_mut(&name);
+            let text = message.join("\n").trim().to_string();
+            if !text.is_empty() {
+                failure.message = Some(text);
+            }
+        }
+        message.clear();
+    };
+
+    for line in output.lines() {
+        if let Some(summary) = line.strip_prefix("test result:") {
+            flush(&mut report, &mut current, &mut message);
+            report.passed += count_of(summary, "passed");
+            report.failed += count_of(summary, "failed");
+            report.skipped += count_of(summary, "ignored");
+        } else if let Some(rest) = line.strip_prefix("test ") {
+            if let Some(name) = rest.strip_suffix(" ... FAILED") {
+                report.failure_mut(name.trim());
+            }
+        } else if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|l| l.strip_suffix(" stdout ----"))
+        {
+            flush(&mut report, &mut current, &mut message);
+            current = Some(name.trim().to_string());
+        } else if current.is_some() {
+            if line.starts_with("failures:") || line.starts_with("note: run with") {
+                flush(&mut report, &mut current, &mut message);
+            } else if line.starts_with("thread '") && line.contains("panicked at") {
+                let name = current.clone().unwrap_or_default();
+                let after = &line[line.find("panicked at").unwrap() + "panicked at".len()..];
+                // Older toolchains: panicked at 'msg', src/lib.rs:10:5
+                if let Some(location) = find_location(after) {
+                    report.failure_mut(&name).location = Some(location);
+                }
+                if let Some(start) = after.find('\'') {
+                    if let Some(end) = after.rfind("', ") {
+                        if end > start {
+                            message.push(&after[start + 1..end]);
+                        }
+                    }
+                }
+            } else {
+                message.push(line);
+            }
+        }
+    }
+    flush(&mut report, &mut current, &mut message);
+
+    report
+}
+
+fn parse_pytest(output: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let mut section: Option<String> = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        // Final summary: "==== 1 failed, 2 passed, 1 skipped in 0.12s ===="
+        if trimmed.starts_with('=') && trimmed.contains(" in ") && trimmed.ends_with('=') {
+            let passed = count_of(trimmed, "passed");
+            let failed = count_of(trimmed, "failed") + count_of(trimmed, "error");
+            let skipped = count_of(trimmed, "skipped") + count_of(trimmed, "xfailed");
+            if passed + failed + skipped > 0 {
+                report.passed = passed;
+                report.failed = failed;
+                report.skipped = skipped;
+            }
+            section = None;
+        } else if trimmed.starts_with("___") && trimmed.ends_with("___") {
+            let name = trimmed.trim_matches('_').trim();
+            section = Some(name.to_string());
+        } else if let Some(rest) = trimmed
+            .strip_prefix("FAILED ")
+            .or_else(|| trimmed.strip_prefix("ERROR "))
+        {
+            let (name, message) = match rest.split_once(" - ") {
+                Some((name, message)) => (name.trim(), Some(message.trim().to_string())),
+                None => (rest.trim(), None),
+            };
+            let failure = report.failure_mut(name);
+            if message.is_some() {
+                failure.message = message;
+            }
+        } else if let Some(test) = &section {
+            // Traceback tail: "tests/test_x.py:5: AssertionError"
+            if let Some(location) = find_location(trimmed) {
+                if trimmed.starts_with(location.as_str()) {
+                    let key = report
+                        .failures
+                        .iter()
+                        .position(|f| f.name.ends_with(&format!("::{}", test)))
+                        .map(|idx| report.failures[idx].name.clone())
+                        .unwrap_or_else(|| test.clone());
+                    report.failure_mut(&key).location = Some(location);
+                }
+            }
+        }
+    }
+
+    // Failures collected from sections are keyed by bare test name; merge them into the summary
+    let bare: Vec<TestFailure> = report
+        .failures
+        .iter()
+        .filter(|f| !f.name.contains("::"))
+        .cloned()
+        .collect();
+    for section_failure in bare {
+        let suffix = format!("::{}", section_failure.name);
+        if let Some(qualified) = report
+            .failures
+            .iter_mut()
+            .find(|f| f.name.ends_with(&suffix))
+        {
+            qualified.location = qualified.location.take().or(section_failure.location);
+            qualified.message = qualified.message.take().or(section_failure.message);
+            report.failures.retain(|f| f.name != section_failure.name);
+        }
+    }
+
+    report
+}
+
+fn parse_jest(output: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let mut current: Option<String> = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if let Some(summary) = trimmed.strip_prefix("Tests:") {
+            report.passed = count_of(summary, "passed");
+            report.failed = count_of(summary, "failed");
+            report.skipped = count_of(summary, "skipped") + count_of(summary, "todo");
+            current = None;
+        } else if let Some(name) = trimmed.strip_prefix("● ") {
+            if name.starts_with("Test suite failed to run") {
+                current = None;
+                continue;
+            }
+            let name = name.replace(" › ", " > ");
+            report.failure_mut(&name);
+            current = Some(name);
+        } else if let Some(name) = &current {
+            if trimmed.starts_with("at ") {
+                if let Some(location) = find_location(trimmed) {
+                    let failure = report.failure_mut(name);
+                    if failure.location.is_none() && !location.contains("node_modules") {
+                        failure.location = Some(location);
+                    }
+                }
+            } else if !trimmed.is_empty() {
+                let failure = report.failure_mut(name);
+                if failure.message.is_none() {
+                    failure.message = Some(trimmed.to_string());
+                }
+            }
+        }
+    }
+
+    report
+}
+
+fn parse_vitest(output: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let mut current: Option<String> = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if let Some(summary) = trimmed.strip_prefix("Tests") {
+            if summary.contains('(') {
+                report.passed = count_of(summary, "passed");
+                report.failed = count_of(summary, "failed");
+                report.skipped = count_of(summary, "skipped") + count_of(summary, "todo");
+                current = None;
+                continue;
+            }
+        }
+
+        if let Some(name) = trimmed.strip_prefix("FAIL ") {
+            let name = name.trim();
+            // File-level FAIL lines without a test path are suite summaries
+            if name.contains(" > ") {
+                report.failure_mut(name);
+                current = Some(name.to_string());
+            }
+        } else if let Some(name) = &current {
+            if let Some(rest) = trimmed.strip_prefix('❯') {
+                if let Some(location) = find_location(rest) {
+                    let failure = report.failure_mut(name);
+                    if failure.location.is_none() {
+                        failure.location = Some(location);
+                    }
+                }
+            } else if !trimmed.is_empty() && !trimmed.starts_with('⎯') {
+                let failure = report.failure_mut(name);
+                if failure.message.is_none() {
+                    failure.message = Some(trimmed.to_string());
+                }
+            }
+        }
+    }
+
+    report
+}
+
+fn parse_go(output: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let mut pending: Vec<(String, String)> = Vec::new();
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("--- PASS: ") {
+            report.passed += 1;
+        } else if trimmed.starts_with("--- SKIP: ") {
+            report.skipped += 1;
+        } else if let Some(rest) = trimmed.strip_prefix("--- FAIL: ") {
+            report.failed += 1;
+            let name = rest.split_whitespace().next().unwrap_or(rest).to_string();
+            let failure = report.failure_mut(&name);
+            // t.Errorf lines are printed before the FAIL marker
+            if let Some((location, message)) = pending.first() {
+                failure.location = Some(location.clone());
+                failure.message = Some(
+                    std::iter::once(message.as_str())
+                        .chain(pending.iter().skip(1).map(|(_, m)| m.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                );
+            }
+            pending.clear();
+        } else if trimmed.starts_with("=== RUN") {
+            pending.clear();
+        } else if let Some(location) = find_location(trimmed) {
+            if trimmed.starts_with(location.as_str()) && location.contains("_test.go") {
+                let message = trimmed[location.len()..]
+                    .trim_start_matches(|c: char| c == ':' || c.is_ascii_digit())
+                    .trim()
+                    .to_string();
+                pending.push((location, message));
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_detect_framework() {
+        let dir = tempdir().unwrap();
+        assert_eq!(TestFramework::detect(dir.path()), None);
+
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"devDependencies":{"vitest":"1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            TestFramework::detect(dir.path()),
+            Some(TestFramework::Vitest)
+        );
+
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        assert_eq!(
+            TestFramework::detect(dir.path()),
+            Some(TestFramework::Cargo)
+        );
+    }
+
+    #[test]
+    fn test_filtered_command() {
+        assert_eq!(
+            TestFramework::Pytest.command(Some("test_login")),
+            "python -m pytest -rfEs -k 'test_login' 2>&1"
+        );
+        assert_eq!(TestFramework::Go.command(None), "go test -v ./... 2>&1");
+    }
+
+    #[test]
+    fn test_parse_cargo() {
+        let output = r#"
+running 3 tests
+test math::tests::adds ... ok
+test math::tests::subtracts ... FAILED
+test math::tests::slow ... ignored
+
+failures:
+
+---- math::tests::subtracts stdout ----
+thread 'math::tests::subtracts' panicked at src/math.rs:14:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+failures:
+    math::tests::subtracts
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+"#;
+        let report = parse_cargo(output);
+        assert_eq!((report.passed, report.failed, report.skipped), (1, 1, 1));
+        assert_eq!(report.failures.len(), 1);
+
+        let failure = &report.failures[0];
+        assert_eq!(failure.name, "math::tests::subtracts");
+        assert_eq!(failure.location.as_deref(), Some("src/math.rs:14"));
+        assert!(failure
+            .message
+            .as_deref()
+            .unwrap()
+            .starts_with("assertion `left == right` failed"));
+    }
+
+    #[test]
+    fn test_parse_pytest() {
+        let output = r#"
+__________________________________ test_total __________________________________
+
+    def test_total():
+>       assert total([1, 2]) == 4
+E       assert 3 == 4
+
+tests/test_cart.py:7: AssertionError
+=========================== short test summary info ============================
+FAILED tests/test_cart.py::test_total - assert 3 == 4
+==================== 1 failed, 4 passed, 1 skipped in 0.05s ====================
+"#;
+        let report = parse_pytest(output);
+        assert_eq!((report.passed, report.failed, report.skipped), (4, 1, 1));
+        assert_eq!(
+            report.failures,
+            vec![TestFailure {
+                name: "tests/test_cart.py::test_total".to_string(),
+                message: Some("assert 3 == 4".to_string()),
+                location: Some("tests/test_cart.py:7".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_jest() {
+        let output = r#"
+ FAIL  src/sum.test.js
+  ● math › adds numbers
+
+    expect(received).toBe(expected) // Object.is equality
+
+      at Object.<anonymous> (src/sum.test.js:4:17)
+
+Tests:       1 failed, 1 skipped, 3 passed, 5 total
+"#;
+        let report = parse_jest(output);
+        assert_eq!((report.passed, report.failed, report.skipped), (3, 1, 1));
+        assert_eq!(report.failures[0].name, "math > adds numbers");
+        assert_eq!(
+            report.failures[0].location.as_deref(),
+            Some("src/sum.test.js:4")
+        );
+    }
+
+    #[test]
+    fn test_parse_vitest() {
+        let output = r#"
+ ❯ src/sum.test.ts (3 tests | 1 failed | 1 skipped) 5ms
+   × math > adds numbers 3ms
+     → expected 3 to be 4 // Object.is equality
+
+⎯⎯⎯⎯⎯⎯⎯ Failed Tests 1 ⎯⎯⎯⎯⎯⎯⎯
+
+ FAIL  src/sum.test.ts > math > adds numbers
+AssertionError: expected 3 to be 4 // Object.is equality
+ ❯ src/sum.test.ts:5:17
+      3|   it('adds numbers', () => {
+      4|     const total = sum(1, 2)
+      5|     expect(total).toBe(4)
+       |                   ^
+
+⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯⎯[1/1]⎯
+
+ Test Files  1 failed (1)
+      Tests  1 failed | 1 passed | 1 skipped (3)
+"#;
+        let report = parse_vitest(output);
+        assert_eq!((report.passed, report.failed, report.skipped), (1, 1, 1));
+        assert_eq!(
+            report.failures,
+            vec![TestFailure {
+                name: "src/sum.test.ts > math > adds numbers".to_string(),
+                message: Some(
+                    "AssertionError: expected 3 to be 4 // Object.is equality".to_string()
+                ),
+                location: Some("src/sum.test.ts:5".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let output = r#"
+=== RUN   TestAdd
+--- PASS: TestAdd (0.00s)
+=== RUN   TestSub
+    math_test.go:12: expected 1, got 2
+--- FAIL: TestSub (0.00s)
+FAIL
+"#;
+        let report = parse_go(output);
+        assert_eq!((report.passed, report.failed, report.skipped), (1, 1, 0));
+        assert_eq!(report.failures[0].name, "TestSub");
+        assert_eq!(
+            report.failures[0].location.as_deref(),
+            Some("math_test.go:12")
+        );
+        assert_eq!(
+            report.failures[0].message.as_deref(),
+            Some("expected 1, got 2")
+        );
+    }
+}
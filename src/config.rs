@@ -0,0 +1,667 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use toml::{Table, Value};
+use tracing::warn;
+
+use crate::agents::{Conversation, CoordinatorOptions, FailurePolicy};
+use crate::llm::{ConfiguredModel, LlmClient, LmStudioClient, ModelSettings, OllamaClient};
+use crate::mcp::config::McpSettings;
+use crate::permissions::PermissionMode;
+
+/// Prefix of the environment variables that override settings;
+/// `LCA_AGENTS__CODE__MODEL` sets `agents.code.model`
+const ENV_PREFIX: &str = "LCA_";
+
+/// Settings from `~/.lca/config.toml`, overlaid by `<project>/.lca/config.toml`
+/// and then by `LCA_*` environment variables. Command line flags are applied
+/// last, by the caller.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub provider: ProviderConfig,
+    /// Model settings for everything without settings of its own
+    pub model: ModelSettings,
+    /// Model settings per agent, keyed by agent name
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub agents: BTreeMap<String, ModelSettings>,
+    pub coordinator: CoordinatorConfig,
+    pub conversation: ConversationConfig,
+    pub permissions: PermissionConfig,
+    pub ui: UiConfig,
+    #[serde(skip_serializing_if = "McpSettings::is_empty")]
+    pub mcp: McpSettings,
+    /// Config files that were found, in the order they were applied
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    Ollama,
+    #[value(name = "lmstudio")]
+    LmStudio,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    pub name: Provider,
+    pub ollama_url: String,
+    pub lmstudio_url: String,
+    /// Price used by `/cost` to estimate what a session's tokens cost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_per_million_tokens: Option<f64>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            name: Provider::Ollama,
+            ollama_url: OllamaClient::DEFAULT_URL.to_string(),
+            lmstudio_url: LmStudioClient::DEFAULT_URL.to_string(),
+            cost_per_million_tokens: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoordinatorConfig {
+    /// Maximum number of independent subtasks running at once
+    pub max_parallel: usize,
+    /// Extra attempts for a failed subtask before the failure policy applies
+    pub retries: usize,
+    pub on_failure: FailurePolicy,
+}
+
+impl Default for CoordinatorConfig {
+    fn default() -> Self {
+        let options = CoordinatorOptions::default();
+        Self {
+            max_parallel: options.max_parallel,
+            retries: options.max_retries,
+            on_failure: options.failure_policy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConversationConfig {
+    /// Characters of history kept before older turns are summarized
+    pub max_chars: usize,
+    /// Latest messages that are never summarized
+    pub keep_recent: usize,
+    /// Model that writes the summaries; `model.model` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl Default for ConversationConfig {
+    fn default() -> Self {
+        Self {
+            max_chars: Conversation::DEFAULT_MAX_CHARS,
+            keep_recent: Conversation::DEFAULT_KEEP_RECENT,
+            model: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PermissionConfig {
+    /// `ask` or `allow-all`
+    pub mode: PermissionMode,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// Interactive mode history; `~/.lca/history.txt` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_path: Option<PathBuf>,
+    /// Project context database; `~/.lca/context.db` when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_db: Option<PathBuf>,
+    /// Print every subtask's full output for multi-agent tasks
+    pub details: bool,
+}
+
+impl UiConfig {
+    pub fn history_path(&self) -> PathBuf {
+        self.history_path
+            .as_deref()
+            .map(expand_home)
+            .unwrap_or_else(|| lca_dir().join("history.txt"))
+    }
+
+    pub fn context_db(&self) -> PathBuf {
+        self.context_db
+            .as_deref()
+            .map(expand_home)
+            .unwrap_or_else(|| lca_dir().join("context.db"))
+    }
+}
+
+impl Config {
+    /// Load and validate every layer for a project
+    pub fn load(project_dir: impl AsRef<Path>) -> Result<Self> {
+        Self::load_layers(
+            &[user_path()],
+            &[project_path(project_dir)],
+            std::env::vars(),
+        )
+    }
+
+    /// Files in `project_paths` come with the repository being worked on, so
+    /// they cannot turn off permission prompts
+    fn load_layers(
+        user_paths: &[PathBuf],
+        project_paths: &[PathBuf],
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        let mut merged = Table::new();
+        let mut sources = Vec::new();
+
+        let layers = user_paths
+            .iter()
+            .map(|path| (path, false))
+            .chain(project_paths.iter().map(|path| (path, true)));
+        for (path, is_project) in layers.filter(|(p, _)| p.exists()) {
+            let mut table = read_table(path)?;
+            // Check each file on its own so an error names the file at fault
+            Self::from_table(table.clone())
+                .with_context(|| format!("Invalid config {:?}", path))?;
+            if is_project && remove_allow_all(&mut table) {
+                warn!(
+                    "Ignoring permissions.mode = \"allow-all\" in {:?}; set it in {:?}, \
+                     LCA_PERMISSIONS__MODE or pass --allow-all",
+                    path,
+                    user_path()
+                );
+            }
+            if is_project {
+                for name in remove_mcp_grants(&mut table) {
+                    warn!(
+                        "Ignoring \"allow\" tool policies and trust_annotations for MCP server \
+                         '{}' in {:?}; only your own config can let its tools run without asking",
+                        name, path
+                    );
+                }
+            }
+            merge(&mut merged, table);
+            sources.push(path.clone());
+        }
+
+        let mut from_env = Vec::new();
+        for (name, value) in env {
+            let Some(key) = env_key(&name) else {
+                continue;
+            };
+            set_value(&mut merged, &key, parse_value(&value))
+                .with_context(|| format!("Invalid environment variable {}", name))?;
+            from_env.push(name);
+        }
+
+        let mut config = Self::from_table(merged).with_context(|| {
+            format!(
+                "Invalid configuration from environment variables {}",
+                from_env.join(", ")
+            )
+        })?;
+        config.sources = sources;
+        Ok(config)
+    }
+
+    fn from_table(table: Table) -> Result<Self> {
+        let config: Self = Value::Table(table).try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the types can't express
+    pub fn validate(&self) -> Result<()> {
+        for (name, url) in [
+            ("provider.ollama_url", &self.provider.ollama_url),
+            ("provider.lmstudio_url", &self.provider.lmstudio_url),
+        ] {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                anyhow::bail!("{} must be an http:// or https:// URL, not '{}'", name, url);
+            }
+        }
+
+        if self
+            .provider
+            .cost_per_million_tokens
+            .is_some_and(|c| c < 0.0)
+        {
+            anyhow::bail!("provider.cost_per_million_tokens must not be negative");
+        }
+
+        let models = std::iter::once(("model".to_string(), &self.model)).chain(
+            self.agents
+                .iter()
+                .map(|(name, settings)| (format!("agents.{}", name), settings)),
+        );
+        for (table, settings) in models {
+            if settings.model.as_deref() == Some("") {
+                anyhow::bail!("{}.model must not be empty", table);
+            }
+            if let Some(temperature) = settings.temperature {
+                if !(0.0..=2.0).contains(&temperature) {
+                    anyhow::bail!(
+                        "{}.temperature must be between 0 and 2, not {}",
+                        table,
+                        temperature
+                    );
+                }
+            }
+            if settings.max_tokens == Some(0) {
+                anyhow::bail!("{}.max_tokens must be at least 1", table);
+            }
+        }
+
+        if self.coordinator.max_parallel == 0 {
+            anyhow::bail!("coordinator.max_parallel must be at least 1");
+        }
+        if self.conversation.max_chars == 0 {
+            anyhow::bail!("conversation.max_chars must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// The configured provider's client, sending `"default"` requests to
+    /// the configured model
+    pub fn llm_client(&self) -> Arc<ConfiguredModel> {
+        let client: Arc<dyn LlmClient> = match self.provider.name {
+            Provider::Ollama => Arc::new(OllamaClient::new(&self.provider.ollama_url)),
+            Provider::LmStudio => Arc::new(LmStudioClient::new(&self.provider.lmstudio_url)),
+        };
+        Arc::new(ConfiguredModel::new(client, self.model.clone()))
+    }
+
+    pub fn coordinator_options(&self) -> CoordinatorOptions {
+        CoordinatorOptions {
+            max_parallel: self.coordinator.max_parallel,
+            max_retries: self.coordinator.retries,
+            failure_policy: self.coordinator.on_failure,
+            ..Default::default()
+        }
+    }
+
+    pub fn conversation(&self) -> Conversation {
+        Conversation::default()
+            .with_limits(self.conversation.max_chars, self.conversation.keep_recent)
+            .with_model(self.conversation.model.clone())
+    }
+
+    /// The effective configuration as TOML
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// One setting by its dotted key, e.g. `agents.code.model`. Tables are
+    /// printed as TOML, strings without quotes.
+    pub fn get(&self, key: &str) -> Result<String> {
+        let table = Table::try_from(self)?;
+        let value =
+            get_value(&table, key).ok_or_else(|| anyhow::anyhow!("No setting '{}' is set", key))?;
+        Ok(match value {
+            Value::String(s) => s.clone(),
+            Value::Table(t) => toml::to_string(t)?,
+            other => other.to_string(),
+        })
+    }
+}
+
+/// Set a dotted key in a config file, keeping its comments and layout. The
+/// file must still be valid afterwards or it is left untouched.
+pub fn set_in_file(path: &Path, key: &str, value: &str) -> Result<()> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    let mut document: toml_edit::DocumentMut = text
+        .parse()
+        .with_context(|| format!("Invalid TOML in {:?}", path))?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, tables) = parts
+        .split_last()
+        .filter(|(last, _)| !last.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Empty setting name"))?;
+    let mut table = document.as_table_mut() as &mut dyn toml_edit::TableLike;
+    for part in tables {
+        let item = table.entry(part).or_insert(toml_edit::table());
+        table = item
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow::anyhow!("'{}' in '{}' is not a table", part, key))?;
+    }
+    let value = value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| value.into());
+    table.insert(last, toml_edit::value(value));
+
+    let updated = document.to_string();
+    let table: Table = toml::from_str(&updated)?;
+    Config::from_table(table).with_context(|| format!("Cannot set {}", key))?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, updated).with_context(|| format!("Failed to write {:?}", path))
+}
+
+fn lca_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".lca")
+}
+
+/// Drop `permissions.mode = "allow-all"` from a layer; returns whether it was set
+fn remove_allow_all(table: &mut Table) -> bool {
+    let Some(Value::Table(permissions)) = table.get_mut("permissions") else {
+        return false;
+    };
+    if permissions.get("mode").and_then(Value::as_str) != Some("allow-all") {
+        return false;
+    }
+    permissions.remove("mode");
+    true
+}
+
+/// Turn `allow` tool policies of `[mcp.servers]` into `ask` and drop
+/// `trust_annotations`, returning the servers that had either
+fn remove_mcp_grants(table: &mut Table) -> Vec<String> {
+    let Some(Value::Table(servers)) = table.get_mut("mcp").and_then(|mcp| mcp.get_mut("servers"))
+    else {
+        return Vec::new();
+    };
+
+    let mut changed = Vec::new();
+    for (name, server) in servers.iter_mut() {
+        let Value::Table(server) = server else {
+            continue;
+        };
+        let mut granted = false;
+        for key in ["trust_annotations", "trustAnnotations"] {
+            granted |= server.remove(key).is_some();
+        }
+        for key in ["tool_policy", "toolPolicy"] {
+            let Some(Value::Table(policies)) = server.get_mut(key) else {
+                continue;
+            };
+            for (_, policy) in policies.iter_mut() {
+                if policy.as_str() == Some("allow") {
+                    *policy = Value::String("ask".to_string());
+                    granted = true;
+                }
+            }
+        }
+        if granted {
+            changed.push(name.clone());
+        }
+    }
+    changed
+}
+
+pub fn user_path() -> PathBuf {
+    lca_dir().join("config.toml")
+}
+
+pub fn project_path(project_dir: impl AsRef<Path>) -> PathBuf {
+    project_dir.as_ref().join(".lca").join("config.toml")
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var("HOME")) {
+        (Ok(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    let data =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    toml::from_str(&data).with_context(|| format!("Invalid config {:?}", path))
+}
+
+/// Overlay `layer` on `base`; tables merge key by key, anything else replaces
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// `LCA_PROVIDER__OLLAMA_URL` -> `provider.ollama_url`; variables without a
+/// `__` are not settings
+fn env_key(name: &str) -> Option<String> {
+    let key = name.strip_prefix(ENV_PREFIX)?;
+    key.contains("__")
+        .then(|| key.to_lowercase().replace("__", "."))
+}
+
+/// A TOML value if the text is one, otherwise the text as a string
+fn parse_value(text: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", text))
+        .ok()
+        .and_then(|mut t| t.remove("value"))
+        .unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+fn get_value<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let (tables, last) = match key.rsplit_once('.') {
+        Some((tables, last)) => (tables.split('.').collect(), last),
+        None => (Vec::new(), key),
+    };
+    let mut table = table;
+    for part in tables {
+        table = table.get(part)?.as_table()?;
+    }
+    table.get(last)
+}
+
+fn set_value(table: &mut Table, key: &str, value: Value) -> Result<()> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap_or_default();
+    let mut table = table;
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow::anyhow!("'{}' in '{}' is not a table", part, key))?;
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user.toml");
+        let project = dir.path().join("project.toml");
+
+        std::fs::write(
+            &user,
+            r#"
+            [provider]
+            name = "lmstudio"
+            lmstudio_url = "http://gpu-box:1234/v1"
+
+            [model]
+            model = "qwen2.5-coder:7b"
+            temperature = 0.2
+
+            [agents.code]
+            model = "qwen2.5-coder:32b"
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            &project,
+            r#"
+            [agents.code]
+            temperature = 0.1
+
+            [permissions]
+            mode = "allow-all"
+
+            [mcp.servers.docs]
+            url = "https://mcp.example.com/mcp"
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::load_layers(
+            std::slice::from_ref(&user),
+            &[project.clone(), dir.path().join("missing.toml")],
+            env(&[
+                ("LCA_MODEL__MAX_TOKENS", "2048"),
+                ("LCA_COORDINATOR__ON_FAILURE", "replan"),
+                ("LCA_HOME", "ignored"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.sources, vec![user, project]);
+        assert_eq!(config.provider.name, Provider::LmStudio);
+        assert_eq!(config.provider.lmstudio_url, "http://gpu-box:1234/v1");
+        assert_eq!(config.provider.ollama_url, "http://localhost:11434");
+        assert_eq!(config.model.model.as_deref(), Some("qwen2.5-coder:7b"));
+        assert_eq!(config.model.max_tokens, Some(2048));
+        let code = &config.agents["code"];
+        assert_eq!(code.model.as_deref(), Some("qwen2.5-coder:32b"));
+        assert_eq!(code.temperature, Some(0.1));
+        // A repository can't grant itself unprompted writes and commands
+        assert_eq!(config.permissions.mode, PermissionMode::Ask);
+        assert_eq!(config.coordinator.on_failure, FailurePolicy::Replan);
+        assert_eq!(config.coordinator.max_parallel, 4);
+        assert!(config.mcp.servers.contains_key("docs"));
+
+        assert_eq!(
+            config.get("agents.code.model").unwrap(),
+            "qwen2.5-coder:32b"
+        );
+        assert_eq!(config.get("model.max_tokens").unwrap(), "2048");
+        assert!(config.get("agents.shell.model").is_err());
+        let shown: Config = toml::from_str(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(shown.agents, config.agents);
+    }
+
+    #[test]
+    fn test_only_user_layers_can_allow_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "[permissions]\nmode = \"allow-all\"").unwrap();
+        let mode = |user: &[PathBuf], project: &[PathBuf], vars: &[(&str, &str)]| {
+            Config::load_layers(user, project, env(vars))
+                .unwrap()
+                .permissions
+                .mode
+        };
+
+        let file = std::slice::from_ref(&path);
+        assert_eq!(mode(file, &[], &[]), PermissionMode::AllowAll);
+        assert_eq!(mode(&[], file, &[]), PermissionMode::Ask);
+        assert_eq!(
+            mode(&[], file, &[("LCA_PERMISSIONS__MODE", "allow-all")]),
+            PermissionMode::AllowAll
+        );
+
+        std::fs::write(
+            &path,
+            "[mcp.servers.fs]\ncommand = \"fs\"\ntrust_annotations = true\n\
+             tool_policy = { \"*\" = \"allow\", \"rm\" = \"deny\" }",
+        )
+        .unwrap();
+        let mcp = |user: &[PathBuf], project: &[PathBuf]| {
+            let config = Config::load_layers(user, project, env(&[])).unwrap();
+            toml::Value::try_from(&config.mcp.servers["fs"]).unwrap()
+        };
+        let server = mcp(file, &[]);
+        assert_eq!(server["toolPolicy"]["*"].as_str(), Some("allow"));
+        assert_eq!(server["trustAnnotations"].as_bool(), Some(true));
+        let server = mcp(&[], file);
+        assert_eq!(server["toolPolicy"]["*"].as_str(), Some("ask"));
+        assert_eq!(server["toolPolicy"]["rm"].as_str(), Some("deny"));
+        assert!(server.get("trustAnnotations").is_none());
+    }
+
+    #[test]
+    fn test_invalid_settings_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let load = |text: &str, vars: &[(&str, &str)]| {
+            std::fs::write(&path, text).unwrap();
+            Config::load_layers(std::slice::from_ref(&path), &[], env(vars))
+                .map_err(|e| format!("{:#}", e))
+        };
+
+        let error = load("[provider]\nname = \"openai\"", &[]).unwrap_err();
+        assert!(error.contains("config.toml"), "{}", error);
+        assert!(error.contains("openai"), "{}", error);
+        assert!(load("[ui]\ncolour = true", &[])
+            .unwrap_err()
+            .contains("colour"));
+        assert!(load("[model]\ntemperature = 3.5", &[])
+            .unwrap_err()
+            .contains("model.temperature"));
+        assert!(load("[provider]\nollama_url = \"localhost\"", &[])
+            .unwrap_err()
+            .contains("provider.ollama_url"));
+
+        let error = load("", &[("LCA_COORDINATOR__MAX_PARALLEL", "zero")]).unwrap_err();
+        assert!(error.contains("LCA_COORDINATOR__MAX_PARALLEL"), "{}", error);
+        assert!(load("", &[("LCA_COORDINATOR__MAX_PARALLEL", "0")]).is_err());
+    }
+
+    #[test]
+    fn test_set_in_file_keeps_comments_and_validates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".lca").join("config.toml");
+
+        set_in_file(&path, "agents.review.model", "qwen2.5-coder:32b").unwrap();
+        set_in_file(&path, "model.temperature", "0.3").unwrap();
+        std::fs::write(
+            &path,
+            format!("# my settings\n{}", std::fs::read_to_string(&path).unwrap()),
+        )
+        .unwrap();
+        set_in_file(&path, "permissions.mode", "allow-all").unwrap();
+
+        let error = set_in_file(&path, "model.temperature", "hot").unwrap_err();
+        assert!(format!("{:#}", error).contains("model.temperature"));
+        assert!(set_in_file(&path, "agents.review.model.name", "x").is_err());
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# my settings"));
+        let config = Config::load_layers(&[path], &[], Vec::new()).unwrap();
+        assert_eq!(
+            config.agents["review"].model.as_deref(),
+            Some("qwen2.5-coder:32b")
+        );
+        assert_eq!(config.model.temperature, Some(0.3));
+        assert_eq!(config.permissions.mode, PermissionMode::AllowAll);
+    }
+}
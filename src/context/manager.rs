@@ -2,6 +2,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use sled::Db;
 use std::path::PathBuf;
+use std::sync::RwLock;
 use tracing::debug;
 
 #[allow(dead_code)]
@@ -26,7 +27,7 @@ pub struct SessionMemory {
 pub struct ContextManager {
     #[allow(dead_code)]
     db: Db,
-    project_context: Option<ProjectContext>,
+    project_context: RwLock<Option<ProjectContext>>,
 }
 
 #[allow(dead_code)]
@@ -35,7 +36,7 @@ impl ContextManager {
         let db = sled::open(db_path.into())?;
         Ok(Self {
             db,
-            project_context: None,
+            project_context: RwLock::new(None),
         })
     }
 
@@ -45,7 +46,7 @@ impl ContextManager {
         Self::new(db_path)
     }
 
-    pub async fn initialize_project(&mut self, root_path: impl Into<String>) -> Result<()> {
+    pub async fn initialize_project(&self, root_path: impl Into<String>) -> Result<()> {
         let root_path = root_path.into();
         debug!("Initializing project context for: {}", root_path);
 
@@ -58,7 +59,7 @@ impl ContextManager {
         let language = self.detect_language(&root_path).await;
         let framework = self.detect_framework(&root_path).await;
 
-        self.project_context = Some(ProjectContext {
+        *self.project_context.write().unwrap() = Some(ProjectContext {
             name,
             root_path: root_path.clone(),
             language,
@@ -72,7 +73,7 @@ impl ContextManager {
     }
 
     pub async fn get_project_summary(&self) -> Result<String> {
-        if let Some(ctx) = &self.project_context {
+        if let Some(ctx) = self.project_context.read().unwrap().as_ref() {
             let mut summary = format!("Project: {}\nPath: {}", ctx.name, ctx.root_path);
 
             if let Some(lang) = &ctx.language {
@@ -126,7 +127,7 @@ impl ContextManager {
     }
 
     fn save_project_context(&self) -> Result<()> {
-        if let Some(ctx) = &self.project_context {
+        if let Some(ctx) = self.project_context.read().unwrap().as_ref() {
             let serialized = serde_json::to_vec(ctx)?;
             self.db.insert(b"project_context", serialized)?;
             self.db.flush()?;
@@ -134,9 +135,9 @@ impl ContextManager {
         Ok(())
     }
 
-    pub fn load_project_context(&mut self) -> Result<()> {
+    pub fn load_project_context(&self) -> Result<()> {
         if let Some(data) = self.db.get(b"project_context")? {
-            self.project_context = Some(serde_json::from_slice(&data)?);
+            *self.project_context.write().unwrap() = Some(serde_json::from_slice(&data)?);
         }
         Ok(())
     }
@@ -170,15 +171,17 @@ impl ContextManager {
         Ok(sessions)
     }
 
-    pub fn set_metadata(&mut self, key: String, value: String) {
-        if let Some(ctx) = &mut self.project_context {
+    pub fn set_metadata(&self, key: String, value: String) {
+        if let Some(ctx) = self.project_context.write().unwrap().as_mut() {
             ctx.metadata.insert(key, value);
-            let _ = self.save_project_context();
         }
+        let _ = self.save_project_context();
     }
 
     pub fn get_metadata(&self, key: &str) -> Option<String> {
         self.project_context
+            .read()
+            .unwrap()
             .as_ref()
             .and_then(|ctx| ctx.metadata.get(key).cloned())
     }
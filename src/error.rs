@@ -0,0 +1,60 @@
+/// Process exit codes, documented in the README for scripts and CI
+pub mod exit_code {
+    pub const SUCCESS: u8 = 0;
+    /// The task ran and failed, or an error without its own code occurred
+    pub const TASK_FAILED: u8 = 1;
+    /// Invalid command line arguments (clap's own code)
+    #[allow(dead_code)]
+    pub const USAGE: u8 = 2;
+    pub const CONFIG: u8 = 3;
+    pub const PERMISSION_DENIED: u8 = 4;
+    pub const LLM_UNREACHABLE: u8 = 5;
+}
+
+/// Failures the CLI reports with their own exit code. They travel inside
+/// `anyhow::Error` and are found again with [`exit_code_for`].
+#[derive(Debug, thiserror::Error)]
+pub enum LcaError {
+    #[error("{0}")]
+    Config(String),
+    #[error("{0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    LlmUnreachable(String),
+}
+
+impl LcaError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Config(_) => exit_code::CONFIG,
+            Self::PermissionDenied(_) => exit_code::PERMISSION_DENIED,
+            Self::LlmUnreachable(_) => exit_code::LLM_UNREACHABLE,
+        }
+    }
+}
+
+/// The exit code for an error, from the first `LcaError` in its chain
+pub fn exit_code_for(error: &anyhow::Error) -> u8 {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<LcaError>())
+        .map_or(exit_code::TASK_FAILED, LcaError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code_is_found_through_context() {
+        let error = Err::<(), _>(LcaError::LlmUnreachable("down".into()))
+            .context("Routing failed")
+            .unwrap_err();
+        assert_eq!(exit_code_for(&error), exit_code::LLM_UNREACHABLE);
+        assert_eq!(format!("{:#}", error), "Routing failed: down");
+
+        let error = anyhow::anyhow!("something else");
+        assert_eq!(exit_code_for(&error), exit_code::TASK_FAILED);
+    }
+}
@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// Something that happened while a task ran; streamed by `--output jsonl`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    SubtaskStarted {
+        index: usize,
+        agent: String,
+        description: String,
+    },
+    SubtaskFinished {
+        index: usize,
+        agent: String,
+        success: bool,
+    },
+    FileChanged {
+        path: String,
+    },
+    CommandRun {
+        command: String,
+        success: bool,
+    },
+    PermissionDenied {
+        /// `file_write` or `shell`
+        operation: String,
+        target: String,
+    },
+}
+
+pub type Listener = Arc<dyn Fn(&Event) + Send + Sync>;
+
+/// What tasks did through the tool executor, kept for the final report and
+/// passed on to a listener as it happens
+#[derive(Default)]
+pub struct Activity {
+    events: Mutex<Vec<Event>>,
+    listener: Mutex<Option<Listener>>,
+}
+
+impl Activity {
+    pub fn set_listener(&self, listener: Option<Listener>) {
+        *self.listener.lock().unwrap() = listener;
+    }
+
+    pub fn emit(&self, event: Event) {
+        let listener = self.listener.lock().unwrap().clone();
+        if let Some(listener) = listener {
+            listener(&event);
+        }
+        self.events.lock().unwrap().push(event);
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_reach_the_listener_and_the_record() {
+        let activity = Activity::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        activity.set_listener(Some(Arc::new(move |event: &Event| {
+            sink.lock()
+                .unwrap()
+                .push(serde_json::to_string(event).unwrap());
+        })));
+
+        activity.emit(Event::FileChanged {
+            path: "src/main.rs".into(),
+        });
+        activity.set_listener(None);
+        activity.emit(Event::CommandRun {
+            command: "cargo test".into(),
+            success: false,
+        });
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![r#"{"event":"file_changed","path":"src/main.rs"}"#]
+        );
+        assert_eq!(activity.events().len(), 2);
+    }
+}
@@ -0,0 +1,686 @@
+use anyhow::{Context as _, Result};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::config::Config;
+use crate::llm::types::Usage;
+use crate::llm::{ConfiguredModel, LlmClient, Message};
+use crate::orchestrator::AgentSystem;
+use crate::permissions::PermissionMode;
+
+/// Built-in slash commands with their usage and a short description
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("help", "/help", "List these commands"),
+    (
+        "agent",
+        "/agent [name|auto]",
+        "Send the following tasks to one agent, or route them again",
+    ),
+    ("model", "/model [name]", "Show or switch the default model"),
+    (
+        "plan",
+        "/plan <task>",
+        "Review the plan before anything runs",
+    ),
+    ("history", "/history", "List this session's tasks"),
+    (
+        "clear",
+        "/clear",
+        "Forget this session's tasks, conversation and attached resources",
+    ),
+    (
+        "permissions",
+        "/permissions [ask|allow-all]",
+        "Show or change the permission mode",
+    ),
+    ("undo", "/undo", "Revert the files the last task changed"),
+    ("cost", "/cost", "Show the requests and tokens used so far"),
+    (
+        "save",
+        "/save [name]",
+        "Save the session under .lca/sessions",
+    ),
+    (
+        "load",
+        "/load [name]",
+        "Restore a saved session, or list them",
+    ),
+    (
+        "attach",
+        "/attach <uri>",
+        "Add an MCP resource to every following task",
+    ),
+    ("detach", "/detach", "Remove attached resources"),
+    (
+        "prompts",
+        "/prompts",
+        "List MCP prompts, run with /<prompt> [args]",
+    ),
+];
+
+/// One task run in the session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionEntry {
+    pub task: String,
+    /// The pinned agent, if routing was skipped
+    pub agent: Option<String>,
+    pub success: bool,
+    pub output: String,
+}
+
+/// What `/save` stores and `/load` restores
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub entries: Vec<SessionEntry>,
+    pub agent: Option<String>,
+    pub model: Option<String>,
+    pub attached: Vec<String>,
+    /// What the agents are given as the conversation so far
+    #[serde(default)]
+    pub conversation: Vec<Message>,
+}
+
+impl Session {
+    fn dir(working_dir: impl AsRef<Path>) -> PathBuf {
+        working_dir.as_ref().join(".lca").join("sessions")
+    }
+
+    fn path(working_dir: impl AsRef<Path>, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            anyhow::bail!(
+                "Invalid session name '{}' (use letters, digits, '-', '_' and '.')",
+                name
+            );
+        }
+        Ok(Self::dir(working_dir).join(format!("{}.json", name)))
+    }
+
+    /// Save under `<working_dir>/.lca/sessions/` and return the file path
+    pub fn save(&self, working_dir: impl AsRef<Path>, name: &str) -> Result<PathBuf> {
+        let path = Self::path(&working_dir, name)?;
+        std::fs::create_dir_all(Self::dir(&working_dir))?;
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write session: {:?}", path))?;
+        Ok(path)
+    }
+
+    pub fn load(working_dir: impl AsRef<Path>, name: &str) -> Result<Self> {
+        let path = Self::path(working_dir, name)?;
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("No saved session '{}'", name))?;
+        serde_json::from_str(&data).with_context(|| format!("Invalid session file: {:?}", path))
+    }
+
+    /// Names of the saved sessions, sorted
+    pub fn list(working_dir: impl AsRef<Path>) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(Self::dir(working_dir))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+/// Tab completion for slash commands, agent names, session names and paths
+pub struct ReplHelper {
+    commands: Vec<String>,
+    agents: Vec<String>,
+    working_dir: PathBuf,
+    files: FilenameCompleter,
+}
+
+impl ReplHelper {
+    pub fn new(agents: Vec<String>, working_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            commands: COMMANDS
+                .iter()
+                .map(|(name, _, _)| name.to_string())
+                .collect(),
+            agents,
+            working_dir: working_dir.into(),
+            files: FilenameCompleter::new(),
+        }
+    }
+
+    /// Also complete these MCP prompt commands
+    pub fn add_commands(&mut self, commands: impl IntoIterator<Item = String>) {
+        for command in commands {
+            if !self.commands.contains(&command) {
+                self.commands.push(command);
+            }
+        }
+    }
+
+    /// Candidates from the command, agent and session names, or `None` when
+    /// the word is a path
+    fn candidates(&self, line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+
+        if start == 0 {
+            if let Some(prefix) = word.strip_prefix('/') {
+                return Some((0, matching("/", &self.commands, prefix)));
+            }
+            if let Some(prefix) = word.strip_prefix('@') {
+                return Some((0, matching("@", &self.agents, prefix)));
+            }
+            return None;
+        }
+
+        // Only the first argument of these commands is a name
+        let mut words = before[..start].split_whitespace();
+        let command = words.next()?;
+        if words.next().is_some() {
+            return None;
+        }
+        let options = match command {
+            "/agent" => {
+                let mut options = self.agents.clone();
+                options.push("auto".to_string());
+                options
+            }
+            "/permissions" => vec!["ask".to_string(), "allow-all".to_string()],
+            "/load" => Session::list(&self.working_dir),
+            _ => return None,
+        };
+        Some((start, matching("", &options, word)))
+    }
+}
+
+fn matching(sigil: &str, options: &[String], prefix: &str) -> Vec<Pair> {
+    let mut pairs: Vec<Pair> = options
+        .iter()
+        .filter(|option| option.starts_with(prefix))
+        .map(|option| Pair {
+            display: format!("{}{}", sigil, option),
+            replacement: format!("{}{} ", sigil, option),
+        })
+        .collect();
+    pairs.sort_by(|a, b| a.display.cmp(&b.display));
+    pairs
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        match self.candidates(line, pos) {
+            Some(candidates) => Ok(candidates),
+            None => self.files.complete_path(line, pos),
+        }
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// The interactive loop: tasks, slash commands and the session they build up
+pub struct Repl {
+    system: Arc<AgentSystem>,
+    model: Arc<ConfiguredModel>,
+    working_dir: String,
+    details: bool,
+    cost_per_million_tokens: Option<f64>,
+    session: Session,
+    usage_at_start: Usage,
+}
+
+impl Repl {
+    pub fn new(
+        system: Arc<AgentSystem>,
+        model: Arc<ConfiguredModel>,
+        working_dir: impl Into<String>,
+        config: &Config,
+    ) -> Self {
+        let usage_at_start = model.usage();
+        Self {
+            system,
+            model,
+            working_dir: working_dir.into(),
+            details: config.ui.details,
+            cost_per_million_tokens: config.provider.cost_per_million_tokens,
+            session: Session::default(),
+            usage_at_start,
+        }
+    }
+
+    pub async fn run(mut self, history_path: &Path) -> Result<()> {
+        println!("Interactive mode - type 'exit' to quit, /help for commands");
+        println!("Prefix a task with @agent (e.g. @shell) to pick the agent yourself");
+        println!("Use Tab to complete commands, agent names and paths, arrow keys for history");
+
+        let agents = self
+            .system
+            .agents()
+            .iter()
+            .map(|a| a.name().to_string())
+            .collect();
+        let mut rl: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+        rl.set_helper(Some(ReplHelper::new(agents, &self.working_dir)));
+
+        // Load history from file if it exists
+        let _ = rl.load_history(history_path);
+
+        loop {
+            let prompt = match &self.session.agent {
+                Some(agent) => format!("\n{}> ", agent),
+                None => "\n> ".to_string(),
+            };
+
+            match rl.readline(&prompt) {
+                Ok(line) => {
+                    let line = line.trim();
+
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    if line == "exit" || line == "quit" {
+                        println!("Goodbye!");
+                        break;
+                    }
+
+                    // Add to history
+                    let _ = rl.add_history_entry(line);
+
+                    match line.strip_prefix('/') {
+                        Some(rest) => {
+                            let (command, input) =
+                                rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                            self.run_command(command, input.trim(), &mut rl).await;
+                        }
+                        None => self.run_task(line, false).await,
+                    }
+                }
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
+                    println!("\nGoodbye!");
+                    break;
+                }
+                Err(err) => {
+                    eprintln!("Error reading input: {}", err);
+                    break;
+                }
+            }
+        }
+
+        // Save history on exit
+        if let Some(parent) = history_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = rl.save_history(history_path);
+        Ok(())
+    }
+
+    async fn run_command(
+        &mut self,
+        command: &str,
+        input: &str,
+        rl: &mut Editor<ReplHelper, DefaultHistory>,
+    ) {
+        match command {
+            "help" => print_help(),
+            "agent" => self.pin_agent(input),
+            "model" if input.is_empty() => println!(
+                "Default model: {}",
+                self.model.model().as_deref().unwrap_or("default")
+            ),
+            "model" => {
+                self.model.set_model(Some(input.to_string()));
+                println!("Default model is now {}", input);
+            }
+            "plan" if input.is_empty() => eprintln!("Usage: /plan <task>"),
+            "plan" => self.run_task(input, true).await,
+            "history" => self.print_history(),
+            "clear" => {
+                self.session.entries.clear();
+                self.session.attached.clear();
+                self.system.conversation.clear();
+                println!("Cleared the session's tasks, conversation and attached resources");
+            }
+            "permissions" => self.permissions(input),
+            "undo" => self.undo().await,
+            "cost" => self.print_cost(),
+            "save" => self.save(input),
+            "load" => self.load(input),
+            "attach" if input.is_empty() => eprintln!("Usage: /attach <uri>"),
+            "attach" => {
+                self.session.attached.push(input.to_string());
+                println!(
+                    "Attached {} to the following tasks (/detach to clear)",
+                    input
+                );
+            }
+            "detach" => {
+                self.session.attached.clear();
+                println!("Detached all resources");
+            }
+            "prompts" => {
+                let commands = print_prompts(&self.system).await;
+                if let Some(helper) = rl.helper_mut() {
+                    helper.add_commands(commands);
+                }
+            }
+            name => match self.system.expand_prompt(name, input).await {
+                Ok(Some(prompt)) => self.run_task(&prompt, false).await,
+                Ok(None) => eprintln!("Unknown command /{} (/help lists commands)", name),
+                Err(e) => eprintln!("/{}: {}", name, e),
+            },
+        }
+    }
+
+    async fn run_task(&mut self, task: &str, planned: bool) {
+        self.system.tool_executor.undo_stack().begin(task);
+
+        let agent = self.session.agent.clone();
+        let outcome = match self
+            .system
+            .attach_resources(task, &self.session.attached)
+            .await
+        {
+            Ok(task) if planned => {
+                crate::plan_and_execute(&self.system, &task, &self.working_dir).await
+            }
+            Ok(task) => match &agent {
+                Some(agent) => self.system.execute_with_agent(agent, &task).await,
+                None => self.system.execute_task(&task).await,
+            }
+            .map(Some),
+            Err(e) => Err(e),
+        };
+
+        match outcome {
+            Ok(None) => {}
+            Ok(Some(result)) => {
+                info!(
+                    "Task result - Success: {}, Output length: {}",
+                    result.success,
+                    result.output.len()
+                );
+                if result.success {
+                    println!("\n{}", result.output);
+                } else {
+                    eprintln!("\nError: {}", result.output);
+                }
+                if self.details {
+                    crate::print_details(&result);
+                }
+                self.session.entries.push(SessionEntry {
+                    task: task.to_string(),
+                    agent,
+                    success: result.success,
+                    output: result.output,
+                });
+            }
+            Err(e) => {
+                eprintln!("\nFailed to execute task: {}", e);
+            }
+        }
+    }
+
+    fn pin_agent(&mut self, name: &str) {
+        match name {
+            "" => {
+                match &self.session.agent {
+                    Some(agent) => println!("Tasks go to the {} agent", agent),
+                    None => println!("Tasks are routed to the best agent"),
+                }
+                let names: Vec<String> = self
+                    .system
+                    .agents()
+                    .iter()
+                    .map(|a| a.name().to_string())
+                    .collect();
+                println!("Agents: {}", names.join(", "));
+            }
+            "auto" => {
+                self.session.agent = None;
+                println!("Tasks are routed to the best agent again");
+            }
+            name if self.system.get_agent(name).is_some() => {
+                self.session.agent = Some(name.to_string());
+                println!(
+                    "Tasks go to the {} agent (/agent auto to route again)",
+                    name
+                );
+            }
+            name => eprintln!("Unknown agent '{}' (/agent lists them)", name),
+        }
+    }
+
+    fn print_history(&self) {
+        if self.session.entries.is_empty() {
+            println!("No tasks in this session yet");
+            return;
+        }
+        for (idx, entry) in self.session.entries.iter().enumerate() {
+            let status = if entry.success { "ok" } else { "failed" };
+            let agent = entry
+                .agent
+                .as_deref()
+                .map(|a| format!(" @{}", a))
+                .unwrap_or_default();
+            println!("{:>3}. [{}{}] {}", idx + 1, status, agent, entry.task);
+        }
+    }
+
+    fn permissions(&self, input: &str) {
+        let pm = &self.system.permission_manager;
+        if input.is_empty() {
+            println!("Permission mode: {}", pm.mode());
+            return;
+        }
+        match input.parse::<PermissionMode>() {
+            Ok(mode) => {
+                if mode == PermissionMode::AllowAll {
+                    println!("WARNING: file writes and shell commands will run without asking");
+                }
+                pm.set_mode(mode);
+                println!("Permission mode: {}", pm.mode());
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    async fn undo(&self) {
+        match self.system.tool_executor.undo_stack().undo().await {
+            Ok(Some((task, paths))) => {
+                println!("Reverted the changes of: {}", task);
+                for path in paths {
+                    println!("  {}", path.display());
+                }
+                println!("Effects of shell commands are not undone");
+            }
+            Ok(None) => println!("Nothing to undo"),
+            Err(e) => eprintln!("Undo failed: {:#}", e),
+        }
+    }
+
+    fn print_cost(&self) {
+        let usage = self.model.usage() - self.usage_at_start;
+        println!("Requests: {}", usage.requests);
+        println!(
+            "Tokens:   {} prompt + {} completion = {}",
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            usage.total_tokens()
+        );
+        match self.cost_per_million_tokens {
+            Some(price) => println!(
+                "Cost:     ${:.4} (at ${} per million tokens)",
+                usage.total_tokens() as f64 * price / 1_000_000.0,
+                price
+            ),
+            None => println!("Set provider.cost_per_million_tokens to estimate a cost"),
+        }
+    }
+
+    fn save(&mut self, name: &str) {
+        let name = if name.is_empty() { "last" } else { name };
+        self.session.model = self.model.model();
+        self.session.conversation = self.system.conversation.messages();
+        match self.session.save(&self.working_dir, name) {
+            Ok(path) => println!("Saved session to {} (/load {})", path.display(), name),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+
+    fn load(&mut self, name: &str) {
+        if name.is_empty() {
+            let names = Session::list(&self.working_dir);
+            if names.is_empty() {
+                println!("No saved sessions");
+            } else {
+                println!("Saved sessions: {}", names.join(", "));
+            }
+            return;
+        }
+
+        match Session::load(&self.working_dir, name) {
+            Ok(session) => {
+                if session.model.is_some() {
+                    self.model.set_model(session.model.clone());
+                }
+                self.system
+                    .conversation
+                    .replace(session.conversation.clone());
+                println!(
+                    "Loaded session '{}' with {} task(s)",
+                    name,
+                    session.entries.len()
+                );
+                self.session = session;
+            }
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+}
+
+fn print_help() {
+    for (_, usage, description) in COMMANDS {
+        println!("  {:<30} {}", usage, description);
+    }
+    println!("  {:<30} Run the task with that agent", "@agent <task>");
+    println!("  {:<30} Leave interactive mode", "exit, quit");
+}
+
+/// List the MCP prompts that can be run as slash-commands and return their
+/// command names
+async fn print_prompts(system: &AgentSystem) -> Vec<String> {
+    let commands = system.prompt_commands().await;
+    if commands.is_empty() {
+        println!("No MCP prompts are available");
+    }
+
+    for (command, prompt) in &commands {
+        match &prompt.description {
+            Some(description) => println!("{}  {}", prompt.usage(command), description),
+            None => println!("{}", prompt.usage(command)),
+        }
+    }
+    commands.into_iter().map(|(command, _)| command).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completions(helper: &ReplHelper, line: &str) -> Option<(usize, Vec<String>)> {
+        helper
+            .candidates(line, line.len())
+            .map(|(start, pairs)| (start, pairs.into_iter().map(|p| p.display).collect()))
+    }
+
+    #[test]
+    fn test_completion() {
+        let dir = tempfile::tempdir().unwrap();
+        Session::default().save(dir.path(), "refactor").unwrap();
+
+        let mut helper = ReplHelper::new(vec!["shell".into(), "search".into()], dir.path());
+        helper.add_commands(["summarize".to_string()]);
+
+        assert_eq!(
+            completions(&helper, "/s"),
+            Some((0, vec!["/save".into(), "/summarize".into()]))
+        );
+        assert_eq!(
+            completions(&helper, "@s"),
+            Some((0, vec!["@search".into(), "@shell".into()]))
+        );
+        assert_eq!(
+            completions(&helper, "/agent "),
+            Some((7, vec!["auto".into(), "search".into(), "shell".into()]))
+        );
+        assert_eq!(
+            completions(&helper, "/permissions al"),
+            Some((13, vec!["allow-all".into()]))
+        );
+        assert_eq!(
+            completions(&helper, "/load re"),
+            Some((6, vec!["refactor".into()]))
+        );
+        // Anything else is a path
+        assert_eq!(completions(&helper, "fix src/ma"), None);
+        assert_eq!(completions(&helper, "/agent shell x"), None);
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = Session {
+            entries: vec![SessionEntry {
+                task: "list files".into(),
+                agent: Some("shell".into()),
+                success: true,
+                output: "src".into(),
+            }],
+            agent: Some("shell".into()),
+            model: Some("qwen2.5-coder:7b".into()),
+            attached: vec!["file:///notes.md".into()],
+            conversation: vec![Message::user("list files"), Message::assistant("src")],
+        };
+
+        let path = session.save(dir.path(), "work").unwrap();
+        assert!(path.ends_with(".lca/sessions/work.json"));
+        let loaded = Session::load(dir.path(), "work").unwrap();
+        assert_eq!(loaded.entries[0].task, "list files");
+        assert_eq!(loaded.model.as_deref(), Some("qwen2.5-coder:7b"));
+        assert_eq!(loaded.conversation, session.conversation);
+        assert_eq!(Session::list(dir.path()), vec!["work"]);
+
+        assert!(session.save(dir.path(), "../escape").is_err());
+        assert!(Session::load(dir.path(), "missing").is_err());
+    }
+}
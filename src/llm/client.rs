@@ -2,31 +2,46 @@ use anyhow::Result;
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::json;
+use std::sync::{Arc, RwLock};
 use tracing::{debug, error};
 
-use super::types::{ChatRequest, ChatResponse, LmStudioResponse, Message};
+use super::types::{
+    ChatRequest, ChatResponse, LmStudioResponse, Message, ModelSettings, Usage, UsageCounter,
+};
+use crate::error::LcaError;
 
 #[async_trait]
 pub trait LlmClient: Send + Sync {
     async fn chat(&self, request: ChatRequest) -> Result<String>;
     async fn chat_with_history(&self, messages: Vec<Message>, model: &str) -> Result<String>;
+
+    /// Requests and tokens used since the client was created, as far as the
+    /// provider reports them
+    fn usage(&self) -> Usage {
+        Usage::default()
+    }
 }
 
 pub struct OllamaClient {
     client: Client,
     base_url: String,
+    usage: UsageCounter,
 }
 
 impl OllamaClient {
+    pub const DEFAULT_URL: &'static str = "http://localhost:11434";
+
     pub fn new(base_url: impl Into<String>) -> Self {
         Self {
             client: Client::new(),
             base_url: base_url.into(),
+            usage: UsageCounter::default(),
         }
     }
 
+    #[allow(dead_code)]
     pub fn default() -> Self {
-        Self::new("http://localhost:11434")
+        Self::new(Self::DEFAULT_URL)
     }
 }
 
@@ -37,7 +52,13 @@ impl LlmClient for OllamaClient {
 
         debug!("Sending chat request to Ollama: {:?}", request.model);
 
-        let response = self.client.post(&url).json(&request).send().await?;
+        let response = self
+            .client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| send_error("Ollama", &self.base_url, e))?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -47,6 +68,8 @@ impl LlmClient for OllamaClient {
         }
 
         let chat_response: ChatResponse = response.json().await?;
+        self.usage
+            .add(chat_response.prompt_eval_count, chat_response.eval_count);
         Ok(chat_response.message.content)
     }
 
@@ -54,23 +77,32 @@ impl LlmClient for OllamaClient {
         let request = ChatRequest::new(model, messages);
         self.chat(request).await
     }
+
+    fn usage(&self) -> Usage {
+        self.usage.get()
+    }
 }
 
 pub struct LmStudioClient {
     client: Client,
     base_url: String,
+    usage: UsageCounter,
 }
 
 impl LmStudioClient {
+    pub const DEFAULT_URL: &'static str = "http://localhost:1234/v1";
+
     pub fn new(base_url: impl Into<String>) -> Self {
         Self {
             client: Client::new(),
             base_url: base_url.into(),
+            usage: UsageCounter::default(),
         }
     }
 
+    #[allow(dead_code)]
     pub fn default() -> Self {
-        Self::new("http://localhost:1234/v1")
+        Self::new(Self::DEFAULT_URL)
     }
 }
 
@@ -102,7 +134,13 @@ impl LlmClient for LmStudioClient {
             "max_tokens": request.max_tokens.unwrap_or(2000),
         });
 
-        let response = self.client.post(&url).json(&body).send().await?;
+        let response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| send_error("LM Studio", &self.base_url, e))?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -112,6 +150,12 @@ impl LlmClient for LmStudioClient {
         }
 
         let lm_response: LmStudioResponse = response.json().await?;
+        let (prompt_tokens, completion_tokens) = lm_response
+            .usage
+            .as_ref()
+            .map(|u| (u.prompt_tokens, u.completion_tokens))
+            .unwrap_or_default();
+        self.usage.add(prompt_tokens, completion_tokens);
 
         let content = lm_response
             .choices
@@ -127,6 +171,87 @@ impl LlmClient for LmStudioClient {
         let request = ChatRequest::new(model, messages);
         self.chat(request).await
     }
+
+    fn usage(&self) -> Usage {
+        self.usage.get()
+    }
+}
+
+/// A provider that can't be reached gets its own error (and exit code)
+fn send_error(provider: &str, base_url: &str, error: reqwest::Error) -> anyhow::Error {
+    if error.is_connect() || error.is_timeout() {
+        LcaError::LlmUnreachable(format!(
+            "Cannot reach {} at {}: {}",
+            provider, base_url, error
+        ))
+        .into()
+    } else {
+        error.into()
+    }
+}
+
+/// Sends requests for the `"default"` model to the configured model and fills
+/// in the sampling settings a request leaves unset
+pub struct ConfiguredModel {
+    inner: Arc<dyn LlmClient>,
+    settings: RwLock<ModelSettings>,
+}
+
+impl ConfiguredModel {
+    pub fn new(inner: Arc<dyn LlmClient>, settings: ModelSettings) -> Self {
+        Self {
+            inner,
+            settings: RwLock::new(settings),
+        }
+    }
+
+    /// The model `"default"` requests go to, if one is configured
+    pub fn model(&self) -> Option<String> {
+        self.settings.read().unwrap().model.clone()
+    }
+
+    /// Switch the model for the following requests
+    pub fn set_model(&self, model: Option<String>) {
+        self.settings.write().unwrap().model = model;
+    }
+
+    /// Wrap `inner` only when there is something to apply
+    pub fn wrap(inner: Arc<dyn LlmClient>, settings: &ModelSettings) -> Arc<dyn LlmClient> {
+        if settings.is_empty() {
+            inner
+        } else {
+            Arc::new(Self::new(inner, settings.clone()))
+        }
+    }
+}
+
+#[async_trait]
+impl LlmClient for ConfiguredModel {
+    async fn chat(&self, mut request: ChatRequest) -> Result<String> {
+        let settings = self.settings.read().unwrap().clone();
+        if let Some(model) = settings
+            .model
+            .as_ref()
+            .filter(|_| request.model == "default")
+        {
+            request.model = model.clone();
+        }
+        if request.temperature.is_none() {
+            request.temperature = settings.temperature.map(|t| t as f32);
+        }
+        if request.max_tokens.is_none() {
+            request.max_tokens = settings.max_tokens;
+        }
+        self.inner.chat(request).await
+    }
+
+    async fn chat_with_history(&self, messages: Vec<Message>, model: &str) -> Result<String> {
+        self.chat(ChatRequest::new(model, messages)).await
+    }
+
+    fn usage(&self) -> Usage {
+        self.inner.usage()
+    }
 }
 
 #[cfg(test)]
@@ -141,4 +266,114 @@ mod tests {
         let lm_studio = LmStudioClient::default();
         assert_eq!(lm_studio.base_url, "http://localhost:1234/v1");
     }
+
+    /// Records the model and temperature of each request
+    struct Recorder(std::sync::Mutex<Vec<(String, Option<f32>)>>);
+
+    #[async_trait]
+    impl LlmClient for Recorder {
+        async fn chat(&self, request: ChatRequest) -> Result<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((request.model.clone(), request.temperature));
+            Ok(String::new())
+        }
+
+        async fn chat_with_history(&self, messages: Vec<Message>, model: &str) -> Result<String> {
+            self.chat(ChatRequest::new(model, messages)).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_configured_model_replaces_default() {
+        let recorder = Arc::new(Recorder(Default::default()));
+        let settings = ModelSettings {
+            model: Some("qwen2.5-coder:7b".to_string()),
+            temperature: Some(0.5),
+            max_tokens: None,
+        };
+        let client = ConfiguredModel::wrap(recorder.clone(), &settings);
+
+        client
+            .chat_with_history(Vec::new(), "default")
+            .await
+            .unwrap();
+        client
+            .chat_with_history(Vec::new(), "llama3.2:1b")
+            .await
+            .unwrap();
+        client
+            .chat(ChatRequest::new("default", Vec::new()).with_temperature(0.0))
+            .await
+            .unwrap();
+
+        let switched = ConfiguredModel::new(recorder.clone(), settings);
+        switched.set_model(Some("llama3.2:3b".to_string()));
+        switched
+            .chat_with_history(Vec::new(), "default")
+            .await
+            .unwrap();
+        assert_eq!(switched.model().as_deref(), Some("llama3.2:3b"));
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                ("qwen2.5-coder:7b".to_string(), Some(0.5)),
+                ("llama3.2:1b".to_string(), Some(0.5)),
+                ("qwen2.5-coder:7b".to_string(), Some(0.0)),
+                ("llama3.2:3b".to_string(), Some(0.5)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_provider() {
+        // Nothing listens on a port that was just released
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = OllamaClient::new(format!("http://127.0.0.1:{}", port));
+
+        let error = client
+            .chat_with_history(vec![Message::user("hi")], "default")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            crate::error::exit_code_for(&error),
+            crate::error::exit_code::LLM_UNREACHABLE
+        );
+        assert!(error.to_string().starts_with("Cannot reach Ollama"));
+    }
+
+    #[test]
+    fn test_usage_is_read_from_responses() {
+        let ollama: ChatResponse = serde_json::from_value(json!({
+            "model": "qwen2.5-coder:7b",
+            "message": {"role": "assistant", "content": "hi"},
+            "done": true,
+            "prompt_eval_count": 26,
+            "eval_count": 298
+        }))
+        .unwrap();
+        assert_eq!((ollama.prompt_eval_count, ollama.eval_count), (26, 298));
+
+        let lm_studio: LmStudioResponse = serde_json::from_value(json!({
+            "id": "chatcmpl-1",
+            "choices": [{"message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 5, "total_tokens": 17}
+        }))
+        .unwrap();
+        let usage = lm_studio.usage.unwrap();
+
+        let counter = UsageCounter::default();
+        counter.add(ollama.prompt_eval_count, ollama.eval_count);
+        counter.add(usage.prompt_tokens, usage.completion_tokens);
+        let total = counter.get();
+        assert_eq!(total.requests, 2);
+        assert_eq!(total.total_tokens(), 341);
+        assert_eq!((total - Usage::default()).prompt_tokens, 38);
+    }
 }
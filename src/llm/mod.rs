@@ -1,5 +1,5 @@
 pub mod client;
 pub mod types;
 
-pub use client::{LlmClient, LmStudioClient, OllamaClient};
-pub use types::Message;
+pub use client::{ConfiguredModel, LlmClient, LmStudioClient, OllamaClient};
+pub use types::{Message, ModelSettings};
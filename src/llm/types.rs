@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -8,7 +9,7 @@ pub enum Role {
     Assistant,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
     pub content: String,
@@ -61,13 +62,11 @@ impl ChatRequest {
         }
     }
 
-    #[allow(dead_code)]
     pub fn with_temperature(mut self, temp: f32) -> Self {
         self.temperature = Some(temp);
         self
     }
 
-    #[allow(dead_code)]
     pub fn with_max_tokens(mut self, tokens: usize) -> Self {
         self.max_tokens = Some(tokens);
         self
@@ -80,6 +79,26 @@ impl ChatRequest {
     }
 }
 
+/// Model and sampling settings from the configuration; unset fields leave
+/// the request as the agent built it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelSettings {
+    /// Model name as the provider knows it, used for `"default"` requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+}
+
+impl ModelSettings {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct StreamChunk {
@@ -96,6 +115,10 @@ pub struct ChatResponse {
     #[allow(dead_code)]
     #[serde(default)]
     pub done: bool,
+    #[serde(default)]
+    pub prompt_eval_count: u64,
+    #[serde(default)]
+    pub eval_count: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -103,6 +126,16 @@ pub struct LmStudioResponse {
     #[allow(dead_code)]
     pub id: String,
     pub choices: Vec<Choice>,
+    #[serde(default)]
+    pub usage: Option<LmStudioUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LmStudioUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -111,3 +144,57 @@ pub struct Choice {
     #[allow(dead_code)]
     pub finish_reason: Option<String>,
 }
+
+/// Requests and tokens reported by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl std::ops::Sub for Usage {
+    type Output = Usage;
+
+    fn sub(self, earlier: Usage) -> Usage {
+        Usage {
+            requests: self.requests.saturating_sub(earlier.requests),
+            prompt_tokens: self.prompt_tokens.saturating_sub(earlier.prompt_tokens),
+            completion_tokens: self
+                .completion_tokens
+                .saturating_sub(earlier.completion_tokens),
+        }
+    }
+}
+
+/// Running totals a client adds each response to
+#[derive(Debug, Default)]
+pub struct UsageCounter {
+    requests: AtomicU64,
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+}
+
+impl UsageCounter {
+    pub fn add(&self, prompt_tokens: u64, completion_tokens: u64) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.prompt_tokens
+            .fetch_add(prompt_tokens, Ordering::Relaxed);
+        self.completion_tokens
+            .fetch_add(completion_tokens, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> Usage {
+        Usage {
+            requests: self.requests.load(Ordering::Relaxed),
+            prompt_tokens: self.prompt_tokens.load(Ordering::Relaxed),
+            completion_tokens: self.completion_tokens.load(Ordering::Relaxed),
+        }
+    }
+}
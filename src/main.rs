@@ -1,18 +1,30 @@
 mod agents;
+mod config;
 mod context;
+mod error;
+mod events;
+mod interactive;
 mod llm;
 mod mcp;
 mod orchestrator;
+mod output;
 mod permissions;
 mod tools;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use std::process::ExitCode;
 use std::sync::Arc;
 use tracing::{info, Level};
 
-use llm::{LlmClient, LmStudioClient, OllamaClient};
-use orchestrator::AgentSystem;
+use agents::{AgentResult, FailurePolicy, SubtaskReport};
+use config::{Config, Provider};
+use error::{exit_code, LcaError};
+use interactive::Repl;
+use orchestrator::mcp_serve::McpServe;
+use orchestrator::plan::review_plan;
+use orchestrator::{AgentSystem, ExecutionPlan};
+use output::{OutputFormat, TaskReport};
 use permissions::{PermissionManager, PermissionMode};
 
 #[derive(Parser)]
@@ -22,8 +34,9 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    #[arg(short, long, default_value = "ollama")]
-    provider: String,
+    /// LLM provider [default: provider.name from the config, else ollama]
+    #[arg(short, long, value_enum)]
+    provider: Option<Provider>,
 
     #[arg(short, long, default_value = ".")]
     working_dir: String,
@@ -36,12 +49,44 @@ struct Cli {
         help = "Allow all operations without prompting (USE WITH CAUTION)"
     )]
     allow_all: bool,
+
+    /// Maximum number of independent subtasks to run at once [default: 4]
+    #[arg(long)]
+    max_parallel: Option<usize>,
+
+    /// Extra attempts for a failed subtask before the failure policy applies [default: 1]
+    #[arg(long)]
+    retries: Option<usize>,
+
+    /// What to do when a subtask fails: fail-fast, continue or replan [default: continue]
+    #[arg(long)]
+    on_failure: Option<FailurePolicy>,
+
+    /// Also print every subtask's full output for multi-agent tasks
+    #[arg(long)]
+    details: bool,
+
+    /// How to print task results: text, json, or jsonl (streamed events)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     Execute {
         task: String,
+
+        /// Show the plan for review before running anything
+        #[arg(long)]
+        plan: bool,
+
+        /// Attach an MCP resource's contents to the task (repeatable)
+        #[arg(long = "resource", value_name = "URI")]
+        resources: Vec<String>,
+    },
+    /// Re-run a plan saved by `execute --plan`
+    RunPlan {
+        path: String,
     },
     Init {
         #[arg(default_value = ".")]
@@ -52,12 +97,101 @@ enum Commands {
         task: String,
     },
     Interactive,
+    /// Review changes and report structured findings
+    Review {
+        /// Review staged changes instead of the working tree
+        #[arg(long, conflicts_with = "target")]
+        staged: bool,
+
+        /// Commit range (<base>..<head>) or file to review
+        target: Option<String>,
+
+        /// Write findings as SARIF to this path
+        #[arg(long)]
+        sarif: Option<String>,
+
+        /// Exit non-zero when a finding has at least this severity (error, warning, info)
+        #[arg(long)]
+        fail_on: Option<String>,
+    },
+    /// Inspect the configured MCP servers
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommand,
+    },
+    /// Serve LCA's tools and agents to an MCP client over stdio
+    McpServe,
+    /// Show or change settings in the layered config.toml files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum McpCommand {
+    /// List the tools every MCP server offers, by the name to call them with
+    Tools,
+    /// List the resources every MCP server offers
+    Resources,
+    /// Print the contents of a resource
+    Read { uri: String },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration, with every layer and flag applied
+    Show,
+    /// Print one setting by its dotted key, e.g. agents.code.model
+    Get { key: String },
+    /// Change a setting in ~/.lca/config.toml
+    Set {
+        key: String,
+        value: String,
+
+        /// Change the project's .lca/config.toml instead
+        #[arg(long)]
+        project: bool,
+    },
+}
+
+impl Cli {
+    /// Command line flags are the last layer over the config files
+    fn apply_flags(&self, config: &mut Config) {
+        if let Some(provider) = self.provider {
+            config.provider.name = provider;
+        }
+        if self.allow_all {
+            config.permissions.mode = PermissionMode::AllowAll;
+        }
+        if let Some(max_parallel) = self.max_parallel {
+            config.coordinator.max_parallel = max_parallel;
+        }
+        if let Some(retries) = self.retries {
+            config.coordinator.retries = retries;
+        }
+        if let Some(on_failure) = self.on_failure {
+            config.coordinator.on_failure = on_failure;
+        }
+        config.ui.details |= self.details;
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
+    let format = cli.output;
+    match run(cli).await {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            let code = error::exit_code_for(&error);
+            output::print_error(format, &error, code);
+            ExitCode::from(code)
+        }
+    }
+}
 
+async fn run(cli: Cli) -> Result<u8> {
     let log_level = if cli.verbose {
         Level::DEBUG
     } else {
@@ -73,142 +207,406 @@ async fn main() -> Result<()> {
         EnvFilter::new("lca=info,warn") // Our app INFO, others WARN
     };
 
-    tracing_subscriber::fmt()
+    // stdout carries the protocol when serving MCP and the result with
+    // --output json or jsonl, so logs go to stderr
+    let serving = matches!(cli.command, Commands::McpServe);
+    let machine_output = cli.output != OutputFormat::Text;
+    let subscriber = tracing_subscriber::fmt()
         .with_max_level(log_level)
-        .with_env_filter(filter)
-        .init();
-
-    let llm_client: Arc<dyn LlmClient> = match cli.provider.as_str() {
-        "ollama" => Arc::new(OllamaClient::default()),
-        "lmstudio" => Arc::new(LmStudioClient::default()),
-        _ => {
-            eprintln!("Unknown provider: {}. Using Ollama.", cli.provider);
-            Arc::new(OllamaClient::default())
-        }
-    };
-
-    let permission_mode = if cli.allow_all {
-        info!("WARNING: Running with --allow-all flag (blanket permissions enabled)");
-        PermissionMode::AllowAll
+        .with_env_filter(filter);
+    if serving || machine_output {
+        subscriber.with_writer(std::io::stderr).init();
     } else {
-        PermissionMode::Ask
-    };
+        subscriber.init();
+    }
 
-    let permission_manager = Arc::new(PermissionManager::new(permission_mode));
-    let system = AgentSystem::new(llm_client, &cli.working_dir, permission_manager)?;
+    // Setting a value must work even when the current config doesn't load
+    if let Commands::Config {
+        command:
+            ConfigCommand::Set {
+                key,
+                value,
+                project,
+            },
+    } = &cli.command
+    {
+        if *project && key == "permissions.mode" && value == "allow-all" {
+            return Err(config_error(anyhow::anyhow!(
+                "permissions.mode = \"allow-all\" is ignored in project config; \
+                 set it in the user config (without --project) instead"
+            )));
+        }
+        let path = if *project {
+            config::project_path(&cli.working_dir)
+        } else {
+            config::user_path()
+        };
+        config::set_in_file(&path, key, value).map_err(config_error)?;
+        println!("Set {} in {}", key, path.display());
+        return Ok(exit_code::SUCCESS);
+    }
 
-    match cli.command {
-        Commands::Execute { task } => {
-            info!("Executing task: {}", task);
-            let result = system.execute_task(&task).await?;
+    let mut config = Config::load(&cli.working_dir).map_err(config_error)?;
+    cli.apply_flags(&mut config);
+    config.validate().map_err(config_error)?;
 
-            if result.success {
-                println!("\nSUCCESS\n{}", result.output);
-            } else {
-                eprintln!("\nFAILED\n{}", result.output);
-            }
-        }
-        Commands::Init { path } => {
-            info!("Initializing project at: {}", path);
-            system.initialize_project(&path).await?;
-            println!("Project initialized successfully!");
-        }
-        Commands::Agent { name, task } => {
-            info!("Executing task with {} agent: {}", name, task);
-
-            let agent = system
-                .get_agent(&name)
-                .ok_or_else(|| anyhow::anyhow!("Agent '{}' not found", name))?;
-
-            let mut context = agents::AgentContext::new(&cli.working_dir);
-            let result = agent
-                .execute(
-                    &task,
-                    &mut context,
-                    Arc::clone(&system.llm_client),
-                    Arc::clone(&system.tool_executor),
-                    Arc::clone(&system.context_manager),
-                )
-                .await?;
-
-            if result.success {
-                println!("\nSUCCESS\n{}", result.output);
-            } else {
-                eprintln!("\nFAILED\n{}", result.output);
+    if let Commands::Config { command } = &cli.command {
+        match command {
+            ConfigCommand::Show => {
+                for source in &config.sources {
+                    println!("# from {}", source.display());
+                }
+                print!("{}", config.to_toml()?);
             }
+            ConfigCommand::Get { key } => println!("{}", config.get(key)?),
+            ConfigCommand::Set { .. } => unreachable!("handled above"),
         }
-        Commands::Interactive => {
-            use rustyline::error::ReadlineError;
-            use rustyline::DefaultEditor;
+        return Ok(exit_code::SUCCESS);
+    }
 
-            println!("Interactive mode - type 'exit' to quit");
-            println!("Use arrow keys to navigate history, Ctrl+C or Ctrl+D to exit");
+    let permission_mode = config.permissions.mode.clone();
+    if permission_mode == PermissionMode::AllowAll {
+        info!("WARNING: Running with blanket permissions (--allow-all or permissions.mode)");
+    }
 
-            let mut rl = DefaultEditor::new()?;
+    // An MCP client or a script reading JSON owns the terminal, so nothing
+    // can be asked interactively
+    let permission_manager = Arc::new(
+        PermissionManager::new(permission_mode).with_interactive(!serving && !machine_output),
+    );
+    let model = config.llm_client();
+    let system = Arc::new(AgentSystem::new(
+        model.clone(),
+        &cli.working_dir,
+        permission_manager,
+        &config,
+    )?);
+    let details = config.ui.details;
+    let report = Reporter::new(&system, cli.output, details);
+
+    // Every way out of the command, errors included, stops the MCP servers
+    // gracefully rather than leaving them to be killed
+    let result: Result<u8> = async {
+        let mut code = exit_code::SUCCESS;
+
+        match cli.command {
+            Commands::Execute {
+                task,
+                plan,
+                resources,
+            } => {
+                if plan && machine_output {
+                    anyhow::bail!(
+                        "--plan needs a terminal and cannot be used with --output json or jsonl"
+                    );
+                }
+                info!("Executing task: {}", task);
+                let task = system.attach_resources(&task, &resources).await?;
+                report.start(&task);
+                let result = if plan {
+                    plan_and_execute(&system, &task, &cli.working_dir).await?
+                } else {
+                    Some(system.execute_task(&task).await?)
+                };
+
+                if let Some(result) = result {
+                    code = report.finish(&result);
+                }
+            }
+            Commands::RunPlan { path } => {
+                let plan = ExecutionPlan::load(&path)?;
+                info!("Re-running plan from {}: {}", path, plan.task);
+                if machine_output {
+                    report.start(&plan.task);
+                } else {
+                    println!("{}", plan.render());
+                }
 
-            // Load history from file if it exists
-            let history_path = std::env::var("HOME")
-                .map(|h| format!("{}/.lca/history.txt", h))
-                .unwrap_or_else(|_| ".lca-history.txt".to_string());
+                let result = system.execute_plan(&plan).await?;
+                code = report.finish(&result);
+            }
+            Commands::Init { path } => {
+                info!("Initializing project at: {}", path);
+                system.initialize_project(&path).await?;
+                println!("Project initialized successfully!");
+            }
+            Commands::Agent { name, task } => {
+                info!("Executing task with {} agent: {}", name, task);
+                report.start(&task);
+
+                let agent = system
+                    .get_agent(&name)
+                    .ok_or_else(|| anyhow::anyhow!("Agent '{}' not found", name))?;
+
+                let mut context = agents::AgentContext::new(&cli.working_dir);
+                let result = agent
+                    .execute(
+                        &task,
+                        &mut context,
+                        Arc::clone(&system.llm_client),
+                        Arc::clone(&system.tool_executor),
+                        Arc::clone(&system.context_manager),
+                    )
+                    .await?;
+
+                code = report.finish(&result);
+            }
+            Commands::Review {
+                staged,
+                target,
+                sarif,
+                fail_on,
+            } => {
+                use agents::review::{self, Finding, Severity};
+
+                let fail_on = match fail_on.as_deref() {
+                    Some(value) => Some(
+                        Severity::parse(value)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown severity: {}", value))?,
+                    ),
+                    None => None,
+                };
+
+                let spec = if staged {
+                    "--staged".to_string()
+                } else {
+                    target.unwrap_or_default()
+                };
+
+                let agent = system
+                    .get_agent("review")
+                    .ok_or_else(|| anyhow::anyhow!("Agent 'review' not found"))?;
+
+                let mut context = agents::AgentContext::new(&cli.working_dir);
+                context.set_metadata("review_target", spec);
+
+                let result = agent
+                    .execute(
+                        "Review the code changes",
+                        &mut context,
+                        Arc::clone(&system.llm_client),
+                        Arc::clone(&system.tool_executor),
+                        Arc::clone(&system.context_manager),
+                    )
+                    .await?;
+
+                if !result.success {
+                    eprintln!("\nFAILED\n{}", result.output);
+                    return Ok(exit_code::TASK_FAILED);
+                }
 
-            let _ = rl.load_history(&history_path);
+                let findings: Vec<Finding> = result
+                    .metadata
+                    .get("findings")
+                    .and_then(|f| serde_json::from_str(f).ok())
+                    .unwrap_or_default();
 
-            loop {
-                let readline = rl.readline("\n> ");
+                if let Some(path) = sarif {
+                    let log = serde_json::to_string_pretty(&review::to_sarif(&findings))?;
+                    std::fs::write(&path, log)?;
+                    info!("Wrote SARIF report to {}", path);
+                }
 
-                match readline {
-                    Ok(line) => {
-                        let task = line.trim();
+                println!("\n{}", result.output);
 
-                        if task.is_empty() {
-                            continue;
+                if let Some(threshold) = fail_on {
+                    if findings.iter().any(|f| f.severity >= threshold) {
+                        code = exit_code::TASK_FAILED;
+                    }
+                }
+            }
+            Commands::Mcp { command } => {
+                let mcp = system.mcp_client.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("No MCP servers are configured (see .lca/mcp.json)")
+                })?;
+
+                match command {
+                    McpCommand::Tools => {
+                        let all_tools = mcp.list_all_tools().await?;
+                        let mut servers: Vec<&String> = all_tools.keys().collect();
+                        servers.sort();
+
+                        for server in servers {
+                            println!("{}:", server);
+                            let tools = &all_tools[server];
+                            if tools.is_empty() {
+                                println!("  (no tools)");
+                            }
+                            for tool in tools {
+                                println!(
+                                    "  {}  {}",
+                                    mcp::client::qualified_name(server, &tool.name),
+                                    tool.description
+                                );
+                            }
                         }
-
-                        if task == "exit" || task == "quit" {
-                            println!("Goodbye!");
-                            break;
+                        for (tool, servers) in mcp.tool_collisions().await {
+                            eprintln!(
+                                "Tool '{}' is offered by {}; call it by its qualified name",
+                                tool,
+                                servers.join(", ")
+                            );
                         }
-
-                        // Add to history
-                        let _ = rl.add_history_entry(task);
-
-                        match system.execute_task(task).await {
-                            Ok(result) => {
-                                info!(
-                                    "Task result - Success: {}, Output length: {}",
-                                    result.success,
-                                    result.output.len()
-                                );
-                                if result.success {
-                                    println!("\n{}", result.output);
-                                } else {
-                                    eprintln!("\nError: {}", result.output);
-                                }
+                        for (server, error) in mcp.unavailable_servers().await {
+                            eprintln!("MCP server '{}' is unavailable: {}", server, error);
+                        }
+                    }
+                    McpCommand::Resources => {
+                        let all_resources = mcp.list_all_resources().await;
+                        let mut servers: Vec<&String> = all_resources.keys().collect();
+                        servers.sort();
+
+                        for server in servers {
+                            println!("{}:", server);
+                            let resources = &all_resources[server];
+                            if resources.is_empty() {
+                                println!("  (no resources)");
                             }
-                            Err(e) => {
-                                eprintln!("\nFailed to execute task: {}", e);
+                            for resource in resources {
+                                let mut line = format!("  {}  {}", resource.uri, resource.name);
+                                if let Some(description) = &resource.description {
+                                    line.push_str(&format!(" - {}", description));
+                                }
+                                if let Some(mime_type) = &resource.mime_type {
+                                    line.push_str(&format!(" [{}]", mime_type));
+                                }
+                                println!("{}", line);
                             }
                         }
+                        for (server, error) in mcp.unavailable_servers().await {
+                            eprintln!("MCP server '{}' is unavailable: {}", server, error);
+                        }
                     }
-                    Err(ReadlineError::Interrupted) => {
-                        println!("\nGoodbye!");
-                        break;
-                    }
-                    Err(ReadlineError::Eof) => {
-                        println!("\nGoodbye!");
-                        break;
-                    }
-                    Err(err) => {
-                        eprintln!("Error reading input: {}", err);
-                        break;
+                    McpCommand::Read { uri } => {
+                        let contents = mcp.read_resource(&uri).await?;
+                        println!("{}", contents.text());
                     }
                 }
             }
+            Commands::McpServe => {
+                system.initialize_project(&cli.working_dir).await?;
+                let transport =
+                    mcp::transport::LineTransport::new(tokio::io::stdin(), tokio::io::stdout());
+                McpServe::new(system.clone()).run(transport).await?;
+            }
+            Commands::Config { .. } => unreachable!("handled before the system starts"),
+            Commands::Interactive => {
+                Repl::new(system.clone(), model, &cli.working_dir, &config)
+                    .run(&config.ui.history_path())
+                    .await?;
+            }
+        }
+
+        Ok(code)
+    }
+    .await;
 
-            // Save history on exit
-            let _ = rl.save_history(&history_path);
+    system.shutdown().await;
+    result
+}
+
+/// Config problems get their own exit code
+fn config_error(error: anyhow::Error) -> anyhow::Error {
+    LcaError::Config(format!("{:#}", error)).into()
+}
+
+/// Prints a task's progress and result in the chosen output format
+struct Reporter {
+    system: Arc<AgentSystem>,
+    format: OutputFormat,
+    details: bool,
+    usage_before: llm::types::Usage,
+}
+
+impl Reporter {
+    fn new(system: &Arc<AgentSystem>, format: OutputFormat, details: bool) -> Self {
+        Self {
+            system: system.clone(),
+            format,
+            details,
+            usage_before: system.llm_client.usage(),
         }
     }
 
-    Ok(())
+    /// With jsonl, announce the task and stream its events from here on
+    fn start(&self, task: &str) {
+        if self.format != OutputFormat::Jsonl {
+            return;
+        }
+        println!(
+            "{}",
+            output::event_line("task_started", serde_json::json!({ "task": task }))
+        );
+        let listener: events::Listener = Arc::new(|event: &events::Event| {
+            if let Ok(line) = serde_json::to_string(event) {
+                println!("{}", line);
+            }
+        });
+        self.system
+            .tool_executor
+            .activity()
+            .set_listener(Some(listener));
+    }
+
+    /// Print the result and return the exit code it maps to
+    fn finish(&self, result: &AgentResult) -> u8 {
+        let activity = self.system.tool_executor.activity();
+        activity.set_listener(None);
+        let usage = self.system.llm_client.usage() - self.usage_before;
+        let report = TaskReport::new(result, &activity.events(), usage);
+
+        match self.format {
+            OutputFormat::Text => print_result(result, self.details),
+            OutputFormat::Json => println!("{}", report.to_json()),
+            OutputFormat::Jsonl => println!("{}", output::event_line("result", report.to_json())),
+        }
+        report.exit_code
+    }
+}
+
+/// Plan a task, let the user review it, save the approved plan and run it.
+/// Returns `None` if the user cancelled.
+async fn plan_and_execute(
+    system: &AgentSystem,
+    task: &str,
+    working_dir: &str,
+) -> Result<Option<AgentResult>> {
+    let mut plan = system.plan_task(task).await?;
+
+    if !review_plan(&mut plan) {
+        return Ok(None);
+    }
+
+    let path = plan.save(working_dir)?;
+    println!(
+        "Plan saved to {} (re-run with: lca run-plan {})",
+        path.display(),
+        path.display()
+    );
+
+    system.execute_plan(&plan).await.map(Some)
+}
+
+fn print_result(result: &AgentResult, details: bool) {
+    if result.success {
+        println!("\nSUCCESS\n{}", result.output);
+    } else {
+        eprintln!("\nFAILED\n{}", result.output);
+    }
+
+    if details {
+        print_details(result);
+    }
+}
+
+/// Print each subtask's full output when the result came from the coordinator
+fn print_details(result: &AgentResult) {
+    let Some(reports) = SubtaskReport::from_result(result) else {
+        return;
+    };
+
+    println!("\nDetails:");
+    for report in reports {
+        println!("\n{}", report.render());
+    }
 }
@@ -0,0 +1,74 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use super::server::ToolPolicy;
+
+/// One MCP tool call, whether or not it was allowed to run
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub server: String,
+    pub tool: String,
+    pub arguments: Value,
+    pub policy: ToolPolicy,
+    pub allowed: bool,
+    /// `denied`, `ok`, `tool error` or `failed: <reason>`
+    pub outcome: String,
+}
+
+impl AuditEntry {
+    pub fn new(server: &str, tool: &str, arguments: &Value, policy: ToolPolicy) -> Self {
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            server: server.to_string(),
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            policy,
+            allowed: false,
+            outcome: "denied".to_string(),
+        }
+    }
+}
+
+/// Append-only JSON Lines record of MCP tool calls
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Write the entry; a log that cannot be written is reported, not fatal
+    pub fn record(&self, entry: &AuditEntry) {
+        info!(
+            "MCP audit: {}/{} {} ({:?})",
+            entry.server, entry.tool, entry.outcome, entry.policy
+        );
+
+        let result = (|| -> std::io::Result<()> {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            let line = serde_json::to_string(entry)?;
+            writeln!(file, "{}", line)
+        })();
+
+        if let Err(e) = result {
+            warn!("Could not write MCP audit log {:?}: {}", self.path, e);
+        }
+    }
+}
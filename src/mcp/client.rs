@@ -1,107 +1,335 @@
 use anyhow::Result;
-use std::collections::HashMap;
+use futures_util::future::join_all;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{debug, info};
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
 
-use super::protocol::Tool;
-use super::server::{McpServer, McpServerConfig};
+use super::audit::{AuditEntry, AuditLog};
+use super::protocol::{
+    CallToolResult, GetPromptResult, JsonRpcError, Prompt, ReadResourceResult, Resource, Tool,
+};
+use super::sampling::{ClientRequests, SamplingModels};
+use super::server::{McpServer, McpServerConfig, ToolPolicy};
+use crate::error::LcaError;
+use crate::llm::LlmClient;
+use crate::permissions::PermissionManager;
+
+/// How often running servers are pinged
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
 
-#[allow(dead_code)]
 pub struct McpClient {
-    servers: Arc<RwLock<HashMap<String, McpServer>>>,
+    /// Servers started on first use
+    configs: Vec<McpServerConfig>,
+    /// Every configured server once started, including ones that are degraded.
+    /// Requests clone a server out rather than holding the lock.
+    servers: Arc<RwLock<HashMap<String, Arc<McpServer>>>>,
+    started: Mutex<bool>,
+    /// Pings servers and restarts the ones that died
+    supervisor: std::sync::Mutex<Option<JoinHandle<()>>>,
+    /// Asked before calling tools whose policy is `ask`
+    permission_manager: Option<Arc<PermissionManager>>,
+    audit_log: Option<AuditLog>,
+    /// Model servers may sample through `sampling/createMessage`
+    sampling: Option<(Arc<dyn LlmClient>, SamplingModels)>,
+    /// Directories reported to servers through `roots/list`
+    roots: Vec<PathBuf>,
 }
 
-#[allow(dead_code)]
 impl McpClient {
     pub fn new() -> Self {
+        Self::with_servers(Vec::new())
+    }
+
+    /// Client for the given servers; none of them are started until a tool is needed
+    pub fn with_servers(configs: Vec<McpServerConfig>) -> Self {
         Self {
+            configs,
             servers: Arc::new(RwLock::new(HashMap::new())),
+            started: Mutex::new(false),
+            supervisor: std::sync::Mutex::new(None),
+            permission_manager: None,
+            audit_log: None,
+            sampling: None,
+            roots: Vec::new(),
         }
     }
 
-    pub async fn register_server(&self, config: McpServerConfig) -> Result<()> {
-        let name = config.name.clone();
-        info!("Registering MCP server: {}", name);
+    pub fn with_permissions(mut self, permission_manager: Arc<PermissionManager>) -> Self {
+        self.permission_manager = Some(permission_manager);
+        self
+    }
 
-        let mut server = McpServer::new(config);
-        server.start().await?;
+    /// Let servers ask the local model for completions, with the user's approval
+    pub fn with_sampling(mut self, llm: Arc<dyn LlmClient>, models: SamplingModels) -> Self {
+        self.sampling = Some((llm, models));
+        self
+    }
 
-        let mut servers = self.servers.write().await;
-        servers.insert(name, server);
+    pub fn with_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.roots = roots;
+        self
+    }
 
-        Ok(())
+    fn new_server(&self, config: McpServerConfig) -> McpServer {
+        let mut requests = ClientRequests::new(&config.name)
+            .with_permissions(self.permission_manager.clone())
+            .with_roots(self.roots.clone());
+        if let Some((llm, models)) = &self.sampling {
+            requests = requests.with_sampling(llm.clone(), models.clone());
+        }
+        McpServer::new(config).with_handler(Arc::new(requests))
     }
 
-    pub async fn list_all_tools(&self) -> Result<HashMap<String, Vec<Tool>>> {
-        let servers = self.servers.read().await;
-        let mut all_tools = HashMap::new();
+    /// Record every tool call, allowed or not, in a JSON Lines file
+    pub fn with_audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_log = Some(AuditLog::new(path));
+        self
+    }
 
-        for (name, server) in servers.iter() {
-            all_tools.insert(name.clone(), server.get_tools().to_vec());
+    /// Start every configured server that is not running yet, then start
+    /// supervising them. A server that fails to start is marked degraded and
+    /// retried later rather than failing the call.
+    async fn ensure_started(&self) {
+        let mut started = self.started.lock().await;
+        if *started {
+            return;
         }
 
-        Ok(all_tools)
+        for config in &self.configs {
+            if self.servers.read().await.contains_key(&config.name) {
+                continue;
+            }
+
+            let server = self.new_server(config.clone());
+            if let Err(e) = server.start().await {
+                server.fail(format!("failed to start: {:#}", e)).await;
+            }
+            self.servers
+                .write()
+                .await
+                .insert(config.name.clone(), Arc::new(server));
+        }
+
+        report_collisions(&*self.servers.read().await);
+
+        if !self.configs.is_empty() {
+            let supervisor = tokio::spawn(supervise(self.servers.clone()));
+            *self.supervisor.lock().unwrap() = Some(supervisor);
+        }
+        *started = true;
+    }
+
+    /// Start the servers if needed and restart any that died since the last call
+    async fn check_health(&self) {
+        self.ensure_started().await;
+
+        let any_down = self
+            .servers
+            .read()
+            .await
+            .values()
+            .any(|server| !server.is_running());
+        if any_down {
+            check_servers(&self.servers, Vec::new()).await;
+        }
+    }
+
+    /// Servers that cannot be used right now, with the reason
+    pub async fn unavailable_servers(&self) -> HashMap<String, String> {
+        self.servers
+            .read()
+            .await
+            .iter()
+            .filter_map(|(name, server)| Some((name.clone(), server.problem()?)))
+            .collect()
     }
 
-    pub async fn find_tool(&self, tool_name: &str) -> Result<Option<(String, Tool)>> {
+    /// Tools of every usable server; degraded servers are left out and
+    /// reported by `unavailable_servers`
+    pub async fn list_all_tools(&self) -> Result<HashMap<String, Vec<Tool>>> {
+        self.check_health().await;
         let servers = self.servers.read().await;
+        let mut all_tools = HashMap::new();
 
-        for (server_name, server) in servers.iter() {
-            if let Some(tool) = server.get_tool(tool_name) {
-                return Ok(Some((server_name.clone(), tool.clone())));
-            }
+        for (name, server) in servers.iter().filter(|(_, s)| s.is_running()) {
+            all_tools.insert(name.clone(), server.get_tools());
         }
 
-        Ok(None)
+        Ok(all_tools)
     }
 
+    /// The server and tool behind `name`, which is `server__tool`,
+    /// `server/tool`, or a tool name only one server offers
+    pub async fn find_tool(&self, name: &str) -> Result<(String, Tool)> {
+        let all_tools = self.list_all_tools().await?;
+        let (server, tool) = resolve_tool(&all_tools, name)
+            .map_err(|e| anyhow::anyhow!("MCP tool '{}': {}", name, e))?;
+        Ok((server.to_string(), tool.clone()))
+    }
+
+    /// Call a tool once its server's tool policy, and the user when the
+    /// policy says to ask, allow it
     pub async fn call_tool(
         &self,
-        tool_name: &str,
-        arguments: HashMap<String, serde_json::Value>,
-    ) -> Result<serde_json::Value> {
-        let (server_name, _tool) = self
-            .find_tool(tool_name)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Tool '{}' not found", tool_name))?;
-
-        debug!("Calling tool '{}' on server '{}'", tool_name, server_name);
-
-        let mut servers = self.servers.write().await;
-        let server = servers
-            .get_mut(&server_name)
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<CallToolResult> {
+        let (server_name, tool) = self.find_tool(name).await?;
+        let server = self
+            .servers
+            .read()
+            .await
+            .get(&server_name)
+            .cloned()
             .ok_or_else(|| anyhow::anyhow!("Server '{}' not found", server_name))?;
+        let policy = server.tool_policy(&tool);
+
+        let mut entry = AuditEntry::new(&server_name, &tool.name, &arguments, policy);
+        if let Err(e) = self.check_permission(&server_name, &tool, &arguments, policy) {
+            self.audit(&entry);
+            return Err(e);
+        }
+        entry.allowed = true;
 
-        server.call_tool(tool_name, arguments).await
+        debug!("Calling tool '{}' on server '{}'", tool.name, server_name);
+        let result = server.call_tool(&tool.name, arguments).await;
+
+        entry.outcome = match &result {
+            Ok(result) if result.is_error => "tool error".to_string(),
+            Ok(_) => "ok".to_string(),
+            Err(e) => format!("failed: {:#}", e),
+        };
+        self.audit(&entry);
+        result
     }
 
-    pub async fn stop_all(&self) -> Result<()> {
-        let mut servers = self.servers.write().await;
+    fn check_permission(
+        &self,
+        server: &str,
+        tool: &Tool,
+        arguments: &serde_json::Value,
+        policy: ToolPolicy,
+    ) -> Result<()> {
+        let pm = match (policy, &self.permission_manager) {
+            (ToolPolicy::Deny, _) => {
+                return Err(LcaError::PermissionDenied(format!(
+                    "MCP tool '{}' on server '{}' is denied by its tool policy",
+                    tool.name, server
+                ))
+                .into())
+            }
+            (ToolPolicy::Allow, _) | (ToolPolicy::Ask, None) => return Ok(()),
+            (ToolPolicy::Ask, Some(pm)) => pm,
+        };
 
-        for (name, server) in servers.iter_mut() {
-            info!("Stopping MCP server: {}", name);
-            server.stop().await?;
+        let pretty = serde_json::to_string_pretty(arguments).unwrap_or_default();
+        if pm.request_mcp_tool_call(server, &tool.name, &pretty, tool.is_destructive()) {
+            return Ok(());
         }
-
-        servers.clear();
-        Ok(())
+        warn!("MCP tool call denied: {}/{}", server, tool.name);
+        let message = if pm.is_interactive() {
+            "MCP tool call permission denied by user"
+        } else {
+            "MCP tool calls that need approval are not allowed without --allow-all in this mode"
+        };
+        Err(LcaError::PermissionDenied(message.to_string()).into())
     }
 
-    pub async fn stop_server(&self, name: &str) -> Result<()> {
-        let mut servers = self.servers.write().await;
+    fn audit(&self, entry: &AuditEntry) {
+        if let Some(log) = &self.audit_log {
+            log.record(entry);
+        }
+    }
 
-        if let Some(server) = servers.get_mut(name) {
-            server.stop().await?;
-            servers.remove(name);
+    /// Tool names offered by more than one running server, with those servers
+    pub async fn tool_collisions(&self) -> Vec<(String, Vec<String>)> {
+        match self.list_all_tools().await {
+            Ok(all_tools) => tool_collisions(&all_tools),
+            Err(_) => Vec::new(),
         }
+    }
 
-        Ok(())
+    /// Resources listed by each running server when it started
+    pub async fn list_all_resources(&self) -> HashMap<String, Vec<Resource>> {
+        self.check_health().await;
+        let servers = self.servers.read().await;
+        servers
+            .iter()
+            .map(|(name, server)| (name.clone(), server.get_resources()))
+            .collect()
     }
 
-    pub async fn get_server_count(&self) -> usize {
+    /// Prompts listed by each running server when it started
+    pub async fn list_all_prompts(&self) -> HashMap<String, Vec<Prompt>> {
+        self.check_health().await;
         let servers = self.servers.read().await;
-        servers.len()
+        servers
+            .iter()
+            .map(|(name, server)| (name.clone(), server.get_prompts()))
+            .collect()
+    }
+
+    /// Read a resource from the server that listed it
+    pub async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult> {
+        self.check_health().await;
+        let server = self
+            .servers
+            .read()
+            .await
+            .values()
+            .find(|server| server.get_resources().iter().any(|r| r.uri == uri))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No MCP server lists the resource '{}'", uri))?;
+
+        debug!("Reading resource '{}'", uri);
+        server.read_resource(uri).await
+    }
+
+    /// Fetch a prompt by the same kind of name as a tool: `server__prompt`,
+    /// `server/prompt`, or a prompt name only one server has
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        let all_prompts = self.list_all_prompts().await;
+        let (server_name, prompt) = resolve_prompt(&all_prompts, name)
+            .map_err(|e| anyhow::anyhow!("MCP prompt '{}': {}", name, e))?;
+        let server = self
+            .servers
+            .read()
+            .await
+            .get(server_name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Server '{}' not found", server_name))?;
+
+        debug!(
+            "Getting prompt '{}' from server '{}'",
+            prompt.name, server_name
+        );
+        server.get_prompt(&prompt.name, arguments).await
+    }
+
+    /// Stop every server gracefully. A restart under way finishes first and
+    /// its server is then stopped too.
+    pub async fn stop_all(&self) {
+        if let Some(supervisor) = self.supervisor.lock().unwrap().take() {
+            supervisor.abort();
+        }
+
+        let servers: Vec<Arc<McpServer>> = self
+            .servers
+            .write()
+            .await
+            .drain()
+            .map(|(_, server)| server)
+            .collect();
+        join_all(servers.iter().map(|server| server.shutdown())).await;
     }
 }
 
@@ -113,12 +341,364 @@ impl Default for McpClient {
 
 impl Drop for McpClient {
     fn drop(&mut self) {
-        let servers = self.servers.clone();
-        tokio::spawn(async move {
-            let mut servers = servers.write().await;
-            for (_, server) in servers.iter_mut() {
-                let _ = server.stop().await;
+        // Server processes are killed when dropped; call `stop_all` first to
+        // let them exit cleanly
+        if let Some(supervisor) = self.supervisor.lock().unwrap().take() {
+            supervisor.abort();
+        }
+    }
+}
+
+/// Name a tool or prompt is offered under; `/` is accepted too, but many
+/// models only write identifier-like tool names
+pub fn qualified_name(server: &str, name: &str) -> String {
+    format!("{}__{}", server, name)
+}
+
+/// Find a tool by qualified or bare name. A bare name must be offered by
+/// exactly one server.
+pub fn resolve_tool<'a>(
+    all_tools: &'a HashMap<String, Vec<Tool>>,
+    name: &str,
+) -> Result<(&'a str, &'a Tool), String> {
+    resolve(all_tools, name, "tool", |tool| &tool.name)
+}
+
+/// Find a prompt the way `resolve_tool` finds a tool
+pub fn resolve_prompt<'a>(
+    all_prompts: &'a HashMap<String, Vec<Prompt>>,
+    name: &str,
+) -> Result<(&'a str, &'a Prompt), String> {
+    resolve(all_prompts, name, "prompt", |prompt| &prompt.name)
+}
+
+fn resolve<'a, T>(
+    offered: &'a HashMap<String, Vec<T>>,
+    name: &str,
+    kind: &str,
+    name_of: impl Fn(&T) -> &str,
+) -> Result<(&'a str, &'a T), String> {
+    for (server, items) in offered {
+        let bare = name
+            .strip_prefix(server.as_str())
+            .and_then(|rest| rest.strip_prefix("__").or_else(|| rest.strip_prefix('/')));
+        if let Some(item) = bare.and_then(|bare| items.iter().find(|i| name_of(i) == bare)) {
+            return Ok((server, item));
+        }
+    }
+
+    let mut matches: Vec<(&str, &T)> = offered
+        .iter()
+        .filter_map(|(server, items)| {
+            let item = items.iter().find(|i| name_of(i) == name)?;
+            Some((server.as_str(), item))
+        })
+        .collect();
+
+    match matches.len() {
+        0 => Err(format!("no such {}", kind)),
+        1 => Ok(matches.remove(0)),
+        _ => {
+            let mut names: Vec<String> = matches
+                .iter()
+                .map(|(server, item)| qualified_name(server, name_of(item)))
+                .collect();
+            names.sort();
+            Err(format!(
+                "offered by several servers, use one of: {}",
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// Tool names offered by more than one server, with those servers, sorted
+pub fn tool_collisions(all_tools: &HashMap<String, Vec<Tool>>) -> Vec<(String, Vec<String>)> {
+    let mut offered_by: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (server, tools) in all_tools {
+        for tool in tools {
+            offered_by
+                .entry(&tool.name)
+                .or_default()
+                .push(server.clone());
+        }
+    }
+
+    offered_by
+        .into_iter()
+        .filter(|(_, servers)| servers.len() > 1)
+        .map(|(tool, mut servers)| {
+            servers.sort();
+            (tool.to_string(), servers)
+        })
+        .collect()
+}
+
+fn report_collisions(servers: &HashMap<String, Arc<McpServer>>) {
+    let all_tools: HashMap<String, Vec<Tool>> = servers
+        .iter()
+        .map(|(name, server)| (name.clone(), server.get_tools()))
+        .collect();
+
+    for (tool, servers) in tool_collisions(&all_tools) {
+        let names: Vec<String> = servers.iter().map(|s| qualified_name(s, &tool)).collect();
+        warn!(
+            "MCP tool '{}' is offered by {}; call it as {}",
+            tool,
+            servers.join(", "),
+            names.join(" or ")
+        );
+    }
+}
+
+/// The servers in the map, so they can be used without holding its lock
+async fn snapshot(
+    servers: &RwLock<HashMap<String, Arc<McpServer>>>,
+) -> Vec<(String, Arc<McpServer>)> {
+    servers
+        .read()
+        .await
+        .iter()
+        .map(|(name, server)| (name.clone(), server.clone()))
+        .collect()
+}
+
+/// Ping running servers on a schedule and restart the ones that died or
+/// stopped responding, within each server's restart policy
+async fn supervise(servers: Arc<RwLock<HashMap<String, Arc<McpServer>>>>) {
+    let mut ticks = tokio::time::interval(PING_INTERVAL);
+    ticks.tick().await;
+
+    loop {
+        ticks.tick().await;
+
+        let running = snapshot(&servers)
+            .await
+            .into_iter()
+            .filter(|(_, server)| server.is_running());
+        let pings = join_all(running.map(|(name, server)| async move {
+            match server.ping(PING_TIMEOUT).await {
+                // An error reply still means the server is alive
+                Err(e) if e.downcast_ref::<JsonRpcError>().is_none() => {
+                    Some((name, format!("ping failed: {:#}", e)))
+                }
+                _ => None,
+            }
+        }))
+        .await;
+
+        check_servers(&servers, pings.into_iter().flatten().collect()).await;
+    }
+}
+
+/// Fail the `unresponsive` servers, then notice servers that died and restart
+/// the ones whose backoff has passed. The servers stay in the map while they
+/// restart, reported as degraded, and calls to the others are not held up.
+async fn check_servers(
+    servers: &RwLock<HashMap<String, Arc<McpServer>>>,
+    mut unresponsive: Vec<(String, String)>,
+) {
+    let checks = snapshot(servers).await.into_iter().map(|(name, server)| {
+        let failure = unresponsive
+            .iter()
+            .position(|(n, _)| *n == name)
+            .map(|i| unresponsive.swap_remove(i).1);
+        async move {
+            if let Some(reason) = failure {
+                server.fail(reason).await;
+                server.check().await;
+            } else if server.needs_check() {
+                server.check().await;
             }
+        }
+    });
+    join_all(checks.collect::<Vec<_>>()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str) -> Tool {
+        serde_json::from_value(serde_json::json!({ "name": name })).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_tool_and_collisions() {
+        let all_tools = HashMap::from([
+            (
+                "github".to_string(),
+                vec![tool("search"), tool("create_issue")],
+            ),
+            ("docs".to_string(), vec![tool("search")]),
+        ]);
+
+        assert_eq!(
+            resolve_tool(&all_tools, "create_issue").unwrap().0,
+            "github"
+        );
+        assert_eq!(resolve_tool(&all_tools, "docs__search").unwrap().0, "docs");
+        assert_eq!(
+            resolve_tool(&all_tools, "github/search").unwrap().0,
+            "github"
+        );
+        assert_eq!(
+            resolve_tool(&all_tools, "search").unwrap_err(),
+            "offered by several servers, use one of: docs__search, github__search"
+        );
+        assert_eq!(
+            resolve_tool(&all_tools, "docs__create_issue").unwrap_err(),
+            "no such tool"
+        );
+
+        assert_eq!(
+            tool_collisions(&all_tools),
+            vec![(
+                "search".to_string(),
+                vec!["docs".to_string(), "github".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_resolve_prompt_uses_tool_names() {
+        let prompt = |name: &str| -> Prompt {
+            serde_json::from_value(serde_json::json!({ "name": name })).unwrap()
+        };
+        let all_prompts = HashMap::from([
+            (
+                "github".to_string(),
+                vec![prompt("review"), prompt("triage")],
+            ),
+            ("docs".to_string(), vec![prompt("review")]),
+        ]);
+
+        assert_eq!(resolve_prompt(&all_prompts, "triage").unwrap().0, "github");
+        assert_eq!(
+            resolve_prompt(&all_prompts, "docs__review").unwrap().0,
+            "docs"
+        );
+        assert_eq!(
+            resolve_prompt(&all_prompts, "github/review").unwrap().0,
+            "github"
+        );
+        assert_eq!(
+            resolve_prompt(&all_prompts, "review").unwrap_err(),
+            "offered by several servers, use one of: docs__review, github__review"
+        );
+        assert_eq!(
+            resolve_prompt(&all_prompts, "docs:review").unwrap_err(),
+            "no such prompt"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_servers_start_lazily_and_failures_are_recorded() {
+        let client = McpClient::with_servers(vec![McpServerConfig {
+            name: "missing".to_string(),
+            command: "/nonexistent/mcp-server".to_string(),
+            ..Default::default()
+        }]);
+        assert!(client.unavailable_servers().await.is_empty());
+
+        let tools = client.list_all_tools().await.unwrap();
+        assert!(tools.is_empty());
+        assert!(client.unavailable_servers().await["missing"].starts_with("failed to start"));
+        assert!(client
+            .call_tool("anything", serde_json::json!({}))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pending_calls_do_not_hold_the_server_map() {
+        let replies = [
+            r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"sh","version":"1"}}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"slow"}]}}"#,
+        ];
+        // Never answers the tool call
+        let script = format!(
+            "read l; echo '{}'; read l; read l; echo '{}'; while read l; do :; done",
+            replies[0], replies[1]
+        );
+        let client = Arc::new(McpClient::with_servers(vec![McpServerConfig {
+            name: "sh".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script],
+            timeout: Some(30),
+            ..Default::default()
+        }]));
+        client.list_all_tools().await.unwrap();
+
+        let call = tokio::spawn({
+            let client = client.clone();
+            async move { client.call_tool("slow", serde_json::json!({})).await }
         });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), client.servers.write())
+                .await
+                .is_ok()
+        );
+
+        client.stop_all().await;
+        assert!(call.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_are_gated_and_audited() {
+        let replies = [
+            r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"sh","version":"1"}}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"lookup","annotations":{"readOnlyHint":true}},{"name":"delete"}]}}"#,
+            r#"{"jsonrpc":"2.0","id":ID,"result":{"content":[{"type":"text","text":"found"}]}}"#,
+        ];
+        // initialize, then the initialized notification, tools/list and
+        // tools/call, whose id is copied into the reply
+        let script = format!(
+            "read l; echo '{}'; read l; read l; echo '{}'; read l; id=$(echo \"$l\" | sed 's/.*\"id\":\\([0-9]*\\).*/\\1/'); echo '{}' | sed \"s/ID/$id/\"; while read l; do :; done",
+            replies[0], replies[1], replies[2]
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let audit_path = dir.path().join("audit.jsonl");
+        let permissions =
+            PermissionManager::new(crate::permissions::PermissionMode::Ask).with_interactive(false);
+
+        let client = McpClient::with_servers(vec![McpServerConfig {
+            name: "sh".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script],
+            timeout: Some(5),
+            trust_annotations: true,
+            ..Default::default()
+        }])
+        .with_permissions(Arc::new(permissions))
+        .with_audit_log(&audit_path);
+
+        let err = client
+            .call_tool("delete", serde_json::json!({"id": 7}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--allow-all"), "{}", err);
+
+        let result = client
+            .call_tool("sh__lookup", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(result.text(), "found");
+        client.stop_all().await;
+
+        let audit: Vec<serde_json::Value> = std::fs::read_to_string(&audit_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0]["tool"], "delete");
+        assert_eq!(audit[0]["arguments"]["id"], 7);
+        assert_eq!(audit[0]["policy"], "ask");
+        assert_eq!(audit[0]["outcome"], "denied");
+        assert_eq!(audit[1]["policy"], "allow");
+        assert_eq!(audit[1]["allowed"], true);
+        assert_eq!(audit[1]["outcome"], "ok");
     }
 }
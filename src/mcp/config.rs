@@ -0,0 +1,340 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use super::sampling::SamplingModels;
+use super::server::{McpServerConfig, ToolPolicy};
+
+/// Server entry as written in `mcp.json`, keyed by server name under
+/// `mcpServers`, or under `[mcp.servers]` in `config.toml`. Exactly one of
+/// `command` (stdio) and `url` (HTTP) is set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+    #[serde(
+        default,
+        rename = "allowTools",
+        alias = "allow_tools",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    allow_tools: Vec<String>,
+    #[serde(
+        default,
+        rename = "denyTools",
+        alias = "deny_tools",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    deny_tools: Vec<String>,
+    #[serde(
+        default,
+        rename = "toolPolicy",
+        alias = "tool_policy",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    tool_policy: HashMap<String, ToolPolicy>,
+    #[serde(
+        default,
+        rename = "trustAnnotations",
+        alias = "trust_annotations",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    trust_annotations: bool,
+}
+
+impl ServerEntry {
+    /// Take back what only the user's own files may grant: `allow` policies
+    /// become `ask` and the server's annotations are not trusted. Returns
+    /// whether anything changed.
+    fn drop_grants(&mut self) -> bool {
+        let mut changed = std::mem::take(&mut self.trust_annotations);
+        for policy in self.tool_policy.values_mut() {
+            if *policy == ToolPolicy::Allow {
+                *policy = ToolPolicy::Ask;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigFile {
+    #[serde(rename = "mcpServers", default)]
+    servers: BTreeMap<String, ServerEntry>,
+    #[serde(rename = "samplingModels", default)]
+    sampling_models: Option<SamplingModels>,
+}
+
+/// The `[mcp]` table of `config.toml`, applied before the `mcp.json` files
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct McpSettings {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub servers: BTreeMap<String, ServerEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling_models: Option<SamplingModels>,
+}
+
+impl McpSettings {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// MCP servers merged from the configuration and the user and project
+/// `mcp.json` files
+#[derive(Debug, Default)]
+pub struct McpConfig {
+    pub servers: Vec<McpServerConfig>,
+    /// Local models offered to servers that sample; the last file that sets
+    /// them wins
+    pub sampling_models: SamplingModels,
+    /// Config files or entries that could not be used, reported instead of aborting
+    pub errors: Vec<String>,
+}
+
+impl McpConfig {
+    /// Apply `settings`, then `~/.lca/mcp.json`, then
+    /// `<project_dir>/.lca/mcp.json`. A later entry replaces an earlier one
+    /// with the same name. The project file comes with the repository, so it
+    /// can't let tools run without asking.
+    pub fn load(project_dir: impl AsRef<Path>, settings: &McpSettings) -> Self {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        let user_path = PathBuf::from(home).join(".lca").join("mcp.json");
+        let project_path = project_dir.as_ref().join(".lca").join("mcp.json");
+
+        Self::load_files(settings, &[user_path], &[project_path], |name| {
+            std::env::var(name).ok()
+        })
+    }
+
+    fn load_files(
+        settings: &McpSettings,
+        user_paths: &[PathBuf],
+        project_paths: &[PathBuf],
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let mut config = Self {
+            sampling_models: settings.sampling_models.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        let mut servers: BTreeMap<String, McpServerConfig> = BTreeMap::new();
+        config.add_servers(
+            &mut servers,
+            settings.servers.clone(),
+            "config.toml",
+            &lookup,
+        );
+
+        let paths = user_paths
+            .iter()
+            .map(|path| (path, false))
+            .chain(project_paths.iter().map(|path| (path, true)));
+        for (path, is_project) in paths.filter(|(p, _)| p.exists()) {
+            match Self::read_file(path) {
+                Ok(mut file) => {
+                    debug!(
+                        "Loaded {} MCP server(s) from {:?}",
+                        file.servers.len(),
+                        path
+                    );
+                    if is_project {
+                        drop_project_grants(&mut file.servers, &format!("{:?}", path));
+                    }
+                    if let Some(models) = file.sampling_models {
+                        config.sampling_models = models;
+                    }
+                    config.add_servers(&mut servers, file.servers, &format!("{:?}", path), &lookup);
+                }
+                Err(e) => {
+                    warn!("Skipping MCP config: {:#}", e);
+                    config.errors.push(format!("{:#}", e));
+                }
+            }
+        }
+
+        config.servers = servers.into_values().collect();
+        config
+    }
+
+    fn add_servers(
+        &mut self,
+        servers: &mut BTreeMap<String, McpServerConfig>,
+        entries: BTreeMap<String, ServerEntry>,
+        source: &str,
+        lookup: &impl Fn(&str) -> Option<String>,
+    ) {
+        for (name, entry) in entries {
+            if entry.command.is_some() == entry.url.is_some() {
+                let error = format!(
+                    "MCP server '{}' in {} needs either a command or a url",
+                    name, source
+                );
+                warn!("Skipping {}", error);
+                self.errors.push(error);
+                continue;
+            }
+
+            let expand_all = |map: &HashMap<String, String>| {
+                map.iter()
+                    .map(|(k, v)| (k.clone(), expand_env(v, lookup)))
+                    .collect()
+            };
+            let config = McpServerConfig {
+                name: name.clone(),
+                command: expand_env(entry.command.as_deref().unwrap_or_default(), lookup),
+                args: entry.args.iter().map(|a| expand_env(a, lookup)).collect(),
+                env: expand_all(&entry.env),
+                url: entry.url.as_deref().map(|u| expand_env(u, lookup)),
+                headers: expand_all(&entry.headers),
+                timeout: entry.timeout,
+                allow_tools: entry.allow_tools,
+                deny_tools: entry.deny_tools,
+                tool_policy: entry.tool_policy,
+                trust_annotations: entry.trust_annotations,
+            };
+            servers.insert(name, config);
+        }
+    }
+
+    fn read_file(path: &Path) -> Result<ConfigFile> {
+        let data =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&data).with_context(|| format!("Invalid MCP config {:?}", path))
+    }
+}
+
+/// Drop the grants of servers from a project's own files, which anyone
+/// who can commit to the repository can write
+fn drop_project_grants(servers: &mut BTreeMap<String, ServerEntry>, source: &str) {
+    for (name, entry) in servers {
+        if entry.drop_grants() {
+            warn!(
+                "Ignoring \"allow\" tool policies and trustAnnotations for MCP server '{}' \
+                 in {}; only your own config can let its tools run without asking",
+                name, source
+            );
+        }
+    }
+}
+
+/// Replace `${NAME}` with the variable's value. Unset variables expand to an
+/// empty string.
+fn expand_env(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + len];
+
+        out.push_str(&rest[..start]);
+        match lookup(name) {
+            Some(v) => out.push_str(&v),
+            None => warn!("Environment variable {} is not set", name),
+        }
+        rest = &rest[start + len + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        (name == "TOKEN").then(|| "secret".to_string())
+    }
+
+    #[test]
+    fn test_expand_env() {
+        assert_eq!(expand_env("Bearer ${TOKEN}", lookup), "Bearer secret");
+        assert_eq!(expand_env("${MISSING}/x ${TOKEN", lookup), "/x ${TOKEN");
+    }
+
+    #[test]
+    fn test_project_config_overrides_user_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user.json");
+        let project = dir.path().join("project.json");
+        let broken = dir.path().join("broken.json");
+
+        std::fs::write(
+            &user,
+            r#"{"mcpServers": {
+                "github": {"command": "npx", "args": ["server-github"], "env": {"GITHUB_TOKEN": "${TOKEN}"},
+                           "toolPolicy": {"get_*": "allow"}, "trustAnnotations": true},
+                "fs": {"command": "npx", "args": ["server-fs", "/old"]},
+                "docs": {"url": "https://mcp.example.com/${TOKEN}", "headers": {"Authorization": "Bearer ${TOKEN}"}},
+                "both": {"command": "npx", "url": "https://mcp.example.com"}
+            }}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &project,
+            r#"{"mcpServers": {"fs": {"command": "npx", "args": ["server-fs", "${TOKEN}"], "denyTools": ["write_*"],
+                                      "toolPolicy": {"*": "allow", "rm": "deny"}, "trustAnnotations": true}},
+                "samplingModels": {"fast": "llama3.2:1b"}}"#,
+        )
+        .unwrap();
+        std::fs::write(&broken, "{not json").unwrap();
+
+        let settings: McpSettings = toml::from_str(
+            r#"
+            [servers.local]
+            command = "lca-local-server"
+            deny_tools = ["write_*"]
+            "#,
+        )
+        .unwrap();
+        let config = McpConfig::load_files(
+            &settings,
+            &[user, broken],
+            &[project, dir.path().join("missing.json")],
+            lookup,
+        );
+
+        assert_eq!(config.servers.len(), 4);
+        assert_eq!(config.servers[3].name, "local");
+        assert!(!config.servers[3].allows_tool("write_file"));
+        assert_eq!(config.servers[0].name, "docs");
+        assert_eq!(
+            config.servers[0].url.as_deref(),
+            Some("https://mcp.example.com/secret")
+        );
+        assert_eq!(config.servers[0].headers["Authorization"], "Bearer secret");
+        assert_eq!(config.servers[1].name, "fs");
+        assert_eq!(config.servers[1].args, vec!["server-fs", "secret"]);
+        assert!(!config.servers[1].allows_tool("write_file"));
+        // The project file can't let tools run without asking
+        assert_eq!(config.servers[1].tool_policy["*"], ToolPolicy::Ask);
+        assert_eq!(config.servers[1].tool_policy["rm"], ToolPolicy::Deny);
+        assert!(!config.servers[1].trust_annotations);
+        assert_eq!(config.servers[2].env["GITHUB_TOKEN"], "secret");
+        assert_eq!(config.servers[2].tool_policy["get_*"], ToolPolicy::Allow);
+        assert!(config.servers[2].trust_annotations);
+        assert_eq!(config.sampling_models.default, "default");
+        assert_eq!(config.sampling_models.fast.as_deref(), Some("llama3.2:1b"));
+        assert_eq!(config.errors.len(), 2);
+        assert!(config.errors[0].contains("'both'"));
+        assert!(config.errors[1].contains("broken.json"));
+    }
+}
@@ -0,0 +1,525 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::protocol::JsonRpcMessage;
+use super::transport::{MessageSink, MessageSource, Transport};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// MCP "streamable HTTP": every message is POSTed to one endpoint and the
+/// server answers with JSON or an SSE stream. Messages the server sends on
+/// its own arrive on an optional GET stream.
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+}
+
+impl HttpTransport {
+    /// `headers` are sent with every request, e.g. `Authorization`
+    pub fn new(url: impl Into<String>, headers: &HashMap<String, String>) -> Result<Self> {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name '{}'", name))?;
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for header '{}'", name))?;
+            header_map.insert(name, value);
+        }
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            headers: header_map,
+        })
+    }
+}
+
+impl Transport for HttpTransport {
+    fn split(self: Box<Self>) -> (Box<dyn MessageSink>, Box<dyn MessageSource>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sink = HttpSink {
+            client: self.client,
+            url: self.url,
+            headers: self.headers,
+            state: Arc::new(Mutex::new(HttpState::default())),
+            incoming: Some(tx),
+            listener: None,
+        };
+        (Box::new(sink), Box::new(HttpSource { incoming: rx }))
+    }
+}
+
+/// Messages from the server, or the error that broke the connection, which
+/// ends the session
+type Incoming = mpsc::UnboundedSender<Result<JsonRpcMessage>>;
+
+/// What the server told us about the connection
+#[derive(Default)]
+struct HttpState {
+    session_id: Option<String>,
+    protocol_version: Option<String>,
+}
+
+struct HttpSink {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    state: Arc<Mutex<HttpState>>,
+    /// Where messages from the server go; `None` once closed
+    incoming: Option<Incoming>,
+    /// The GET stream for messages the server sends on its own
+    listener: Option<JoinHandle<()>>,
+}
+
+impl HttpSink {
+    /// Configured headers plus the session and protocol version headers
+    fn headers(&self) -> HeaderMap {
+        let mut headers = self.headers.clone();
+        let state = self.state.lock().unwrap();
+        for (name, value) in [
+            (SESSION_HEADER, &state.session_id),
+            (PROTOCOL_VERSION_HEADER, &state.protocol_version),
+        ] {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+        headers
+    }
+
+    fn start_listening(&mut self, incoming: Incoming) {
+        let request = self
+            .client
+            .get(&self.url)
+            .headers(self.headers())
+            .header(ACCEPT, "text/event-stream");
+        let state = self.state.clone();
+
+        self.listener = Some(tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    if let Err(e) = forward(response, &incoming, state).await {
+                        let _ = incoming.send(Err(e.context("MCP event stream failed")));
+                    }
+                }
+                // 405 means the server does not offer a stream, which is allowed
+                Ok(response) => debug!("MCP server has no event stream ({})", response.status()),
+                Err(e) => {
+                    let error = anyhow::Error::new(e).context("Could not open MCP event stream");
+                    let _ = incoming.send(Err(error));
+                }
+            }
+        }));
+    }
+}
+
+#[async_trait]
+impl MessageSink for HttpSink {
+    async fn send(&mut self, message: &JsonRpcMessage) -> Result<()> {
+        let incoming = self
+            .incoming
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("MCP connection is closed"))?;
+        debug!("MCP send: {}", serde_json::to_string(message)?);
+
+        let had_session = self.state.lock().unwrap().session_id.is_some();
+        let sent = self
+            .client
+            .post(&self.url)
+            .headers(self.headers())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(message)
+            .send()
+            .await;
+        let response = match sent {
+            Ok(response) => response,
+            Err(e) => {
+                let error = format!("Failed to reach MCP server at {}: {}", self.url, e);
+                // The session can't be used any more; closing it fails the
+                // other waiting requests and lets the server be restarted
+                let _ = incoming.send(Err(anyhow::anyhow!("{}", error)));
+                anyhow::bail!(error);
+            }
+        };
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND && had_session {
+            anyhow::bail!("MCP session expired (HTTP 404)");
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("MCP server returned HTTP {}: {}", status, body.trim());
+        }
+
+        if let Some(id) = response.headers().get(SESSION_HEADER) {
+            self.state.lock().unwrap().session_id = id.to_str().ok().map(str::to_string);
+        }
+
+        // Read the reply in the background so a long SSE stream does not
+        // hold up other requests
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = forward(response, &incoming, state).await {
+                let _ = incoming.send(Err(e.context("Lost MCP response stream")));
+            }
+        });
+
+        let initialized = matches!(
+            message,
+            JsonRpcMessage::Notification(n) if n.method == "notifications/initialized"
+        );
+        if initialized && self.listener.is_none() {
+            if let Some(incoming) = self.incoming.clone() {
+                self.start_listening(incoming);
+            }
+        }
+        Ok(())
+    }
+
+    /// End the session; the server may answer 405 if it does not allow that
+    async fn close(&mut self) -> Result<()> {
+        self.incoming = None;
+        if let Some(listener) = self.listener.take() {
+            listener.abort();
+        }
+
+        if self.state.lock().unwrap().session_id.is_some() {
+            let response = self
+                .client
+                .delete(&self.url)
+                .headers(self.headers())
+                .send()
+                .await?;
+            debug!("MCP session closed ({})", response.status());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HttpSink {
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener.take() {
+            listener.abort();
+        }
+    }
+}
+
+struct HttpSource {
+    incoming: mpsc::UnboundedReceiver<Result<JsonRpcMessage>>,
+}
+
+#[async_trait]
+impl MessageSource for HttpSource {
+    async fn receive(&mut self) -> Result<Option<JsonRpcMessage>> {
+        self.incoming.recv().await.transpose()
+    }
+}
+
+/// Pass the messages in an HTTP response on, whether it is plain JSON
+/// (one message or a batch) or an SSE stream
+async fn forward(
+    response: reqwest::Response,
+    incoming: &Incoming,
+    state: Arc<Mutex<HttpState>>,
+) -> Result<()> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let deliver = |data: &str| {
+        let messages = match serde_json::from_str::<Vec<JsonRpcMessage>>(data) {
+            Ok(batch) => batch,
+            Err(_) => match serde_json::from_str(data) {
+                Ok(message) => vec![message],
+                Err(e) => {
+                    warn!(
+                        "Ignoring non JSON-RPC data from MCP server ({}): {}",
+                        e, data
+                    );
+                    return;
+                }
+            },
+        };
+        for message in messages {
+            debug!("MCP receive: {}", data);
+            remember_protocol_version(&message, &state);
+            let _ = incoming.send(Ok(message));
+        }
+    };
+
+    if content_type.starts_with("text/event-stream") {
+        let mut events = EventParser::default();
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            for data in events.push(&chunk?) {
+                deliver(&data);
+            }
+        }
+    } else {
+        let body = response.text().await?;
+        if !body.trim().is_empty() {
+            deliver(&body);
+        }
+    }
+    Ok(())
+}
+
+/// Later requests must carry the version agreed in the `initialize` reply
+fn remember_protocol_version(message: &JsonRpcMessage, state: &Mutex<HttpState>) {
+    if let JsonRpcMessage::Response(response) = message {
+        let result = response.result.as_ref();
+        if let Some(version) = result
+            .filter(|r| r.get("serverInfo").is_some())
+            .and_then(|r| r.get("protocolVersion"))
+            .and_then(|v| v.as_str())
+        {
+            state.lock().unwrap().protocol_version = Some(version.to_string());
+        }
+    }
+}
+
+/// Splits a server-sent events stream into the `data` of each event
+#[derive(Default)]
+struct EventParser {
+    line: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl EventParser {
+    /// Feed bytes in; returns the data of every event they complete
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+
+        for &byte in bytes {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+
+            let line = String::from_utf8_lossy(&self.line).into_owned();
+            self.line.clear();
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(value) = line.strip_prefix("data") {
+                // `data:value` and `data: value` are the same; other fields
+                // (event, id, retry) and `:` comments are not needed
+                let value = value.strip_prefix(':').unwrap_or(value);
+                self.data
+                    .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::server::{McpServer, McpServerConfig};
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_event_parser_handles_split_chunks() {
+        let mut parser = EventParser::default();
+        assert!(parser
+            .push(b": keep-alive\n\nevent: message\nda")
+            .is_empty());
+        assert_eq!(
+            parser.push(b"ta: {\"a\":1}\r\n\r\nid: 2\n"),
+            vec![r#"{"a":1}"#]
+        );
+        assert_eq!(parser.push(b"data:one\ndata: two\n\n"), vec!["one\ntwo"]);
+    }
+
+    struct HttpRequest {
+        method: String,
+        headers: HashMap<String, String>,
+        body: Value,
+    }
+
+    async fn read_request(stream: &mut BufReader<TcpStream>) -> HttpRequest {
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        let method = line.split(' ').next().unwrap_or_default().to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await.unwrap();
+            let Some((name, value)) = line.trim_end().split_once(": ") else {
+                break;
+            };
+            headers.insert(name.to_lowercase(), value.to_string());
+        }
+
+        let length = headers
+            .get("content-length")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.unwrap();
+
+        HttpRequest {
+            method,
+            headers,
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        }
+    }
+
+    /// An MCP server with one `echo` tool that answers `initialize` with
+    /// JSON and everything else with SSE. Returns its URL and the methods of
+    /// the HTTP requests it saw.
+    async fn stand_in_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut stream = BufReader::new(stream);
+                let request = read_request(&mut stream).await;
+                log.lock().unwrap().push(request.method.clone());
+
+                let authorized = request.headers.get("authorization").map(String::as_str)
+                    == Some("Bearer secret");
+                let in_session =
+                    request.headers.get(SESSION_HEADER).map(String::as_str) == Some("s-1");
+                let id = request.body["id"].clone();
+
+                let response = match (request.method.as_str(), request.body["method"].as_str()) {
+                    _ if !authorized => "HTTP/1.1 401 Unauthorized\r\n\r\n".to_string(),
+                    ("POST", Some("initialize")) => {
+                        let body = json!({"jsonrpc": "2.0", "id": id, "result": {
+                            "protocolVersion": "2025-06-18",
+                            "capabilities": {"tools": {}},
+                            "serverInfo": {"name": "http", "version": "1"}
+                        }})
+                        .to_string();
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                             Mcp-Session-Id: s-1\r\nContent-Length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    _ if !in_session => "HTTP/1.1 400 Bad Request\r\n\r\n".to_string(),
+                    ("POST", _) if id.is_null() => "HTTP/1.1 202 Accepted\r\n\r\n".to_string(),
+                    ("POST", Some("tools/call"))
+                        if request.body["params"]["arguments"]["text"] == "break" =>
+                    {
+                        // The stream ends long before the promised length
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                         Content-Length: 1000\r\n\r\n: keep-alive\n\n"
+                            .to_string()
+                    }
+                    ("POST", Some(method)) => {
+                        let result = match method {
+                            "tools/list" => json!({"tools": [{"name": "echo"}]}),
+                            _ => {
+                                let text = request.body["params"]["arguments"]["text"].clone();
+                                json!({"content": [{"type": "text", "text": text}]})
+                            }
+                        };
+                        let progress = json!({"jsonrpc": "2.0", "method": "notifications/message",
+                            "params": {"level": "info", "data": "working"}});
+                        let reply = json!({"jsonrpc": "2.0", "id": id, "result": result});
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n\
+                             data: {}\n\nid: 1\ndata: {}\n\n",
+                            progress, reply
+                        )
+                    }
+                    ("DELETE", _) => "HTTP/1.1 200 OK\r\n\r\n".to_string(),
+                    _ => "HTTP/1.1 405 Method Not Allowed\r\n\r\n".to_string(),
+                };
+
+                let mut stream = stream.into_inner();
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+
+        (url, seen)
+    }
+
+    fn http_server(url: String, token: &str) -> McpServer {
+        McpServer::new(McpServerConfig {
+            name: "http".to_string(),
+            url: Some(url),
+            headers: HashMap::from([("Authorization".to_string(), format!("Bearer {}", token))]),
+            timeout: Some(5),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_http_server_round_trip() {
+        let (url, seen) = stand_in_server().await;
+        let server = http_server(url, "secret");
+
+        server.start().await.unwrap();
+        assert!(server.is_running());
+        assert_eq!(server.get_tools().len(), 1);
+
+        let result = server
+            .call_tool("echo", json!({"text": "hi"}))
+            .await
+            .unwrap();
+        assert_eq!(result.text(), "hi");
+
+        server.shutdown().await;
+        assert!(!server.is_running());
+
+        let seen = seen.lock().unwrap();
+        assert!(seen.contains(&"GET".to_string()));
+        assert_eq!(seen.last().map(String::as_str), Some("DELETE"));
+    }
+
+    #[tokio::test]
+    async fn test_broken_reply_stream_fails_the_request_and_closes_the_session() {
+        let (url, _) = stand_in_server().await;
+        let server = http_server(url, "secret");
+        server.start().await.unwrap();
+
+        let started = std::time::Instant::now();
+        let err = server
+            .call_tool("echo", json!({"text": "break"}))
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(
+            format!("{:#}", err).contains("Lost MCP response stream"),
+            "{:#}",
+            err
+        );
+        assert!(!server.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_http_errors_are_reported() {
+        let (url, _) = stand_in_server().await;
+        let err = http_server(url, "wrong").start().await.unwrap_err();
+        assert!(format!("{:#}", err).contains("401"), "{:#}", err);
+    }
+}
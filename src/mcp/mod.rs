@@ -1,5 +1,13 @@
+pub mod audit;
 pub mod client;
+pub mod config;
+pub mod http;
 pub mod protocol;
+pub mod sampling;
+pub mod schema;
 pub mod server;
+pub mod session;
+pub mod transport;
 
 pub use client::McpClient;
+pub use config::McpConfig;
@@ -1,147 +1,627 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    #[serde(default)]
-    pub parameters: HashMap<String, ParameterSchema>,
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Protocol versions this client can speak, newest first. The newest is
+/// offered in `initialize`; the server may answer with any of them.
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Standard JSON-RPC error codes
+pub mod error_codes {
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ParameterSchema {
-    #[serde(rename = "type")]
-    pub param_type: String,
-    pub description: Option<String>,
-    pub required: Option<bool>,
-    pub default: Option<serde_json::Value>,
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(i64),
+    String(String),
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "method")]
-pub enum McpRequest {
-    #[serde(rename = "tools/list")]
-    ListTools {},
-
-    #[serde(rename = "tools/call")]
-    CallTool {
-        name: String,
-        arguments: HashMap<String, serde_json::Value>,
-    },
-
-    #[serde(rename = "prompts/list")]
-    ListPrompts {},
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
 
-    #[serde(rename = "prompts/get")]
-    GetPrompt {
-        name: String,
-        arguments: Option<HashMap<String, String>>,
-    },
+impl JsonRpcRequest {
+    pub fn new(id: RequestId, method: impl Into<String>, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            method: method.into(),
+            params,
+        }
+    }
+}
 
-    #[serde(rename = "resources/list")]
-    ListResources {},
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
 
-    #[serde(rename = "resources/read")]
-    ReadResource { uri: String },
+impl JsonRpcNotification {
+    pub fn new(method: impl Into<String>, params: Option<Value>) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: method.into(),
+            params,
+        }
+    }
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct McpResponse {
-    pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub result: Option<serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: RequestId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
 }
 
-#[allow(dead_code)]
-impl McpResponse {
-    pub fn success(result: serde_json::Value) -> Self {
+impl JsonRpcResponse {
+    pub fn success(id: RequestId, result: Value) -> Self {
         Self {
-            success: true,
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
             result: Some(result),
             error: None,
         }
     }
 
-    pub fn error(message: impl Into<String>) -> Self {
+    pub fn error(id: RequestId, error: JsonRpcError) -> Self {
         Self {
-            success: false,
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
             result: None,
-            error: Some(message.into()),
+            error: Some(error),
+        }
+    }
+
+    /// The result, or the error object as an `Err`
+    pub fn into_result(self) -> Result<Value, JsonRpcError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.result.unwrap_or(Value::Null)),
         }
     }
 }
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, thiserror::Error)]
+#[error("{message} (code {code})")]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+/// Any message that can arrive on an MCP connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcMessage {
+    Request(JsonRpcRequest),
+    Response(JsonRpcResponse),
+    Notification(JsonRpcNotification),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolCall {
+pub struct Implementation {
     pub name: String,
-    pub arguments: HashMap<String, serde_json::Value>,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientCapabilities {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roots: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerCapabilities {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompts: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging: Option<Value>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolResult {
-    pub success: bool,
-    pub output: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<HashMap<String, String>>,
+#[serde(rename_all = "camelCase")]
+pub struct InitializeParams {
+    pub protocol_version: String,
+    pub capabilities: ClientCapabilities,
+    pub client_info: Implementation,
 }
 
-#[allow(dead_code)]
-impl ToolResult {
-    pub fn success(output: impl Into<String>) -> Self {
-        Self {
-            success: true,
-            output: output.into(),
-            metadata: None,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    pub protocol_version: String,
+    #[serde(default)]
+    pub capabilities: ServerCapabilities,
+    pub server_info: Implementation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// JSON Schema for the tool's arguments
+    #[serde(rename = "inputSchema", default = "empty_object_schema")]
+    pub input_schema: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+/// Hints a server gives about a tool's behaviour. They come from the
+/// server, so they only ever relax prompting for servers the user trusts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
+}
+
+impl Tool {
+    /// Declared read-only by its server
+    pub fn is_read_only(&self) -> bool {
+        self.annotations
+            .as_ref()
+            .and_then(|a| a.read_only_hint)
+            .unwrap_or(false)
+    }
+
+    /// May delete or overwrite data. The MCP default for tools that are not
+    /// read-only is to assume they might.
+    pub fn is_destructive(&self) -> bool {
+        !self.is_read_only()
+            && self
+                .annotations
+                .as_ref()
+                .and_then(|a| a.destructive_hint)
+                .unwrap_or(true)
+    }
+}
+
+fn empty_object_schema() -> Value {
+    serde_json::json!({"type": "object"})
+}
+
+/// One item of a tool result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Content {
+    Text {
+        text: String,
+    },
+    Image {
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Audio {
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Resource {
+        resource: Value,
+    },
+    ResourceLink {
+        uri: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    #[serde(default)]
+    pub content: Vec<Content>,
+    #[serde(default)]
+    pub is_error: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+}
+
+impl Content {
+    /// Text items verbatim, other items as placeholders
+    pub fn text(&self) -> String {
+        match self {
+            Content::Text { text } => text.clone(),
+            Content::Image { mime_type } => format!("[image: {}]", mime_type),
+            Content::Audio { mime_type } => format!("[audio: {}]", mime_type),
+            Content::Resource { resource } => match resource.get("text") {
+                Some(Value::String(text)) => text.clone(),
+                _ => format!(
+                    "[resource: {}]",
+                    resource.get("uri").and_then(Value::as_str).unwrap_or("?")
+                ),
+            },
+            Content::ResourceLink { uri } => format!("[resource link: {}]", uri),
+            Content::Unknown => "[unsupported content]".to_string(),
         }
     }
+}
 
-    pub fn error(output: impl Into<String>) -> Self {
-        Self {
-            success: false,
-            output: output.into(),
-            metadata: None,
+impl CallToolResult {
+    /// Text rendering of the content, one line per item
+    pub fn text(&self) -> String {
+        let parts: Vec<String> = self.content.iter().map(Content::text).collect();
+
+        match (&parts[..], &self.structured_content) {
+            ([], Some(structured)) => structured.to_string(),
+            _ => parts.join("\n"),
         }
     }
+}
 
-    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
-        self.metadata
-            .get_or_insert_with(HashMap::new)
-            .insert(key.into(), value.into());
-        self
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// One item of a `resources/read` result: either text or base64 `blob`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadResourceResult {
+    #[serde(default)]
+    pub contents: Vec<ResourceContents>,
+}
+
+impl ReadResourceResult {
+    /// Text contents verbatim; binary contents as a placeholder
+    pub fn text(&self) -> String {
+        self.contents
+            .iter()
+            .map(|item| match &item.text {
+                Some(text) => text.clone(),
+                None => format!(
+                    "[binary resource: {} ({})]",
+                    item.uri,
+                    item.mime_type.as_deref().unwrap_or("unknown type")
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Prompt {
     pub name: String,
-    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     #[serde(default)]
     pub arguments: Vec<PromptArgument>,
 }
 
-#[allow(dead_code)]
+impl Prompt {
+    /// Usage line for the prompt as a slash-command, e.g. `/review <file> [focus]`
+    pub fn usage(&self, command: &str) -> String {
+        let mut usage = format!("/{}", command);
+        for argument in &self.arguments {
+            if argument.required {
+                usage.push_str(&format!(" <{}>", argument.name));
+            } else {
+                usage.push_str(&format!(" [{}]", argument.name));
+            }
+        }
+        usage
+    }
+
+    /// Parse `key=value` pairs typed after the slash-command. A prompt with a
+    /// single argument also takes the whole input as its value.
+    pub fn parse_arguments(&self, input: &str) -> Result<HashMap<String, String>, String> {
+        let input = input.trim();
+        let mut arguments = HashMap::new();
+
+        if self.arguments.len() == 1 && !input.is_empty() && !input.contains('=') {
+            arguments.insert(self.arguments[0].name.clone(), input.to_string());
+        } else {
+            for pair in input.split_whitespace() {
+                let (key, value) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, got '{}'", pair))?;
+                if !self.arguments.iter().any(|a| a.name == key) {
+                    return Err(format!("unknown argument '{}'", key));
+                }
+                arguments.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        let missing: Vec<&str> = self
+            .arguments
+            .iter()
+            .filter(|a| a.required && !arguments.contains_key(&a.name))
+            .map(|a| a.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("missing argument(s): {}", missing.join(", ")));
+        }
+
+        Ok(arguments)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptArgument {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(default)]
     pub required: bool,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Resource {
-    pub uri: String,
-    pub name: String,
+pub struct PromptMessage {
+    pub role: String,
+    pub content: Content,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetPromptResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
-    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<PromptMessage>,
+}
+
+impl GetPromptResult {
+    /// The messages' text, in order, separated by blank lines
+    pub fn text(&self) -> String {
+        self.messages
+            .iter()
+            .map(|message| message.content.text())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Parameters of `sampling/createMessage`, a server asking for a completion
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageParams {
+    pub messages: Vec<SamplingMessage>,
+    #[serde(default)]
+    pub model_preferences: Option<ModelPreferences>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SamplingMessage {
+    pub role: String,
+    pub content: SamplingContent,
+}
+
+/// A single content item, or several in newer protocol versions
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SamplingContent {
+    One(Content),
+    Many(Vec<Content>),
+}
+
+impl SamplingContent {
+    pub fn text(&self) -> String {
+        match self {
+            SamplingContent::One(content) => content.text(),
+            SamplingContent::Many(items) => items
+                .iter()
+                .map(Content::text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// What kind of model the server would like; priorities are 0 to 1
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPreferences {
+    #[serde(default)]
+    pub hints: Vec<ModelHint>,
+    #[serde(default)]
+    pub cost_priority: Option<f64>,
+    #[serde(default)]
+    pub speed_priority: Option<f64>,
+    #[serde(default)]
+    pub intelligence_priority: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelHint {
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateMessageResult {
+    pub role: String,
+    pub content: Content,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+/// A directory the client lets servers work in, answered to `roots/list`
+#[derive(Debug, Clone, Serialize)]
+pub struct Root {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_envelope() {
+        let request = JsonRpcRequest::new(RequestId::Number(7), "tools/list", None);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"jsonrpc": "2.0", "id": 7, "method": "tools/list"})
+        );
+    }
+
+    #[test]
+    fn test_message_kinds() {
+        let parse = |v: Value| serde_json::from_value::<JsonRpcMessage>(v).unwrap();
+
+        assert!(matches!(
+            parse(json!({"jsonrpc": "2.0", "id": "a", "method": "ping"})),
+            JsonRpcMessage::Request(_)
+        ));
+        assert!(matches!(
+            parse(json!({"jsonrpc": "2.0", "method": "notifications/message", "params": {}})),
+            JsonRpcMessage::Notification(_)
+        ));
+
+        let JsonRpcMessage::Response(response) = parse(json!({
+            "jsonrpc": "2.0", "id": 1, "error": {"code": -32601, "message": "Method not found"}
+        })) else {
+            panic!("expected a response");
+        };
+        let error = response.into_result().unwrap_err();
+        assert_eq!(error.code, error_codes::METHOD_NOT_FOUND);
+        assert_eq!(error.to_string(), "Method not found (code -32601)");
+    }
+
+    #[test]
+    fn test_call_tool_result_text() {
+        let result: CallToolResult = serde_json::from_value(json!({
+            "content": [
+                {"type": "text", "text": "hello"},
+                {"type": "image", "data": "...", "mimeType": "image/png"},
+                {"type": "something_new"}
+            ],
+            "isError": true
+        }))
+        .unwrap();
+
+        assert!(result.is_error);
+        assert_eq!(
+            result.text(),
+            "hello\n[image: image/png]\n[unsupported content]"
+        );
+    }
+
+    #[test]
+    fn test_resource_and_prompt_text() {
+        let read: ReadResourceResult = serde_json::from_value(json!({
+            "contents": [
+                {"uri": "file:///a.txt", "mimeType": "text/plain", "text": "alpha"},
+                {"uri": "file:///b.png", "mimeType": "image/png", "blob": "AAAA"}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            read.text(),
+            "alpha\n[binary resource: file:///b.png (image/png)]"
+        );
+
+        let prompt: GetPromptResult = serde_json::from_value(json!({
+            "messages": [
+                {"role": "user", "content": {"type": "text", "text": "Review this"}},
+                {"role": "user", "content": {"type": "resource", "resource": {"uri": "x", "text": "fn main() {}"}}}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(prompt.text(), "Review this\n\nfn main() {}");
+    }
+
+    #[test]
+    fn test_prompt_arguments() {
+        let prompt: Prompt = serde_json::from_value(json!({
+            "name": "review",
+            "arguments": [{"name": "file", "required": true}, {"name": "focus"}]
+        }))
+        .unwrap();
+
+        assert_eq!(prompt.usage("git:review"), "/git:review <file> [focus]");
+        assert_eq!(
+            prompt.parse_arguments("file=main.rs focus=errors").unwrap()["focus"],
+            "errors"
+        );
+        assert_eq!(
+            prompt.parse_arguments("focus=errors").unwrap_err(),
+            "missing argument(s): file"
+        );
+        assert_eq!(
+            prompt.parse_arguments("path=main.rs").unwrap_err(),
+            "unknown argument 'path'"
+        );
+
+        let single: Prompt = serde_json::from_value(json!({
+            "name": "explain",
+            "arguments": [{"name": "topic", "required": true}]
+        }))
+        .unwrap();
+        assert_eq!(
+            single.parse_arguments("error handling").unwrap()["topic"],
+            "error handling"
+        );
+    }
 }
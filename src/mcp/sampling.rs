@@ -0,0 +1,332 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+use url::Url;
+
+use super::protocol::{
+    error_codes, ClientCapabilities, Content, CreateMessageParams, CreateMessageResult,
+    JsonRpcError, ModelPreferences, Root,
+};
+use super::session::RequestHandler;
+use crate::llm::types::ChatRequest;
+use crate::llm::{LlmClient, Message};
+use crate::permissions::PermissionManager;
+
+/// Error code for a sampling request the user refused
+const USER_REJECTED: i64 = -1;
+
+/// The local models a server's sampling request can be given
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingModels {
+    #[serde(default = "default_model")]
+    pub default: String,
+    /// For requests that favour speed or cost
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fast: Option<String>,
+    /// For requests that favour intelligence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capable: Option<String>,
+}
+
+fn default_model() -> String {
+    "default".to_string()
+}
+
+impl Default for SamplingModels {
+    fn default() -> Self {
+        Self {
+            default: default_model(),
+            fast: None,
+            capable: None,
+        }
+    }
+}
+
+impl SamplingModels {
+    /// A model named by one of the server's hints wins, in hint order;
+    /// otherwise the priorities pick the fast, capable or default model
+    pub fn choose(&self, preferences: Option<&ModelPreferences>) -> &str {
+        let Some(preferences) = preferences else {
+            return &self.default;
+        };
+
+        let ours = [
+            Some(&self.default),
+            self.fast.as_ref(),
+            self.capable.as_ref(),
+        ];
+        for hint in preferences.hints.iter().filter_map(|h| h.name.as_deref()) {
+            if let Some(model) = ours.iter().flatten().find(|m| m.contains(hint)) {
+                return model;
+            }
+        }
+
+        let speed = preferences
+            .speed_priority
+            .unwrap_or(0.0)
+            .max(preferences.cost_priority.unwrap_or(0.0));
+        let intelligence = preferences.intelligence_priority.unwrap_or(0.0);
+        match (&self.fast, &self.capable) {
+            (Some(fast), _) if speed > intelligence => fast,
+            (_, Some(capable)) if intelligence > speed => capable,
+            _ => &self.default,
+        }
+    }
+}
+
+/// Answers `sampling/createMessage` with the local model, after asking the
+/// user, and `roots/list` with the project directories
+pub struct ClientRequests {
+    server: String,
+    llm: Option<Arc<dyn LlmClient>>,
+    models: SamplingModels,
+    permission_manager: Option<Arc<PermissionManager>>,
+    roots: Vec<PathBuf>,
+}
+
+impl ClientRequests {
+    pub fn new(server: impl Into<String>) -> Self {
+        Self {
+            server: server.into(),
+            llm: None,
+            models: SamplingModels::default(),
+            permission_manager: None,
+            roots: Vec::new(),
+        }
+    }
+
+    pub fn with_sampling(mut self, llm: Arc<dyn LlmClient>, models: SamplingModels) -> Self {
+        self.llm = Some(llm);
+        self.models = models;
+        self
+    }
+
+    pub fn with_permissions(mut self, permission_manager: Option<Arc<PermissionManager>>) -> Self {
+        self.permission_manager = permission_manager;
+        self
+    }
+
+    pub fn with_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.roots = roots;
+        self
+    }
+
+    fn list_roots(&self) -> Value {
+        let roots: Vec<Root> = self
+            .roots
+            .iter()
+            .filter_map(|path| {
+                // Percent-encoded, so spaces, `#` and `%` in the path survive
+                let absolute = std::path::absolute(path).ok()?;
+                let uri = Url::from_directory_path(&absolute).ok()?;
+                Some(Root {
+                    uri: uri.to_string(),
+                    name: path.file_name().map(|n| n.to_string_lossy().into_owned()),
+                })
+            })
+            .collect();
+        json!({ "roots": roots })
+    }
+
+    async fn create_message(&self, params: Option<Value>) -> Result<Value, JsonRpcError> {
+        let Some(llm) = &self.llm else {
+            return Err(JsonRpcError::new(
+                error_codes::METHOD_NOT_FOUND,
+                "Sampling is not enabled",
+            ));
+        };
+        let params: CreateMessageParams = serde_json::from_value(params.unwrap_or(Value::Null))
+            .map_err(|e| JsonRpcError::new(error_codes::INVALID_PARAMS, e.to_string()))?;
+
+        let model = self.models.choose(params.model_preferences.as_ref());
+        let mut messages = Vec::new();
+        if let Some(system_prompt) = &params.system_prompt {
+            messages.push(Message::system(system_prompt.clone()));
+        }
+        for message in &params.messages {
+            let text = message.content.text();
+            messages.push(match message.role.as_str() {
+                "assistant" => Message::assistant(text),
+                _ => Message::user(text),
+            });
+        }
+
+        let transcript = messages
+            .iter()
+            .map(|m| format!("{:?}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let approved = self
+            .permission_manager
+            .as_ref()
+            .is_none_or(|pm| pm.request_mcp_sampling(&self.server, model, &transcript));
+        if !approved {
+            warn!("Refused sampling request from MCP server {}", self.server);
+            return Err(JsonRpcError::new(
+                USER_REJECTED,
+                "User rejected sampling request",
+            ));
+        }
+
+        info!(
+            "MCP server {} is sampling model {} ({} messages)",
+            self.server,
+            model,
+            params.messages.len()
+        );
+        let mut request = ChatRequest::new(model, messages);
+        if let Some(max_tokens) = params.max_tokens {
+            request = request.with_max_tokens(max_tokens);
+        }
+        if let Some(temperature) = params.temperature {
+            request = request.with_temperature(temperature);
+        }
+        let text = llm
+            .chat(request)
+            .await
+            .map_err(|e| JsonRpcError::new(error_codes::INTERNAL_ERROR, format!("{:#}", e)))?;
+
+        let result = CreateMessageResult {
+            role: "assistant".to_string(),
+            content: Content::Text { text },
+            model: model.to_string(),
+            stop_reason: Some("endTurn".to_string()),
+        };
+        Ok(serde_json::to_value(result).unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl RequestHandler for ClientRequests {
+    fn capabilities(&self) -> ClientCapabilities {
+        ClientCapabilities {
+            roots: (!self.roots.is_empty()).then(|| json!({ "listChanged": false })),
+            sampling: self.llm.is_some().then(|| json!({})),
+        }
+    }
+
+    async fn handle(&self, method: &str, params: Option<Value>) -> Result<Value, JsonRpcError> {
+        match method {
+            "roots/list" => Ok(self.list_roots()),
+            "sampling/createMessage" => self.create_message(params).await,
+            _ => Err(JsonRpcError::new(
+                error_codes::METHOD_NOT_FOUND,
+                format!("Method not found: {}", method),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::PermissionMode;
+
+    /// Replies with the model name and the last message
+    struct EchoLlm;
+
+    #[async_trait]
+    impl LlmClient for EchoLlm {
+        async fn chat(&self, request: ChatRequest) -> anyhow::Result<String> {
+            let last = request.messages.last().map(|m| m.content.clone());
+            Ok(format!(
+                "{} says {}",
+                request.model,
+                last.unwrap_or_default()
+            ))
+        }
+
+        async fn chat_with_history(
+            &self,
+            messages: Vec<Message>,
+            model: &str,
+        ) -> anyhow::Result<String> {
+            self.chat(ChatRequest::new(model, messages)).await
+        }
+    }
+
+    fn preferences(value: Value) -> ModelPreferences {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_model_preferences_map_to_our_models() {
+        let models = SamplingModels {
+            default: "qwen2.5-coder:7b".to_string(),
+            fast: Some("llama3.2:1b".to_string()),
+            capable: Some("qwen2.5-coder:32b".to_string()),
+        };
+
+        assert_eq!(models.choose(None), "qwen2.5-coder:7b");
+        assert_eq!(
+            models.choose(Some(&preferences(
+                json!({"hints": [{"name": "claude"}, {"name": "llama"}]})
+            ))),
+            "llama3.2:1b"
+        );
+        assert_eq!(
+            models.choose(Some(&preferences(
+                json!({"intelligencePriority": 0.9, "speedPriority": 0.2})
+            ))),
+            "qwen2.5-coder:32b"
+        );
+        assert_eq!(
+            models.choose(Some(&preferences(json!({"costPriority": 0.8})))),
+            "llama3.2:1b"
+        );
+        assert_eq!(
+            SamplingModels::default().choose(Some(&preferences(json!({"costPriority": 0.8})))),
+            "default"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sampling_and_roots() {
+        let models = SamplingModels {
+            fast: Some("small".to_string()),
+            ..Default::default()
+        };
+        let handler = ClientRequests::new("docs")
+            .with_sampling(Arc::new(EchoLlm), models)
+            .with_roots(vec![
+                PathBuf::from("/work/project"),
+                PathBuf::from("/work/my app #2"),
+            ]);
+
+        let capabilities = handler.capabilities();
+        assert!(capabilities.sampling.is_some() && capabilities.roots.is_some());
+
+        let roots = handler.handle("roots/list", None).await.unwrap();
+        assert_eq!(
+            roots,
+            json!({"roots": [
+                {"uri": "file:///work/project/", "name": "project"},
+                {"uri": "file:///work/my%20app%20%232/", "name": "my app #2"}
+            ]})
+        );
+
+        let params = json!({
+            "messages": [{"role": "user", "content": {"type": "text", "text": "hi"}}],
+            "modelPreferences": {"speedPriority": 1.0},
+            "maxTokens": 50
+        });
+        let result = handler
+            .handle("sampling/createMessage", Some(params.clone()))
+            .await
+            .unwrap();
+        assert_eq!(result["content"]["text"], "small says hi");
+        assert_eq!(result["model"], "small");
+        assert_eq!(result["role"], "assistant");
+
+        let permissions = PermissionManager::new(PermissionMode::Ask).with_interactive(false);
+        let refused = handler
+            .with_permissions(Some(Arc::new(permissions)))
+            .handle("sampling/createMessage", Some(params))
+            .await
+            .unwrap_err();
+        assert_eq!(refused.code, USER_REJECTED);
+    }
+}
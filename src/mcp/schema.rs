@@ -0,0 +1,360 @@
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Check `value` against the subset of JSON Schema that MCP tools use in
+/// `inputSchema`. Returns one message per problem, each
+/// prefixed with the path to the offending value (`$` is the root).
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, "$", &mut errors);
+    errors
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` or a missing schema accepts anything
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{}: no value is allowed here", path));
+        }
+        return;
+    };
+
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| has_type(value, t)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                path,
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!(
+                "{}: must be one of {}, got {}",
+                path,
+                options.join(", "),
+                value
+            ));
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: must be {}", path, expected));
+        }
+    }
+
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(variants) = schema.get(keyword).and_then(Value::as_array) {
+            let matching = variants
+                .iter()
+                .filter(|variant| validate(variant, value).is_empty())
+                .count();
+            let ok = match keyword {
+                "oneOf" => matching == 1,
+                _ => matching > 0,
+            };
+            if !ok {
+                errors.push(format!(
+                    "{}: does not match {} of the allowed forms",
+                    path,
+                    if keyword == "oneOf" {
+                        "exactly one"
+                    } else {
+                        "any"
+                    }
+                ));
+            }
+        }
+    }
+
+    if let Some(variants) = schema.get("allOf").and_then(Value::as_array) {
+        for variant in variants {
+            check(variant, value, path, errors);
+        }
+    }
+
+    match value {
+        Value::Object(object) => check_object(schema, object, path, errors),
+        Value::Array(items) => check_array(schema, items, path, errors),
+        Value::String(s) => check_string(schema, s, path, errors),
+        Value::Number(n) => check_number(schema, n.as_f64().unwrap_or_default(), path, errors),
+        _ => {}
+    }
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    let empty = Map::new();
+    let properties = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+
+    for required in schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        if !object.contains_key(required) {
+            errors.push(format!(
+                "{}: missing required property '{}'",
+                path, required
+            ));
+        }
+    }
+
+    for (key, value) in object {
+        let child = format!("{}.{}", path, key);
+        match (properties.get(key), schema.get("additionalProperties")) {
+            (Some(property), _) => check(property, value, &child, errors),
+            (None, Some(Value::Bool(false))) => {
+                let known: Vec<&str> = properties.keys().map(String::as_str).collect();
+                errors.push(format!(
+                    "{}: unknown property (expected one of: {})",
+                    child,
+                    known.join(", ")
+                ));
+            }
+            (None, Some(additional)) => check(additional, value, &child, errors),
+            (None, None) => {}
+        }
+    }
+}
+
+fn check_array(schema: &Map<String, Value>, items: &[Value], path: &str, errors: &mut Vec<String>) {
+    if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+        if (items.len() as u64) < min {
+            errors.push(format!("{}: needs at least {} item(s)", path, min));
+        }
+    }
+    if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+        if items.len() as u64 > max {
+            errors.push(format!("{}: allows at most {} item(s)", path, max));
+        }
+    }
+    if let Some(item_schema) = schema.get("items") {
+        for (idx, item) in items.iter().enumerate() {
+            check(item_schema, item, &format!("{}[{}]", path, idx), errors);
+        }
+    }
+}
+
+fn check_string(schema: &Map<String, Value>, s: &str, path: &str, errors: &mut Vec<String>) {
+    let len = s.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+        if len < min {
+            errors.push(format!("{}: must be at least {} character(s)", path, min));
+        }
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+        if len > max {
+            errors.push(format!("{}: must be at most {} character(s)", path, max));
+        }
+    }
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        if let Ok(re) = Regex::new(pattern) {
+            if !re.is_match(s) {
+                errors.push(format!("{}: must match the pattern {}", path, pattern));
+            }
+        }
+    }
+}
+
+fn check_number(schema: &Map<String, Value>, n: f64, path: &str, errors: &mut Vec<String>) {
+    if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+        if n < min {
+            errors.push(format!("{}: must be >= {}", path, min));
+        }
+    }
+    if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+        if n > max {
+            errors.push(format!("{}: must be <= {}", path, max));
+        }
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Describe an object schema's properties one per line, nested properties
+/// indented, for use in a prompt
+pub fn render(schema: &Value) -> String {
+    let mut lines = Vec::new();
+    render_properties(schema, 0, &mut lines);
+    if lines.is_empty() {
+        "(no arguments)".to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+fn render_properties(schema: &Value, depth: usize, lines: &mut Vec<String>) {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return;
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    for (name, property) in properties {
+        let mut line = format!(
+            "{}- {} ({}{})",
+            "  ".repeat(depth),
+            name,
+            describe_type(property),
+            if required.contains(&name.as_str()) {
+                ", required"
+            } else {
+                ""
+            }
+        );
+        if let Some(description) = property.get("description").and_then(Value::as_str) {
+            line.push_str(&format!(": {}", description));
+        }
+        if let Some(options) = property.get("enum").and_then(Value::as_array) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            line.push_str(&format!(" [one of: {}]", options.join(", ")));
+        }
+        if let Some(default) = property.get("default") {
+            line.push_str(&format!(" [default: {}]", default));
+        }
+        lines.push(line);
+
+        render_properties(property, depth + 1, lines);
+        if let Some(items) = property.get("items") {
+            render_properties(items, depth + 1, lines);
+        }
+    }
+}
+
+fn describe_type(schema: &Value) -> String {
+    match schema.get("type") {
+        Some(Value::String(t)) if t == "array" => match schema.get("items") {
+            Some(items) => format!("array of {}", describe_type(items)),
+            None => "array".to_string(),
+        },
+        Some(Value::String(t)) => t.clone(),
+        Some(Value::Array(ts)) => ts
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" | "),
+        _ => "any".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "File to read", "minLength": 1},
+                "mode": {"type": "string", "enum": ["text", "binary"], "default": "text"},
+                "limit": {"type": "integer", "minimum": 1},
+                "filters": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {"glob": {"type": "string"}},
+                        "required": ["glob"],
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_arguments() {
+        let args =
+            json!({"path": "a.txt", "mode": "text", "limit": 3, "filters": [{"glob": "*.rs"}]});
+        assert!(validate(&schema(), &args).is_empty());
+        assert!(validate(&json!({}), &json!({"anything": 1})).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let args = json!({
+            "mode": "json",
+            "limit": 1.5,
+            "filters": [{"glob": 3}, {"pattern": "x"}],
+            "extra": true
+        });
+
+        assert_eq!(
+            validate(&schema(), &args),
+            vec![
+                "$: missing required property 'path'",
+                "$.extra: unknown property (expected one of: filters, limit, mode, path)",
+                "$.filters[0].glob: expected string, got number",
+                "$.filters[1]: missing required property 'glob'",
+                "$.filters[1].pattern: unknown property (expected one of: glob)",
+                "$.limit: expected integer, got number",
+                "$.mode: must be one of \"text\", \"binary\", got \"json\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(&schema()),
+            "- filters (array of object)\n  \
+             - glob (string, required)\n\
+             - limit (integer)\n\
+             - mode (string) [one of: \"text\", \"binary\"] [default: \"text\"]\n\
+             - path (string, required): File to read"
+        );
+        assert_eq!(render(&json!({"type": "object"})), "(no arguments)");
+    }
+}
@@ -1,154 +1,696 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::{Child, Command};
-use tracing::{debug, info};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
 
-use super::protocol::{McpRequest, McpResponse, Tool};
+use super::http::HttpTransport;
+use super::protocol::{
+    CallToolResult, GetPromptResult, Prompt, ReadResourceResult, Resource, Tool,
+};
+use super::session::{McpSession, RequestHandler, DEFAULT_REQUEST_TIMEOUT};
+use super::transport::LineTransport;
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A server is either a child process spoken to over stdio (`command`,
+/// `args`, `env`) or an HTTP endpoint (`url`, `headers`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct McpServerConfig {
     pub name: String,
+    #[serde(default)]
     pub command: String,
+    #[serde(default)]
     pub args: Vec<String>,
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Streamable HTTP endpoint, used instead of `command` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Sent with every HTTP request, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Seconds to wait for each request; defaults to 60
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Tools to offer; all of them when empty. `*` matches any characters.
+    #[serde(default)]
+    pub allow_tools: Vec<String>,
+    /// Tools never to offer, even when allowed
+    #[serde(default)]
+    pub deny_tools: Vec<String>,
+    /// Whether calling a tool needs approval, by tool name pattern
+    #[serde(default)]
+    pub tool_policy: HashMap<String, ToolPolicy>,
+    /// Call tools the server declares read-only without asking. The server
+    /// makes that claim itself, so it is only believed when set.
+    #[serde(default)]
+    pub trust_annotations: bool,
+}
+
+/// Whether a tool may be called without asking the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolPolicy {
+    Allow,
+    Ask,
+    Deny,
+}
+
+impl McpServerConfig {
+    pub fn request_timeout(&self) -> Duration {
+        self.timeout
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    /// Whether the allow and deny lists let the server offer this tool
+    pub fn allows_tool(&self, name: &str) -> bool {
+        (self.allow_tools.is_empty() || self.allow_tools.iter().any(|p| glob_match(p, name)))
+            && !self.deny_tools.iter().any(|p| glob_match(p, name))
+    }
+
+    /// The configured policy for a tool; an exact name beats a pattern and
+    /// a longer pattern beats a shorter one. Without one, tools need approval
+    /// unless the server is trusted to declare them read-only.
+    pub fn policy_for(&self, tool: &Tool) -> ToolPolicy {
+        let configured = self
+            .tool_policy
+            .iter()
+            .filter(|(pattern, _)| glob_match(pattern, &tool.name))
+            .max_by_key(|(pattern, _)| (!pattern.contains('*'), pattern.len()))
+            .map(|(_, policy)| *policy);
+
+        configured.unwrap_or(if self.trust_annotations && tool.is_read_only() {
+            ToolPolicy::Allow
+        } else {
+            ToolPolicy::Ask
+        })
+    }
+}
+
+/// Match `name` against a pattern where `*` stands for any characters
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Lines of stderr kept to explain a server that fails to start
+const STDERR_TAIL_LINES: usize = 20;
+
+/// How long a server gets to exit after its stdin is closed before it is killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// How a server that died or stopped responding is restarted
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Restarts allowed over the server's lifetime
+    pub max_restarts: u32,
+    /// Wait before the first restart; doubles after each failed attempt
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RestartPolicy {
+    fn backoff(&self, restarts: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(restarts))
+            .min(self.max_backoff)
+    }
 }
 
-#[allow(dead_code)]
 pub struct McpServer {
     config: McpServerConfig,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    restart_policy: RestartPolicy,
+    /// Answers requests the server makes, such as sampling
+    handler: Option<Arc<dyn RequestHandler>>,
+    /// Held while the server is started, stopped or restarted
+    lifecycle: tokio::sync::Mutex<Lifecycle>,
+    /// What callers use, swapped as the server starts and stops so calls
+    /// never wait for a restart
+    state: RwLock<State>,
+}
+
+struct Lifecycle {
     process: Option<Child>,
+    restarts: u32,
+    next_restart: Instant,
+    /// Set by `shutdown`; the server is not started again
+    shut_down: bool,
+}
+
+#[derive(Default)]
+struct State {
+    session: Option<Arc<McpSession>>,
     tools: Vec<Tool>,
+    resources: Vec<Resource>,
+    prompts: Vec<Prompt>,
+    /// Why the server is not usable, while it is not
+    degraded: Option<String>,
 }
 
-#[allow(dead_code)]
 impl McpServer {
     pub fn new(config: McpServerConfig) -> Self {
         Self {
             config,
-            process: None,
-            tools: Vec::new(),
+            stderr_tail: Arc::new(Mutex::new(VecDeque::new())),
+            restart_policy: RestartPolicy::default(),
+            handler: None,
+            lifecycle: tokio::sync::Mutex::new(Lifecycle {
+                process: None,
+                restarts: 0,
+                next_restart: Instant::now(),
+                shut_down: false,
+            }),
+            state: RwLock::new(State::default()),
         }
     }
 
-    pub async fn start(&mut self) -> Result<()> {
+    pub fn with_handler(mut self, handler: Arc<dyn RequestHandler>) -> Self {
+        self.handler = Some(handler);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        let mut lifecycle = self.lifecycle.lock().await;
+        self.start_locked(&mut lifecycle).await
+    }
+
+    async fn start_locked(&self, lifecycle: &mut Lifecycle) -> Result<()> {
+        if lifecycle.shut_down {
+            anyhow::bail!("MCP server '{}' has been shut down", self.config.name);
+        }
         info!("Starting MCP server: {}", self.config.name);
 
+        let (session, mut stderr_task) = match &self.config.url {
+            Some(url) => {
+                let transport = HttpTransport::new(url.clone(), &self.config.headers)?;
+                (McpSession::new(transport), None)
+            }
+            None => self.spawn(lifecycle)?,
+        };
+        let mut session = session.with_timeout(self.config.request_timeout());
+        if let Some(handler) = &self.handler {
+            session = session.with_handler(handler.clone());
+        }
+        let handshake = async {
+            session.initialize().await?;
+            session.list_tools().await
+        };
+
+        let tools = match handshake.await {
+            Ok(tools) => {
+                let (offered, hidden): (Vec<Tool>, Vec<Tool>) = tools
+                    .into_iter()
+                    .partition(|t| self.config.allows_tool(&t.name));
+                if !hidden.is_empty() {
+                    debug!(
+                        "MCP server {} tools hidden by allow/deny lists: {}",
+                        self.config.name,
+                        hidden
+                            .iter()
+                            .map(|t| t.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                offered
+            }
+            Err(e) => {
+                // Give the stderr reader a moment to catch the server's last words
+                if let Some(task) = stderr_task.as_mut() {
+                    let _ = tokio::time::timeout(Duration::from_millis(200), task).await;
+                }
+                let tail = self.stderr_tail();
+                self.state.write().unwrap().session = Some(Arc::new(session));
+                self.stop_locked(lifecycle).await;
+                if tail.is_empty() {
+                    return Err(e);
+                }
+                return Err(e.context(format!("server stderr:\n{}", tail.join("\n"))));
+            }
+        };
+        // Resources and prompts are optional extras; a server that fails to
+        // list them still provides its tools
+        let resources = session.list_resources().await.unwrap_or_else(|e| {
+            warn!(
+                "MCP server {} failed to list resources: {}",
+                self.config.name, e
+            );
+            Vec::new()
+        });
+        let prompts = session.list_prompts().await.unwrap_or_else(|e| {
+            warn!(
+                "MCP server {} failed to list prompts: {}",
+                self.config.name, e
+            );
+            Vec::new()
+        });
+
+        info!(
+            "MCP server {} started with {} tools, {} resources and {} prompts",
+            self.config.name,
+            tools.len(),
+            resources.len(),
+            prompts.len()
+        );
+        *self.state.write().unwrap() = State {
+            session: Some(Arc::new(session)),
+            tools,
+            resources,
+            prompts,
+            degraded: None,
+        };
+
+        Ok(())
+    }
+
+    /// Launch the server process and connect to its stdio
+    fn spawn(&self, lifecycle: &mut Lifecycle) -> Result<(McpSession, Option<JoinHandle<()>>)> {
         let mut cmd = Command::new(&self.config.command);
         cmd.args(&self.config.args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         for (key, value) in &self.config.env {
             cmd.env(key, value);
         }
 
-        let child = cmd.spawn()?;
-        self.process = Some(child);
+        let mut child = cmd.spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("MCP server stdin unavailable"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("MCP server stdout unavailable"))?;
+        let stderr_task = child.stderr.take().map(|stderr| {
+            tokio::spawn(drain_stderr(
+                self.config.name.clone(),
+                stderr,
+                self.stderr_tail.clone(),
+            ))
+        });
+        lifecycle.process = Some(child);
 
-        self.discover_tools().await?;
+        Ok((
+            McpSession::new(LineTransport::new(stdout, stdin)),
+            stderr_task,
+        ))
+    }
 
-        info!(
-            "MCP server {} started with {} tools",
-            self.config.name,
-            self.tools.len()
-        );
+    /// The open session; requests go through a clone of it so a restart or
+    /// stop never waits for them
+    fn session(&self) -> Result<Arc<McpSession>> {
+        self.state
+            .read()
+            .unwrap()
+            .session
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("MCP server '{}' not running", self.config.name))
+    }
 
-        Ok(())
+    pub async fn call_tool(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<CallToolResult> {
+        self.session()?.call_tool(name, arguments).await
     }
 
-    async fn discover_tools(&mut self) -> Result<()> {
-        let request = McpRequest::ListTools {};
-        let response = self.send_request(&request).await?;
+    pub async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult> {
+        self.session()?.read_resource(uri).await
+    }
 
-        if response.success {
-            if let Some(result) = response.result {
-                if let Ok(tools) = serde_json::from_value::<Vec<Tool>>(result) {
-                    self.tools = tools;
-                }
-            }
-        }
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        self.session()?.get_prompt(name, arguments).await
+    }
 
-        Ok(())
+    pub fn get_tools(&self) -> Vec<Tool> {
+        self.state.read().unwrap().tools.clone()
     }
 
-    pub async fn send_request(&mut self, request: &McpRequest) -> Result<McpResponse> {
-        if let Some(process) = &mut self.process {
-            let request_json = serde_json::to_string(request)?;
-            debug!("Sending MCP request: {}", request_json);
+    pub fn tool_policy(&self, tool: &Tool) -> ToolPolicy {
+        self.config.policy_for(tool)
+    }
 
-            if let Some(stdin) = &mut process.stdin {
-                stdin.write_all(request_json.as_bytes()).await?;
-                stdin.write_all(b"\n").await?;
-                stdin.flush().await?;
-            }
+    /// The last lines the server wrote to stderr
+    pub fn stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.lock().unwrap().iter().cloned().collect()
+    }
 
-            if let Some(stdout) = &mut process.stdout {
-                let mut reader = BufReader::new(stdout);
-                let mut response_line = String::new();
-                reader.read_line(&mut response_line).await?;
+    pub fn get_resources(&self) -> Vec<Resource> {
+        self.state.read().unwrap().resources.clone()
+    }
 
-                let response: McpResponse = serde_json::from_str(&response_line)?;
-                debug!("Received MCP response: {:?}", response);
+    pub fn get_prompts(&self) -> Vec<Prompt> {
+        self.state.read().unwrap().prompts.clone()
+    }
 
-                return Ok(response);
-            }
-        }
+    /// Whether the server is up and its connection open
+    pub fn is_running(&self) -> bool {
+        let state = self.state.read().unwrap();
+        state.degraded.is_none() && state.session.as_ref().is_some_and(|s| !s.is_closed())
+    }
 
-        Ok(McpResponse::error("MCP server not running"))
+    /// Why the server cannot be used right now, if it cannot
+    pub fn problem(&self) -> Option<String> {
+        self.state.read().unwrap().degraded.clone()
     }
 
-    pub async fn call_tool(
-        &mut self,
-        name: &str,
-        arguments: HashMap<String, serde_json::Value>,
-    ) -> Result<serde_json::Value> {
-        let request = McpRequest::CallTool {
-            name: name.to_string(),
-            arguments,
+    /// Ask the server whether it is still responsive
+    pub async fn ping(&self, timeout: Duration) -> Result<()> {
+        self.session()?
+            .request_with_timeout("ping", None, timeout)
+            .await?;
+        Ok(())
+    }
+
+    /// Record that the server cannot be used and stop what is left of it,
+    /// so the next `check` restarts it once the backoff allows
+    pub async fn fail(&self, reason: impl Into<String>) {
+        let mut lifecycle = self.lifecycle.lock().await;
+        self.fail_locked(&mut lifecycle, reason.into()).await;
+    }
+
+    async fn fail_locked(&self, lifecycle: &mut Lifecycle, reason: String) {
+        warn!("MCP server {} is degraded: {}", self.config.name, reason);
+        self.state.write().unwrap().degraded = Some(reason);
+        lifecycle.next_restart = Instant::now() + self.restart_policy.backoff(lifecycle.restarts);
+        self.stop_locked(lifecycle).await;
+    }
+
+    /// Whether `check` has work to do: the server died since it was last
+    /// checked, or it is degraded and may be restarted now. A server that is
+    /// being started or stopped is left to that.
+    pub fn needs_check(&self) -> bool {
+        let Ok(mut lifecycle) = self.lifecycle.try_lock() else {
+            return false;
         };
+        match self.problem() {
+            None => self.exit_reason(&mut lifecycle).is_some(),
+            Some(_) => {
+                !lifecycle.shut_down
+                    && lifecycle.restarts < self.restart_policy.max_restarts
+                    && Instant::now() >= lifecycle.next_restart
+            }
+        }
+    }
 
-        let response = self.send_request(&request).await?;
+    /// Notice a server that exited or dropped its connection and restart it
+    /// within the restart policy. Returns whether the server is usable.
+    pub async fn check(&self) -> bool {
+        let mut lifecycle = self.lifecycle.lock().await;
+        if lifecycle.shut_down {
+            return false;
+        }
+        if self.problem().is_none() {
+            match self.exit_reason(&mut lifecycle) {
+                Some(reason) => self.fail_locked(&mut lifecycle, reason).await,
+                None => return true,
+            }
+        }
 
-        if response.success {
-            response
-                .result
-                .ok_or_else(|| anyhow::anyhow!("No result from tool call"))
-        } else {
-            Err(anyhow::anyhow!("Tool call failed: {:?}", response.error))
+        if lifecycle.restarts >= self.restart_policy.max_restarts
+            || Instant::now() < lifecycle.next_restart
+        {
+            return false;
+        }
+
+        lifecycle.restarts += 1;
+        info!(
+            "Restarting MCP server {} (attempt {} of {})",
+            self.config.name, lifecycle.restarts, self.restart_policy.max_restarts
+        );
+        self.state.write().unwrap().degraded = Some("restarting".to_string());
+        match self.start_locked(&mut lifecycle).await {
+            Ok(()) => true,
+            Err(e) => {
+                self.fail_locked(&mut lifecycle, format!("restart failed: {:#}", e))
+                    .await;
+                false
+            }
         }
     }
 
-    pub fn get_tools(&self) -> &[Tool] {
-        &self.tools
+    fn exit_reason(&self, lifecycle: &mut Lifecycle) -> Option<String> {
+        let state = self.state.read().unwrap();
+        if self.config.url.is_some() {
+            // There is no process to watch, only the connection
+            return match &state.session {
+                None => Some("not started".to_string()),
+                Some(session) if session.is_closed() => Some("connection closed".to_string()),
+                Some(_) => None,
+            };
+        }
+
+        match lifecycle.process.as_mut().map(|p| p.try_wait()) {
+            None => Some("not started".to_string()),
+            Some(Ok(Some(status))) => Some(format!("process exited ({})", status)),
+            Some(Err(e)) => Some(format!("process state unknown: {}", e)),
+            Some(Ok(None)) if state.session.as_ref().is_none_or(|s| s.is_closed()) => {
+                Some("connection closed".to_string())
+            }
+            Some(Ok(None)) => None,
+        }
     }
 
-    pub fn get_tool(&self, name: &str) -> Option<&Tool> {
-        self.tools.iter().find(|t| t.name == name)
+    /// Stop the server for good; a restart already under way finishes
+    /// first and later ones do nothing
+    pub async fn shutdown(&self) {
+        let mut lifecycle = self.lifecycle.lock().await;
+        lifecycle.shut_down = true;
+        self.stop_locked(&mut lifecycle).await;
     }
 
-    pub async fn stop(&mut self) -> Result<()> {
-        if let Some(mut process) = self.process.take() {
+    /// Close the server's stdin, give it a moment to exit, then kill it
+    async fn stop_locked(&self, lifecycle: &mut Lifecycle) {
+        let session = self.state.write().unwrap().session.take();
+        if let Some(session) = session {
+            if let Err(e) = session.close().await {
+                debug!(
+                    "Could not close MCP server {} stdin: {}",
+                    self.config.name, e
+                );
+            }
+        }
+
+        if let Some(mut process) = lifecycle.process.take() {
             info!("Stopping MCP server: {}", self.config.name);
-            process.kill().await?;
+            match tokio::time::timeout(SHUTDOWN_GRACE, process.wait()).await {
+                Ok(_) => debug!("MCP server {} exited", self.config.name),
+                Err(_) => {
+                    warn!(
+                        "MCP server {} did not exit within {}s, killing it",
+                        self.config.name,
+                        SHUTDOWN_GRACE.as_secs()
+                    );
+                    let _ = process.kill().await;
+                }
+            }
         }
-        Ok(())
     }
 }
 
-impl Drop for McpServer {
-    fn drop(&mut self) {
-        if let Some(process) = self.process.take() {
-            if let Some(pid) = process.id() {
-                let _ = std::process::Command::new("kill")
-                    .arg(pid.to_string())
-                    .spawn();
+/// Log a server's stderr so it cannot fill the pipe and block the server,
+/// keeping the last few lines for error messages
+async fn drain_stderr(
+    name: String,
+    stderr: impl AsyncRead + Unpin,
+    tail: Arc<Mutex<VecDeque<String>>>,
+) {
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        debug!("[{} stderr] {}", name, line);
+        let mut tail = tail.lock().unwrap();
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Answers `initialize` with no capabilities, then runs `after`
+    fn shell_server(after: &str) -> McpServer {
+        let script = format!(
+            "read line; echo '{}'; {}",
+            r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{},"serverInfo":{"name":"sh","version":"1"}}}"#,
+            after
+        );
+        McpServer::new(McpServerConfig {
+            name: "sh".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script],
+            timeout: Some(5),
+            ..Default::default()
+        })
+        .with_restart_policy(RestartPolicy {
+            max_restarts: 1,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        })
+    }
+
+    async fn wait_until_down(server: &McpServer) {
+        for _ in 0..100 {
+            if !server.is_running() {
+                return;
             }
+            tokio::time::sleep(Duration::from_millis(20)).await;
         }
+        panic!("server did not go down");
+    }
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let config = McpServerConfig {
+            allow_tools: vec!["read_*".to_string(), "search".to_string()],
+            deny_tools: vec!["*_secret*".to_string()],
+            ..Default::default()
+        };
+        assert!(config.allows_tool("read_file"));
+        assert!(config.allows_tool("search"));
+        assert!(!config.allows_tool("search_all"));
+        assert!(!config.allows_tool("write_file"));
+        assert!(!config.allows_tool("read_secret_key"));
+
+        assert!(McpServerConfig::default().allows_tool("anything"));
+        assert!(glob_match("a*b*c", "abbc"));
+        assert!(!glob_match("a*bc", "abc_"));
+        assert!(!glob_match("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn test_tool_policy() {
+        let tool = |name: &str, annotations: serde_json::Value| -> Tool {
+            serde_json::from_value(serde_json::json!({"name": name, "annotations": annotations}))
+                .unwrap()
+        };
+        let config = McpServerConfig {
+            tool_policy: HashMap::from([
+                ("*".to_string(), ToolPolicy::Ask),
+                ("get_*".to_string(), ToolPolicy::Allow),
+                ("get_secret".to_string(), ToolPolicy::Deny),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.policy_for(&tool("get_user", json!({}))),
+            ToolPolicy::Allow
+        );
+        assert_eq!(
+            config.policy_for(&tool("get_secret", json!({}))),
+            ToolPolicy::Deny
+        );
+        assert_eq!(
+            config.policy_for(&tool("list", json!({"readOnlyHint": true}))),
+            ToolPolicy::Ask
+        );
+
+        let defaults = McpServerConfig::default();
+        assert_eq!(
+            defaults.policy_for(&tool("list", json!({"readOnlyHint": true}))),
+            ToolPolicy::Ask
+        );
+        let trusted = McpServerConfig {
+            trust_annotations: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            trusted.policy_for(&tool("list", json!({"readOnlyHint": true}))),
+            ToolPolicy::Allow
+        );
+        assert_eq!(
+            trusted.policy_for(&tool("drop", json!({"destructiveHint": true}))),
+            ToolPolicy::Ask
+        );
+        assert_eq!(
+            defaults.policy_for(&tool("drop", json!({"destructiveHint": true}))),
+            ToolPolicy::Ask
+        );
+        assert_eq!(
+            defaults.policy_for(&tool("other", json!(null))),
+            ToolPolicy::Ask
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dead_server_is_restarted_within_policy() {
+        // Exits right after the initialized notification
+        let server = shell_server("read line");
+        server.start().await.unwrap();
+
+        wait_until_down(&server).await;
+        assert!(server.needs_check());
+        assert!(server.check().await);
+        assert!(server.problem().is_none());
+
+        wait_until_down(&server).await;
+        assert!(!server.check().await);
+        assert!(server.problem().is_some());
+        assert!(!server.needs_check());
+        assert!(!server.check().await);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_stdin_and_waits() {
+        let server = shell_server("while read line; do :; done");
+        server.start().await.unwrap();
+        assert!(server.is_running());
+
+        let started = Instant::now();
+        server.shutdown().await;
+        assert!(started.elapsed() < SHUTDOWN_GRACE);
+        assert!(!server.is_running());
+        assert!(!server.check().await);
+        assert!(server.start().await.is_err());
     }
 }
@@ -0,0 +1,679 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use super::protocol::{
+    error_codes, CallToolResult, ClientCapabilities, GetPromptResult, Implementation,
+    InitializeParams, InitializeResult, JsonRpcError, JsonRpcMessage, JsonRpcNotification,
+    JsonRpcRequest, JsonRpcResponse, Prompt, ReadResourceResult, RequestId, Resource,
+    ServerCapabilities, Tool, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use super::transport::{MessageSink, MessageSource, Transport};
+
+/// How long a request waits for its response unless the server config says otherwise
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A client connection to one MCP server: request ids, the initialize
+/// handshake and the typed MCP methods on top of a [`Transport`].
+///
+/// A background task reads everything the server sends and hands each
+/// response to the request waiting for that id, so requests can be made
+/// concurrently through `&self`.
+pub struct McpSession {
+    shared: Arc<Shared>,
+    next_id: AtomicI64,
+    server: OnceLock<InitializeResult>,
+    timeout: Duration,
+    reader: JoinHandle<()>,
+}
+
+/// State used by both the session and its reader task
+struct Shared {
+    sink: tokio::sync::Mutex<Box<dyn MessageSink>>,
+    /// Requests waiting for a response; `None` once the connection is closed
+    pending: Mutex<Option<HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>>>,
+    /// Why the connection closed, when it broke rather than ended
+    failure: Mutex<Option<String>>,
+    handler: OnceLock<Arc<dyn RequestHandler>>,
+}
+
+/// Answers the requests a server sends to the client, such as
+/// `sampling/createMessage`. `ping` is always answered by the session.
+#[async_trait]
+pub trait RequestHandler: Send + Sync {
+    /// Capabilities announced in `initialize`
+    fn capabilities(&self) -> ClientCapabilities;
+
+    async fn handle(&self, method: &str, params: Option<Value>) -> Result<Value, JsonRpcError>;
+}
+
+impl McpSession {
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        let (sink, source) = Box::new(transport).split();
+        let shared = Arc::new(Shared {
+            sink: tokio::sync::Mutex::new(sink),
+            pending: Mutex::new(Some(HashMap::new())),
+            failure: Mutex::new(None),
+            handler: OnceLock::new(),
+        });
+        let reader = tokio::spawn(read_messages(source, shared.clone()));
+
+        Self {
+            shared,
+            next_id: AtomicI64::new(1),
+            server: OnceLock::new(),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            reader,
+        }
+    }
+
+    /// Default timeout for requests made through this session
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Let the server make requests of its own; without a handler they are
+    /// declined
+    pub fn with_handler(self, handler: Arc<dyn RequestHandler>) -> Self {
+        let _ = self.shared.handler.set(handler);
+        self
+    }
+
+    /// Negotiate the protocol version and exchange capabilities. Must be
+    /// called before any other request.
+    pub async fn initialize(&self) -> Result<&InitializeResult> {
+        let params = InitializeParams {
+            protocol_version: SUPPORTED_PROTOCOL_VERSIONS[0].to_string(),
+            capabilities: self
+                .shared
+                .handler
+                .get()
+                .map(|h| h.capabilities())
+                .unwrap_or_default(),
+            client_info: Implementation {
+                name: "lca".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        };
+
+        let result = self
+            .request("initialize", Some(serde_json::to_value(params)?))
+            .await?;
+        let result: InitializeResult = serde_json::from_value(result)?;
+
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&result.protocol_version.as_str()) {
+            anyhow::bail!(
+                "Server '{}' uses unsupported MCP protocol version {} (supported: {})",
+                result.server_info.name,
+                result.protocol_version,
+                SUPPORTED_PROTOCOL_VERSIONS.join(", ")
+            );
+        }
+
+        self.notify("notifications/initialized", None).await?;
+
+        info!(
+            "Connected to MCP server {} {} (protocol {})",
+            result.server_info.name, result.server_info.version, result.protocol_version
+        );
+
+        Ok(self.server.get_or_init(|| result))
+    }
+
+    /// Send a request and wait for its response, up to the session's timeout
+    pub async fn request(&self, method: &str, params: Option<Value>) -> Result<Value> {
+        self.request_with_timeout(method, params, self.timeout)
+            .await
+    }
+
+    /// Send a request and wait at most `timeout` for its response. On timeout
+    /// the server is told to cancel the request.
+    pub async fn request_with_timeout(
+        &self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> Result<Value> {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+
+        match self.shared.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(id.clone(), tx),
+            None => anyhow::bail!("MCP server connection is closed"),
+        };
+
+        let request = JsonRpcMessage::Request(JsonRpcRequest::new(id.clone(), method, params));
+        if let Err(e) = self.shared.send(&request).await {
+            self.shared.forget(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => Ok(response.into_result()?),
+            Ok(Err(_)) => match self.shared.failure.lock().unwrap().clone() {
+                Some(failure) => anyhow::bail!("{} during '{}'", failure, method),
+                None => anyhow::bail!("MCP server closed the connection during '{}'", method),
+            },
+            Err(_) => {
+                self.shared.forget(&id);
+                let cancel = json!({ "requestId": id, "reason": "timed out" });
+                if let Err(e) = self.notify("notifications/cancelled", Some(cancel)).await {
+                    debug!("Could not cancel MCP request: {}", e);
+                }
+                anyhow::bail!(
+                    "MCP request '{}' timed out after {:.1}s",
+                    method,
+                    timeout.as_secs_f64()
+                )
+            }
+        }
+    }
+
+    pub async fn notify(&self, method: &str, params: Option<Value>) -> Result<()> {
+        self.shared
+            .send(&JsonRpcMessage::Notification(JsonRpcNotification::new(
+                method, params,
+            )))
+            .await
+    }
+
+    /// Whether the reader task has seen the connection close
+    pub fn is_closed(&self) -> bool {
+        self.shared.pending.lock().unwrap().is_none()
+    }
+
+    /// Close the sending side; for a stdio server this closes its stdin,
+    /// which is how servers are asked to exit
+    pub async fn close(&self) -> Result<()> {
+        self.shared.sink.lock().await.close().await
+    }
+
+    /// All tools, following pagination. Empty if the server has no tools capability.
+    pub async fn list_tools(&self) -> Result<Vec<Tool>> {
+        if !self.supports(|c| c.tools.is_some()) {
+            return Ok(Vec::new());
+        }
+        self.list_paginated("tools/list", "tools").await
+    }
+
+    /// All resources, following pagination. Empty if the server has no resources capability.
+    pub async fn list_resources(&self) -> Result<Vec<Resource>> {
+        if !self.supports(|c| c.resources.is_some()) {
+            return Ok(Vec::new());
+        }
+        self.list_paginated("resources/list", "resources").await
+    }
+
+    /// All prompts, following pagination. Empty if the server has no prompts capability.
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>> {
+        if !self.supports(|c| c.prompts.is_some()) {
+            return Ok(Vec::new());
+        }
+        self.list_paginated("prompts/list", "prompts").await
+    }
+
+    /// Whether the initialized server declared a capability. Before
+    /// `initialize` everything is assumed to be supported.
+    fn supports(&self, capability: impl Fn(&ServerCapabilities) -> bool) -> bool {
+        self.server
+            .get()
+            .is_none_or(|s| capability(&s.capabilities))
+    }
+
+    async fn list_paginated<T: DeserializeOwned>(&self, method: &str, key: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = cursor.take().map(|c| json!({ "cursor": c }));
+            let mut page = self.request(method, params).await?;
+
+            let page_items: Vec<T> = serde_json::from_value(page[key].take())
+                .map_err(|e| anyhow::anyhow!("Invalid '{}' result: {}", method, e))?;
+            items.extend(page_items);
+
+            match page.get("nextCursor").and_then(Value::as_str) {
+                Some(next) => cursor = Some(next.to_string()),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<ReadResourceResult> {
+        let result = self
+            .request("resources/read", Some(json!({ "uri": uri })))
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        let result = self
+            .request(
+                "prompts/get",
+                Some(json!({ "name": name, "arguments": arguments })),
+            )
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult> {
+        self.call_tool_with_timeout(name, arguments, self.timeout)
+            .await
+    }
+
+    /// Call a tool, asking the server to report progress while it runs
+    pub async fn call_tool_with_timeout(
+        &self,
+        name: &str,
+        arguments: Value,
+        timeout: Duration,
+    ) -> Result<CallToolResult> {
+        let progress_token = format!("{}-{}", name, self.next_id.fetch_add(1, Ordering::Relaxed));
+        let params = json!({
+            "name": name,
+            "arguments": arguments,
+            "_meta": { "progressToken": progress_token },
+        });
+
+        let result = self
+            .request_with_timeout("tools/call", Some(params), timeout)
+            .await?;
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
+impl Drop for McpSession {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+impl Shared {
+    async fn send(&self, message: &JsonRpcMessage) -> Result<()> {
+        self.sink.lock().await.send(message).await
+    }
+
+    fn forget(&self, id: &RequestId) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(id);
+        }
+    }
+
+    async fn dispatch(&self, message: JsonRpcMessage) {
+        match message {
+            JsonRpcMessage::Response(response) => {
+                let waiting = self
+                    .pending
+                    .lock()
+                    .unwrap()
+                    .as_mut()
+                    .and_then(|pending| pending.remove(&response.id));
+                match waiting {
+                    Some(tx) => {
+                        let _ = tx.send(response);
+                    }
+                    // Usually a request that already timed out
+                    None => warn!("Ignoring MCP response to unknown request {:?}", response.id),
+                }
+            }
+            JsonRpcMessage::Notification(notification) => log_notification(&notification),
+            JsonRpcMessage::Request(request) => {
+                let reply = match (request.method.as_str(), self.handler.get()) {
+                    ("ping", _) => Ok(json!({})),
+                    (_, Some(handler)) => handler.handle(&request.method, request.params).await,
+                    (method, None) => {
+                        debug!("Declining MCP server request: {}", method);
+                        Err(JsonRpcError::new(
+                            error_codes::METHOD_NOT_FOUND,
+                            format!("Method not found: {}", method),
+                        ))
+                    }
+                };
+                let reply = match reply {
+                    Ok(result) => JsonRpcResponse::success(request.id, result),
+                    Err(error) => JsonRpcResponse::error(request.id, error),
+                };
+                if let Err(e) = self.send(&JsonRpcMessage::Response(reply)).await {
+                    warn!("Could not answer MCP server request: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Reader task: route everything the server sends until the connection
+/// closes, then fail the requests still waiting
+async fn read_messages(mut source: Box<dyn MessageSource>, shared: Arc<Shared>) {
+    loop {
+        match source.receive().await {
+            // A server request may take a while (sampling runs the model),
+            // so it must not hold up the responses behind it
+            Ok(Some(message @ JsonRpcMessage::Request(_))) => {
+                let shared = shared.clone();
+                tokio::spawn(async move { shared.dispatch(message).await });
+            }
+            Ok(Some(message)) => shared.dispatch(message).await,
+            Ok(None) => {
+                debug!("MCP server closed the connection");
+                break;
+            }
+            Err(e) => {
+                warn!("Lost connection to MCP server: {:#}", e);
+                *shared.failure.lock().unwrap() = Some(format!("{:#}", e));
+                break;
+            }
+        }
+    }
+
+    // Dropping the senders wakes every waiting request with an error
+    shared.pending.lock().unwrap().take();
+}
+
+fn log_notification(notification: &JsonRpcNotification) {
+    let params = notification.params.clone().unwrap_or(Value::Null);
+
+    match notification.method.as_str() {
+        "notifications/progress" => {
+            let progress = params
+                .get("progress")
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0);
+            let mut line = match params.get("total").and_then(Value::as_f64) {
+                Some(total) => format!("MCP progress: {}/{}", progress, total),
+                None => format!("MCP progress: {}", progress),
+            };
+            if let Some(message) = params.get("message").and_then(Value::as_str) {
+                line.push_str(&format!(" - {}", message));
+            }
+            info!("{}", line);
+        }
+        "notifications/message" => {
+            let data = match params.get("data") {
+                Some(Value::String(text)) => text.clone(),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            };
+            match params.get("level").and_then(Value::as_str) {
+                Some("debug") => debug!("MCP server log: {}", data),
+                Some("info") | Some("notice") => info!("MCP server log: {}", data),
+                _ => warn!("MCP server log: {}", data),
+            }
+        }
+        method => debug!("MCP notification: {}", method),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::transport::LineTransport;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    /// Minimal MCP server on the other end of a duplex stream. Answers with
+    /// `protocol_version`, pages tools two at a time, and sends a notification
+    /// and a ping before each tool result.
+    fn stand_in_server(protocol_version: &'static str) -> LineTransport {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, mut server_write) = tokio::io::split(server);
+
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(server_read).lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let message: Value = serde_json::from_str(&line).unwrap();
+                let id = message.get("id").cloned();
+                let method = message.get("method").and_then(Value::as_str);
+
+                let reply = match (method, id) {
+                    (Some("initialize"), Some(id)) => {
+                        assert_eq!(message["params"]["clientInfo"]["name"], "lca");
+                        json!({"jsonrpc": "2.0", "id": id, "result": {
+                            "protocolVersion": protocol_version,
+                            "capabilities": {"tools": {}, "resources": {}, "prompts": {}},
+                            "serverInfo": {"name": "stand-in", "version": "1.0"}
+                        }})
+                    }
+                    (Some("tools/list"), Some(id)) => {
+                        let page = if message["params"]["cursor"] == "2" {
+                            json!({"tools": [{"name": "c", "inputSchema": {}}]})
+                        } else {
+                            json!({"tools": [{"name": "a", "description": "A"}, {"name": "b"}], "nextCursor": "2"})
+                        };
+                        json!({"jsonrpc": "2.0", "id": id, "result": page})
+                    }
+                    (Some("tools/call"), Some(id)) => {
+                        let extra = format!(
+                            "{}\n{}\n",
+                            json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {}}),
+                            json!({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"})
+                        );
+                        server_write.write_all(extra.as_bytes()).await.unwrap();
+                        let text =
+                            format!("called {}", message["params"]["name"].as_str().unwrap());
+                        json!({"jsonrpc": "2.0", "id": id, "result": {"content": [{"type": "text", "text": text}]}})
+                    }
+                    (Some("resources/list"), Some(id)) => {
+                        json!({"jsonrpc": "2.0", "id": id, "result": {
+                            "resources": [{"uri": "file:///notes.md", "name": "notes", "mimeType": "text/markdown"}]
+                        }})
+                    }
+                    (Some("resources/read"), Some(id)) => {
+                        let uri = message["params"]["uri"].clone();
+                        json!({"jsonrpc": "2.0", "id": id, "result": {
+                            "contents": [{"uri": uri, "text": "# Notes"}]
+                        }})
+                    }
+                    (Some("prompts/list"), Some(id)) => {
+                        json!({"jsonrpc": "2.0", "id": id, "result": {
+                            "prompts": [{"name": "review", "arguments": [{"name": "file", "required": true}]}]
+                        }})
+                    }
+                    (Some("prompts/get"), Some(id)) => {
+                        let text = format!(
+                            "Review {}",
+                            message["params"]["arguments"]["file"].as_str().unwrap()
+                        );
+                        json!({"jsonrpc": "2.0", "id": id, "result": {
+                            "messages": [{"role": "user", "content": {"type": "text", "text": text}}]
+                        }})
+                    }
+                    (Some(other), Some(id)) => json!({"jsonrpc": "2.0", "id": id, "error": {
+                        "code": -32601, "message": format!("Unknown method {}", other)
+                    }}),
+                    // Notifications and our replies to the server's ping
+                    _ => continue,
+                };
+
+                let line = format!("{}\n", reply);
+                server_write.write_all(line.as_bytes()).await.unwrap();
+            }
+        });
+
+        LineTransport::new(client_read, client_write)
+    }
+
+    #[tokio::test]
+    async fn test_handshake_tools_and_errors() {
+        let session = McpSession::new(stand_in_server("2025-03-26"));
+
+        let server = session.initialize().await.unwrap();
+        assert_eq!(server.server_info.name, "stand-in");
+        assert_eq!(server.protocol_version, "2025-03-26");
+
+        let tools = session.list_tools().await.unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+
+        let result = session.call_tool("a", json!({})).await.unwrap();
+        assert_eq!(result.text(), "called a");
+
+        let err = session.request("unknown/method", None).await.unwrap_err();
+        let rpc = err.downcast_ref::<JsonRpcError>().unwrap();
+        assert_eq!(rpc.code, error_codes::METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_resources_and_prompts() {
+        let session = McpSession::new(stand_in_server("2025-06-18"));
+        session.initialize().await.unwrap();
+
+        let resources = session.list_resources().await.unwrap();
+        assert_eq!(resources[0].uri, "file:///notes.md");
+        assert_eq!(resources[0].mime_type.as_deref(), Some("text/markdown"));
+
+        let contents = session.read_resource("file:///notes.md").await.unwrap();
+        assert_eq!(contents.text(), "# Notes");
+
+        let prompts = session.list_prompts().await.unwrap();
+        assert_eq!(prompts[0].name, "review");
+        assert!(prompts[0].arguments[0].required);
+
+        let arguments = HashMap::from([("file".to_string(), "main.rs".to_string())]);
+        let prompt = session.get_prompt("review", &arguments).await.unwrap();
+        assert_eq!(prompt.text(), "Review main.rs");
+    }
+
+    /// The client end of a duplex stream, plus the server's line reader and writer
+    fn raw_server() -> (
+        LineTransport,
+        tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>,
+        tokio::io::WriteHalf<tokio::io::DuplexStream>,
+    ) {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (client_read, client_write) = tokio::io::split(client);
+        let (server_read, server_write) = tokio::io::split(server);
+        (
+            LineTransport::new(client_read, client_write),
+            BufReader::new(server_read).lines(),
+            server_write,
+        )
+    }
+
+    struct Roots;
+
+    #[async_trait]
+    impl RequestHandler for Roots {
+        fn capabilities(&self) -> ClientCapabilities {
+            ClientCapabilities {
+                roots: Some(json!({})),
+                sampling: None,
+            }
+        }
+
+        async fn handle(
+            &self,
+            method: &str,
+            _params: Option<Value>,
+        ) -> Result<Value, JsonRpcError> {
+            match method {
+                "roots/list" => Ok(json!({"roots": []})),
+                _ => Err(JsonRpcError::new(error_codes::METHOD_NOT_FOUND, method)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_requests_go_to_the_handler() {
+        let (transport, mut lines, mut server_write) = raw_server();
+        let _session = McpSession::new(transport).with_handler(Arc::new(Roots));
+
+        for (id, method) in [(1, "roots/list"), (2, "ping"), (3, "elicitation/create")] {
+            let line = format!(
+                "{}\n",
+                json!({"jsonrpc": "2.0", "id": id, "method": method})
+            );
+            server_write.write_all(line.as_bytes()).await.unwrap();
+        }
+
+        let mut replies = HashMap::new();
+        for _ in 0..3 {
+            let reply: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            replies.insert(reply["id"].as_i64().unwrap(), reply);
+        }
+        assert_eq!(replies[&1]["result"], json!({"roots": []}));
+        assert_eq!(replies[&2]["result"], json!({}));
+        assert_eq!(
+            replies[&3]["error"]["code"],
+            json!(error_codes::METHOD_NOT_FOUND)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_are_routed_by_id() {
+        let (transport, mut lines, mut server_write) = raw_server();
+        let session = McpSession::new(transport);
+
+        tokio::spawn(async move {
+            let first: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            let second: Value =
+                serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+            // Answer in reverse order, with a log line and a notification in between
+            for request in [&second, &first] {
+                let reply = format!(
+                    "server log line\n{}\n{}\n",
+                    json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {"progress": 1}}),
+                    json!({"jsonrpc": "2.0", "id": request["id"], "result": {"method": request["method"]}})
+                );
+                server_write.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+
+        let (a, b) = tokio::join!(session.request("a", None), session.request("b", None));
+        assert_eq!(a.unwrap()["method"], "a");
+        assert_eq!(b.unwrap()["method"], "b");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_cancels_and_close_fails_pending() {
+        let (transport, mut lines, server_write) = raw_server();
+        let session = McpSession::new(transport);
+
+        let err = session
+            .request_with_timeout("slow", None, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("'slow' timed out"));
+
+        let request: Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let cancel: Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(cancel["method"], "notifications/cancelled");
+        assert_eq!(cancel["params"]["requestId"], request["id"]);
+
+        // The server goes away while a request is waiting
+        let server_exits = async move {
+            lines.next_line().await.unwrap();
+            drop(server_write);
+        };
+        let (result, ()) = tokio::join!(session.request("never", None), server_exits);
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("closed the connection"));
+        assert!(session.request("after", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_protocol_version_is_rejected() {
+        let session = McpSession::new(stand_in_server("1999-01-01"));
+        let err = session.initialize().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported MCP protocol version 1999-01-01"));
+    }
+}
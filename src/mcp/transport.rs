@@ -0,0 +1,121 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{debug, warn};
+
+use super::protocol::JsonRpcMessage;
+
+/// A connection to an MCP server. It is split into its two directions so a
+/// background task can keep reading while requests are sent concurrently.
+pub trait Transport: Send {
+    fn split(self: Box<Self>) -> (Box<dyn MessageSink>, Box<dyn MessageSource>);
+}
+
+/// Sending half of a [`Transport`]
+#[async_trait]
+pub trait MessageSink: Send {
+    async fn send(&mut self, message: &JsonRpcMessage) -> Result<()>;
+
+    /// Tell the server no more messages are coming
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Receiving half of a [`Transport`]
+#[async_trait]
+pub trait MessageSource: Send {
+    /// Next message from the server, or `None` once the connection is closed
+    async fn receive(&mut self) -> Result<Option<JsonRpcMessage>>;
+}
+
+/// Newline-delimited JSON over a pair of byte streams, as used by stdio servers
+pub struct LineTransport {
+    reader: LineReader,
+    writer: LineWriter,
+}
+
+impl LineTransport {
+    pub fn new(
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Self {
+        Self {
+            reader: LineReader {
+                reader: BufReader::new(Box::new(reader)),
+            },
+            writer: LineWriter {
+                writer: Some(Box::new(writer)),
+            },
+        }
+    }
+}
+
+impl Transport for LineTransport {
+    fn split(self: Box<Self>) -> (Box<dyn MessageSink>, Box<dyn MessageSource>) {
+        (Box::new(self.writer), Box::new(self.reader))
+    }
+}
+
+struct LineWriter {
+    /// Dropped on `close`, so the stream really closes even while the
+    /// session is still shared
+    writer: Option<Box<dyn AsyncWrite + Send + Unpin>>,
+}
+
+#[async_trait]
+impl MessageSink for LineWriter {
+    async fn send(&mut self, message: &JsonRpcMessage) -> Result<()> {
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("connection closed"))?;
+        let line = serde_json::to_string(message)?;
+        debug!("MCP send: {}", line);
+
+        writer.write_all(line.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.shutdown().await?;
+        }
+        Ok(())
+    }
+}
+
+struct LineReader {
+    reader: BufReader<Box<dyn AsyncRead + Send + Unpin>>,
+}
+
+#[async_trait]
+impl MessageSource for LineReader {
+    async fn receive(&mut self) -> Result<Option<JsonRpcMessage>> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            debug!("MCP receive: {}", trimmed);
+            match serde_json::from_str(trimmed) {
+                Ok(message) => return Ok(Some(message)),
+                // Servers sometimes print banners or logs to stdout; skip them
+                Err(e) => warn!(
+                    "Ignoring non JSON-RPC line from MCP server ({}): {}",
+                    e, trimmed
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use super::AgentSystem;
+use crate::agents::Agent;
+use crate::mcp::protocol::{
+    error_codes, CallToolResult, Content, Implementation, InitializeResult, JsonRpcError,
+    JsonRpcMessage, JsonRpcRequest, JsonRpcResponse, ReadResourceResult, Resource,
+    ResourceContents, ServerCapabilities, Tool, ToolAnnotations, SUPPORTED_PROTOCOL_VERSIONS,
+};
+use crate::mcp::schema;
+use crate::mcp::transport::Transport;
+use crate::tools::ToolExecutor;
+
+const PROJECT_SUMMARY_URI: &str = "lca://project/summary";
+
+/// Prefix of the tools that run a registered agent, e.g. `agent_shell`
+const AGENT_TOOL_PREFIX: &str = "agent_";
+
+/// Serves the local tools and agents to an MCP client
+pub struct McpServe {
+    system: Arc<AgentSystem>,
+}
+
+impl McpServe {
+    pub fn new(system: Arc<AgentSystem>) -> Self {
+        Self { system }
+    }
+
+    /// Answer requests until the client closes the connection. Requests are
+    /// handled concurrently; notifications from the client are only logged.
+    pub async fn run(self, transport: impl Transport + 'static) -> Result<()> {
+        let (sink, mut source) = Box::new(transport).split();
+        let sink = Arc::new(Mutex::new(sink));
+        let server = Arc::new(self);
+
+        while let Some(message) = source.receive().await? {
+            match message {
+                JsonRpcMessage::Request(request) => {
+                    let (server, sink) = (server.clone(), sink.clone());
+                    tokio::spawn(async move {
+                        let response = server.handle(request).await;
+                        let message = JsonRpcMessage::Response(response);
+                        if let Err(e) = sink.lock().await.send(&message).await {
+                            warn!("Could not send MCP response: {}", e);
+                        }
+                    });
+                }
+                JsonRpcMessage::Notification(notification) => {
+                    debug!("MCP client notification: {}", notification.method);
+                }
+                JsonRpcMessage::Response(response) => {
+                    debug!("Ignoring MCP response {:?}", response.id);
+                }
+            }
+        }
+
+        info!("MCP client disconnected");
+        Ok(())
+    }
+
+    async fn handle(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let params = request.params.unwrap_or(Value::Null);
+
+        let result = match request.method.as_str() {
+            "initialize" => Ok(initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.tools() })),
+            "tools/call" => self.call_tool(&params).await,
+            "resources/list" => Ok(json!({ "resources": [project_summary_resource()] })),
+            "resources/read" => self.read_resource(&params).await,
+            method => Err(JsonRpcError::new(
+                error_codes::METHOD_NOT_FOUND,
+                format!("Method not found: {}", method),
+            )),
+        };
+
+        match result {
+            Ok(result) => JsonRpcResponse::success(request.id, result),
+            Err(error) => JsonRpcResponse::error(request.id, error),
+        }
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        let mut tools = builtin_tools();
+        tools.extend(self.system.agents().iter().map(|a| agent_tool(a.as_ref())));
+        tools.push(tool(
+            "run_task",
+            "Route a task to the best agent, or split it across several agents",
+            json!({
+                "type": "object",
+                "properties": {"task": {"type": "string", "description": "What to do"}},
+                "required": ["task"]
+            }),
+        ));
+        tools
+    }
+
+    async fn call_tool(&self, params: &Value) -> Result<Value, JsonRpcError> {
+        let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+
+        let tool = self
+            .tools()
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| {
+                JsonRpcError::new(
+                    error_codes::INVALID_PARAMS,
+                    format!("Unknown tool: {}", name),
+                )
+            })?;
+
+        // Bad arguments and failed calls are tool errors the model can act
+        // on, so they go in the result rather than a JSON-RPC error
+        let errors = schema::validate(&tool.input_schema, &arguments);
+        let result = if !errors.is_empty() {
+            error_result(format!("Invalid arguments:\n{}", errors.join("\n")))
+        } else {
+            match self.run_tool(name, &arguments).await {
+                Ok(result) => result,
+                Err(e) => error_result(format!("{:#}", e)),
+            }
+        };
+
+        Ok(serde_json::to_value(result).unwrap_or_default())
+    }
+
+    async fn run_tool(&self, name: &str, arguments: &Value) -> Result<CallToolResult> {
+        info!("MCP client called tool: {}", name);
+
+        if let Some(output) = call_builtin(&self.system.tool_executor, name, arguments).await? {
+            return Ok(text_result(output));
+        }
+
+        let task = arguments["task"].as_str().unwrap_or_default();
+        let result = match name.strip_prefix(AGENT_TOOL_PREFIX) {
+            Some(agent) => self.system.execute_with_agent(agent, task).await?,
+            None => self.system.execute_task(task).await?,
+        };
+
+        Ok(CallToolResult {
+            is_error: !result.success,
+            ..text_result(result.output)
+        })
+    }
+
+    async fn read_resource(&self, params: &Value) -> Result<Value, JsonRpcError> {
+        let uri = params.get("uri").and_then(Value::as_str).unwrap_or("");
+        if uri != PROJECT_SUMMARY_URI {
+            return Err(JsonRpcError::new(
+                error_codes::INVALID_PARAMS,
+                format!("Unknown resource: {}", uri),
+            ));
+        }
+
+        let summary = self
+            .system
+            .context_manager
+            .get_project_summary()
+            .await
+            .map_err(|e| JsonRpcError::new(error_codes::INTERNAL_ERROR, e.to_string()))?;
+
+        let result = ReadResourceResult {
+            contents: vec![ResourceContents {
+                uri: uri.to_string(),
+                mime_type: Some("text/plain".to_string()),
+                text: Some(summary),
+                blob: None,
+            }],
+        };
+        Ok(serde_json::to_value(result).unwrap_or_default())
+    }
+}
+
+/// Accept the client's protocol version if we speak it, otherwise offer our newest
+fn initialize(params: &Value) -> Value {
+    let requested = params.get("protocolVersion").and_then(Value::as_str);
+    let protocol_version = requested
+        .filter(|v| SUPPORTED_PROTOCOL_VERSIONS.contains(v))
+        .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0]);
+
+    let result = InitializeResult {
+        protocol_version: protocol_version.to_string(),
+        capabilities: ServerCapabilities {
+            tools: Some(json!({})),
+            resources: Some(json!({})),
+            ..Default::default()
+        },
+        server_info: Implementation {
+            name: "lca".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        instructions: Some(
+            "Local coding agents. File writes and shell commands are only allowed \
+             when lca was started with --allow-all."
+                .to_string(),
+        ),
+    };
+    serde_json::to_value(result).unwrap_or_default()
+}
+
+fn tool(name: &str, description: &str, input_schema: Value) -> Tool {
+    Tool {
+        name: name.to_string(),
+        description: description.to_string(),
+        input_schema,
+        annotations: None,
+    }
+}
+
+/// The `ToolExecutor` operations
+fn builtin_tools() -> Vec<Tool> {
+    let path = |description: &str| json!({"type": "string", "description": description});
+
+    let mut tools = vec![
+        tool(
+            "read_file",
+            "Read a text file",
+            json!({
+                "type": "object",
+                "properties": {"path": path("File path, relative to the project")},
+                "required": ["path"]
+            }),
+        ),
+        tool(
+            "write_file",
+            "Create or overwrite a file",
+            json!({
+                "type": "object",
+                "properties": {
+                    "path": path("File path, relative to the project"),
+                    "content": {"type": "string", "description": "New file contents"}
+                },
+                "required": ["path", "content"]
+            }),
+        ),
+        tool(
+            "list_files",
+            "List the entries of a directory",
+            json!({
+                "type": "object",
+                "properties": {"path": path("Directory, relative to the project (default: .)")}
+            }),
+        ),
+        tool(
+            "search_files",
+            "Find files containing a literal string, honouring ignore files",
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {"type": "string", "description": "Text to search for"},
+                    "path": path("Directory to search (default: .)")
+                },
+                "required": ["pattern"]
+            }),
+        ),
+        tool(
+            "execute_shell",
+            "Run a shell command and return its output",
+            json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string", "description": "Command line to run"},
+                    "working_dir": path("Directory to run in (default: .)")
+                },
+                "required": ["command"]
+            }),
+        ),
+    ];
+    for tool in &mut tools {
+        let read_only = !matches!(tool.name.as_str(), "write_file" | "execute_shell");
+        tool.annotations = Some(ToolAnnotations {
+            read_only_hint: Some(read_only),
+            destructive_hint: Some(!read_only),
+            ..Default::default()
+        });
+    }
+    tools
+}
+
+fn agent_tool(agent: &dyn Agent) -> Tool {
+    tool(
+        &format!("{}{}", AGENT_TOOL_PREFIX, agent.name()),
+        &format!(
+            "Run a task with the {} agent: {}",
+            agent.name(),
+            agent.description()
+        ),
+        json!({
+            "type": "object",
+            "properties": {"task": {"type": "string", "description": "What the agent should do"}},
+            "required": ["task"]
+        }),
+    )
+}
+
+/// Run one of the `builtin_tools`. `None` if `name` is not one of them.
+async fn call_builtin(
+    tools: &ToolExecutor,
+    name: &str,
+    arguments: &Value,
+) -> Result<Option<String>> {
+    let arg = |key: &str| arguments[key].as_str().unwrap_or_default();
+    let dir = |key: &str| arguments[key].as_str().unwrap_or(".");
+
+    let output = match name {
+        "read_file" => tools.read_file(arg("path")).await?,
+        "write_file" => {
+            tools.write_file(arg("path"), arg("content")).await?;
+            format!("Wrote {}", arg("path"))
+        }
+        "list_files" => tools.list_files(dir("path")).await?.join("\n"),
+        "search_files" => tools
+            .search_files(dir("path"), arg("pattern"))
+            .await?
+            .join("\n"),
+        "execute_shell" => {
+            tools
+                .execute_shell(arg("command"), dir("working_dir"))
+                .await?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(output))
+}
+
+fn project_summary_resource() -> Resource {
+    Resource {
+        uri: PROJECT_SUMMARY_URI.to_string(),
+        name: "project-summary".to_string(),
+        description: Some("Project name, path, language and framework".to_string()),
+        mime_type: Some("text/plain".to_string()),
+    }
+}
+
+fn text_result(text: String) -> CallToolResult {
+    CallToolResult {
+        content: vec![Content::Text { text }],
+        is_error: false,
+        structured_content: None,
+    }
+}
+
+fn error_result(text: String) -> CallToolResult {
+    CallToolResult {
+        is_error: true,
+        ..text_result(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::{PermissionManager, PermissionMode};
+
+    fn executor(dir: &std::path::Path, mode: PermissionMode) -> ToolExecutor {
+        let permissions = PermissionManager::new(mode).with_interactive(false);
+        ToolExecutor::new(dir).with_permissions(Arc::new(permissions))
+    }
+
+    #[tokio::test]
+    async fn test_builtin_tools_follow_the_permission_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+
+        let tools = executor(dir.path(), PermissionMode::Ask);
+        let read = call_builtin(&tools, "read_file", &json!({"path": "notes.txt"}))
+            .await
+            .unwrap();
+        assert_eq!(read.as_deref(), Some("hello"));
+
+        let write = json!({"path": "out.txt", "content": "x"});
+        let err = call_builtin(&tools, "write_file", &write)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--allow-all"));
+        assert!(!dir.path().join("out.txt").exists());
+
+        let tools = executor(dir.path(), PermissionMode::AllowAll);
+        call_builtin(&tools, "write_file", &write).await.unwrap();
+        assert!(dir.path().join("out.txt").exists());
+
+        assert!(call_builtin(&tools, "agent_shell", &json!({}))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_builtin_tool_schemas() {
+        for tool in builtin_tools() {
+            assert_eq!(tool.input_schema["type"], "object", "{}", tool.name);
+        }
+        let read_file = &builtin_tools()[0];
+        assert_eq!(
+            schema::validate(&read_file.input_schema, &json!({})),
+            vec!["$: missing required property 'path'"]
+        );
+    }
+
+    #[test]
+    fn test_initialize_negotiates_the_version() {
+        let result = initialize(&json!({"protocolVersion": "2024-11-05"}));
+        assert_eq!(result["protocolVersion"], "2024-11-05");
+        assert_eq!(result["serverInfo"]["name"], "lca");
+
+        let result = initialize(&json!({"protocolVersion": "1999-01-01"}));
+        assert_eq!(result["protocolVersion"], SUPPORTED_PROTOCOL_VERSIONS[0]);
+    }
+}
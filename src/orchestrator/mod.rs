@@ -1,3 +1,6 @@
+pub mod mcp_serve;
+pub mod plan;
 pub mod system;
 
+pub use plan::ExecutionPlan;
 pub use system::AgentSystem;
@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::agents::{AgentCapability, SubTask};
+
+/// A subtask as shown to the user for review, with the agent's own step breakdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedSubtask {
+    pub description: String,
+    pub agent_type: String,
+    #[serde(default)]
+    pub dependencies: Vec<usize>,
+    #[serde(default)]
+    pub steps: Vec<String>,
+    #[serde(default)]
+    pub side_effects: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPlan {
+    pub task: String,
+    pub created_at: u64,
+    pub subtasks: Vec<PlannedSubtask>,
+}
+
+impl ExecutionPlan {
+    pub fn new(task: impl Into<String>, subtasks: Vec<PlannedSubtask>) -> Self {
+        Self {
+            task: task.into(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            subtasks,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read plan: {:?}", path))?;
+        serde_json::from_str(&data).with_context(|| format!("Invalid plan file: {:?}", path))
+    }
+
+    /// Save under `<working_dir>/.lca/plans/` and return the file path. Plans
+    /// created in the same second get a numbered suffix instead of replacing
+    /// each other.
+    pub fn save(&self, working_dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let dir = working_dir.as_ref().join(".lca").join("plans");
+        std::fs::create_dir_all(&dir)?;
+        let json = serde_json::to_string_pretty(self)?;
+
+        let mut path = dir.join(format!("plan-{}.json", self.created_at));
+        let mut attempt = 1;
+        loop {
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path);
+            match file {
+                Ok(mut file) => {
+                    file.write_all(json.as_bytes())
+                        .with_context(|| format!("Failed to write plan: {:?}", path))?;
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    attempt += 1;
+                    path = dir.join(format!("plan-{}-{}.json", self.created_at, attempt));
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to write plan: {:?}", path))
+                }
+            }
+        }
+    }
+
+    /// Convert to coordinator subtasks, folding the reviewed steps into each description
+    pub fn to_subtasks(&self) -> Vec<SubTask> {
+        self.subtasks
+            .iter()
+            .map(|planned| {
+                let mut description = planned.description.clone();
+                if planned.steps.len() > 1 {
+                    description.push_str("\n\nPlanned steps:");
+                    for (idx, step) in planned.steps.iter().enumerate() {
+                        description.push_str(&format!("\n{}. {}", idx + 1, step));
+                    }
+                }
+                SubTask {
+                    description,
+                    agent_type: planned.agent_type.clone(),
+                    dependencies: planned.dependencies.clone(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = format!("Plan for: {}\n", self.task);
+
+        for (idx, subtask) in self.subtasks.iter().enumerate() {
+            out.push_str(&format!(
+                "\n  [{}] ({}) {}",
+                idx, subtask.agent_type, subtask.description
+            ));
+            if !subtask.dependencies.is_empty() {
+                let deps: Vec<String> =
+                    subtask.dependencies.iter().map(|d| d.to_string()).collect();
+                out.push_str(&format!("\n      depends on: {}", deps.join(", ")));
+            }
+            for step in &subtask.steps {
+                out.push_str(&format!("\n      - {}", step));
+            }
+            if !subtask.side_effects.is_empty() {
+                out.push_str(&format!(
+                    "\n      side effects: {}",
+                    subtask.side_effects.join("; ")
+                ));
+            }
+        }
+
+        out
+    }
+
+    /// Remove a subtask, dropping dependencies on it and renumbering later ones
+    pub fn delete(&mut self, idx: usize) -> Result<()> {
+        if idx >= self.subtasks.len() {
+            anyhow::bail!("No subtask {}", idx);
+        }
+
+        self.subtasks.remove(idx);
+        for subtask in &mut self.subtasks {
+            subtask.dependencies.retain(|d| *d != idx);
+            for dep in &mut subtask.dependencies {
+                if *dep > idx {
+                    *dep -= 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move subtask `from` to position `to`, keeping dependencies pointing at the same subtasks.
+    /// Fails if the new order would run a subtask before one it depends on.
+    pub fn move_subtask(&mut self, from: usize, to: usize) -> Result<()> {
+        let len = self.subtasks.len();
+        if from >= len || to >= len {
+            anyhow::bail!("Positions must be between 0 and {}", len.saturating_sub(1));
+        }
+        // A hand-edited plan may point at subtasks that don't exist
+        for (idx, subtask) in self.subtasks.iter().enumerate() {
+            if let Some(dep) = subtask.dependencies.iter().find(|d| **d >= len) {
+                anyhow::bail!(
+                    "Subtask {} depends on subtask {}, which does not exist",
+                    idx,
+                    dep
+                );
+            }
+        }
+
+        let mut order: Vec<usize> = (0..len).collect();
+        let moved = order.remove(from);
+        order.insert(to, moved);
+
+        // new_index[old] = new position of the subtask previously at `old`
+        let mut new_index = vec![0; len];
+        for (new, old) in order.iter().enumerate() {
+            new_index[*old] = new;
+        }
+
+        let reordered: Vec<PlannedSubtask> = order
+            .iter()
+            .map(|old| {
+                let mut subtask = self.subtasks[*old].clone();
+                subtask.dependencies = subtask.dependencies.iter().map(|d| new_index[*d]).collect();
+                subtask
+            })
+            .collect();
+
+        for (idx, subtask) in reordered.iter().enumerate() {
+            if let Some(dep) = subtask.dependencies.iter().find(|d| **d >= idx) {
+                anyhow::bail!("Subtask {} would run before its dependency {}", idx, dep);
+            }
+        }
+
+        self.subtasks = reordered;
+        Ok(())
+    }
+}
+
+/// Describe what running an agent with these capabilities may change
+pub fn side_effects_for(capabilities: &[AgentCapability]) -> Vec<String> {
+    let mut effects = Vec::new();
+
+    for capability in capabilities {
+        let effect = match capability {
+            AgentCapability::CodeGeneration | AgentCapability::CodeEditing => {
+                "may create or modify source files"
+            }
+            AgentCapability::FileOperations => "may read or write files",
+            AgentCapability::ShellExecution | AgentCapability::Testing => "runs shell commands",
+            AgentCapability::TaskOrchestration => "calls other agents or external tools",
+            AgentCapability::Analysis | AgentCapability::ContextManagement => continue,
+        };
+        if !effects.iter().any(|e| e == effect) {
+            effects.push(effect.to_string());
+        }
+    }
+
+    if effects.is_empty() {
+        effects.push("read-only".to_string());
+    }
+
+    effects
+}
+
+/// Show the plan and let the user approve, edit, reorder or delete subtasks.
+/// Returns `false` if the user cancelled.
+pub fn review_plan(plan: &mut ExecutionPlan) -> bool {
+    let _prompt = crate::permissions::prompt_lock();
+
+    loop {
+        println!("\n┌─────────────────────────────────────────────────────────────┐");
+        println!("│ EXECUTION PLAN REVIEW                                      │");
+        println!("└─────────────────────────────────────────────────────────────┘");
+        println!("{}", plan.render());
+
+        println!("\n  Options:");
+        println!("    [a]     Approve and run");
+        println!("    [e N]   Edit subtask N");
+        println!("    [m N M] Move subtask N to position M");
+        println!("    [d N]   Delete subtask N");
+        println!("    [q]     Cancel");
+
+        // Closed or piped stdin cancels rather than asking forever
+        let Some(input) = read_line("\n  Your choice: ") else {
+            println!("\n  >> Plan cancelled\n");
+            return false;
+        };
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        let index = |i: usize| parts.get(i).and_then(|p| p.parse::<usize>().ok());
+
+        let outcome = match parts.first().map(|p| p.to_lowercase()).as_deref() {
+            Some("a") | Some("approve") => {
+                if plan.subtasks.is_empty() {
+                    Err(anyhow::anyhow!("The plan has no subtasks left"))
+                } else {
+                    println!("  >> Plan approved\n");
+                    return true;
+                }
+            }
+            Some("q") | Some("quit") => {
+                println!("  >> Plan cancelled\n");
+                return false;
+            }
+            Some("d") => match index(1) {
+                Some(n) => plan.delete(n),
+                None => Err(anyhow::anyhow!("Usage: d N")),
+            },
+            Some("m") => match (index(1), index(2)) {
+                (Some(from), Some(to)) => plan.move_subtask(from, to),
+                _ => Err(anyhow::anyhow!("Usage: m N M")),
+            },
+            Some("e") => match index(1) {
+                Some(n) => edit_subtask(plan, n),
+                None => Err(anyhow::anyhow!("Usage: e N")),
+            },
+            _ => Err(anyhow::anyhow!("Invalid choice")),
+        };
+
+        if let Err(e) = outcome {
+            println!("  {}", e);
+        }
+    }
+}
+
+fn edit_subtask(plan: &mut ExecutionPlan, idx: usize) -> Result<()> {
+    let subtask = plan
+        .subtasks
+        .get_mut(idx)
+        .ok_or_else(|| anyhow::anyhow!("No subtask {}", idx))?;
+
+    println!("  Current: {}", subtask.description);
+    let description = read_line("  New description (blank to keep): ").unwrap_or_default();
+    if !description.is_empty() {
+        subtask.description = description;
+        // The agent's step breakdown no longer matches the new description
+        subtask.steps.clear();
+    }
+
+    let agent = read_line(&format!("  Agent [{}]: ", subtask.agent_type)).unwrap_or_default();
+    if !agent.is_empty() {
+        subtask.agent_type = agent;
+    }
+
+    Ok(())
+}
+
+/// The trimmed line, or `None` at end of input or on a read error
+fn read_line(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(description: &str, dependencies: Vec<usize>) -> PlannedSubtask {
+        PlannedSubtask {
+            description: description.to_string(),
+            agent_type: "file".to_string(),
+            dependencies,
+            steps: vec![],
+            side_effects: vec![],
+        }
+    }
+
+    fn plan() -> ExecutionPlan {
+        ExecutionPlan::new(
+            "update config",
+            vec![
+                planned("read config", vec![]),
+                planned("read defaults", vec![]),
+                planned("merge", vec![0, 1]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_delete_renumbers_dependencies() {
+        let mut plan = plan();
+        plan.delete(0).unwrap();
+
+        assert_eq!(plan.subtasks.len(), 2);
+        assert_eq!(plan.subtasks[1].dependencies, vec![0]);
+        assert!(plan.delete(5).is_err());
+    }
+
+    #[test]
+    fn test_move_keeps_dependencies_and_rejects_invalid_order() {
+        let mut plan = plan();
+        plan.move_subtask(1, 0).unwrap();
+
+        assert_eq!(plan.subtasks[0].description, "read defaults");
+        assert_eq!(plan.subtasks[2].dependencies, vec![1, 0]);
+
+        assert!(plan.move_subtask(2, 0).is_err());
+        assert_eq!(plan.subtasks[2].description, "merge");
+
+        plan.subtasks[2].dependencies.push(7);
+        let err = plan.move_subtask(0, 1).unwrap_err();
+        assert!(err.to_string().contains("depends on subtask 7"));
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let plan = plan();
+
+        let path = plan.save(dir.path()).unwrap();
+        let loaded = ExecutionPlan::load(&path).unwrap();
+
+        assert_eq!(loaded.task, "update config");
+        assert_eq!(loaded.to_subtasks()[2].dependencies, vec![0, 1]);
+
+        // Saved again in the same second, it gets a file of its own
+        let again = plan.save(dir.path()).unwrap();
+        assert_ne!(again, path);
+        assert!(again.to_string_lossy().ends_with("-2.json"));
+        assert!(ExecutionPlan::load(&path).is_ok());
+    }
+}
@@ -1,24 +1,36 @@
 use anyhow::Result;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 
+use super::plan::{self, ExecutionPlan, PlannedSubtask};
+use crate::agents::mcp_agent::McpAgent;
+use crate::agents::router::parse_agent_mention;
 use crate::agents::{
-    Agent, AgentContext, AgentRegistry, AgentResult, AnalysisAgent, CodeAgent, CoordinatorAgent,
-    FileAgent, ShellAgent,
+    Agent, AgentContext, AgentRegistry, AgentResult, AgentRouter, AnalysisAgent, CodeAgent,
+    ConfiguredAgent, Conversation, CoordinatorAgent, FileAgent, ReviewAgent, SearchAgent,
+    ShellAgent, TestAgent,
 };
+use crate::config::Config;
 use crate::context::ContextManager;
 use crate::llm::LlmClient;
+use crate::mcp::client::qualified_name;
+use crate::mcp::protocol::Prompt;
+use crate::mcp::{McpClient, McpConfig};
 use crate::permissions::PermissionManager;
 use crate::tools::ToolExecutor;
 
 pub struct AgentSystem {
     coordinator: Arc<CoordinatorAgent>,
     registry: Arc<AgentRegistry>,
+    router: AgentRouter,
     pub llm_client: Arc<dyn LlmClient>,
     pub tool_executor: Arc<ToolExecutor>,
     pub context_manager: Arc<ContextManager>,
-    #[allow(dead_code)]
+    /// Present when MCP servers are configured
+    pub mcp_client: Option<Arc<McpClient>>,
     pub permission_manager: Arc<PermissionManager>,
+    /// Earlier tasks and answers, given to every agent
+    pub conversation: Conversation,
 }
 
 impl AgentSystem {
@@ -26,71 +38,312 @@ impl AgentSystem {
         llm_client: Arc<dyn LlmClient>,
         working_directory: impl Into<String>,
         permission_manager: Arc<PermissionManager>,
+        config: &Config,
     ) -> Result<Self> {
         let working_dir = working_directory.into();
 
         let mut registry = AgentRegistry::new();
+        // Agents with an `[agents.<name>]` table use their own model settings
+        let mut register = |agent: Arc<dyn Agent>| {
+            let settings = config.agents.get(agent.name());
+            registry.register(ConfiguredAgent::wrap(agent, settings));
+        };
 
-        registry.register(Arc::new(CodeAgent::new()));
-        registry.register(Arc::new(ShellAgent::new()));
-        registry.register(Arc::new(FileAgent::new()));
-        registry.register(Arc::new(AnalysisAgent::new()));
+        register(Arc::new(CodeAgent::new()));
+        register(Arc::new(ShellAgent::new()));
+        register(Arc::new(FileAgent::new()));
+        register(Arc::new(AnalysisAgent::new()));
+        register(Arc::new(TestAgent::new()));
+        register(Arc::new(ReviewAgent::new()));
+        register(Arc::new(SearchAgent::new()));
+
+        // Problems with mcp.json are logged while loading and don't stop LCA
+        let mcp_config = McpConfig::load(&working_dir, &config.mcp);
+        let mcp_client = if mcp_config.servers.is_empty() {
+            None
+        } else {
+            info!(
+                "Registering MCP agent for {} configured server(s)",
+                mcp_config.servers.len()
+            );
+            let mcp_client = Arc::new(
+                McpClient::with_servers(mcp_config.servers)
+                    .with_permissions(permission_manager.clone())
+                    .with_sampling(llm_client.clone(), mcp_config.sampling_models)
+                    .with_roots(vec![std::fs::canonicalize(&working_dir)
+                        .unwrap_or_else(|_| working_dir.clone().into())])
+                    .with_audit_log(
+                        std::path::Path::new(&working_dir)
+                            .join(".lca")
+                            .join("mcp-audit.jsonl"),
+                    ),
+            );
+            register(Arc::new(McpAgent::new(mcp_client.clone())));
+            Some(mcp_client)
+        };
+
+        for name in config.agents.keys() {
+            if registry.get(name).is_none() {
+                warn!("Ignoring settings for unknown agent '{}'", name);
+            }
+        }
 
         let registry = Arc::new(registry);
-        let coordinator = Arc::new(CoordinatorAgent::new(registry.clone()));
+        let coordinator = Arc::new(
+            CoordinatorAgent::new(registry.clone()).with_options(config.coordinator_options()),
+        );
+        let router = AgentRouter::new(registry.clone());
 
         let tool_executor =
             Arc::new(ToolExecutor::new(working_dir).with_permissions(permission_manager.clone()));
 
-        let context_manager = Arc::new(ContextManager::default()?);
+        let context_manager = Arc::new(ContextManager::new(config.ui.context_db())?);
 
         Ok(Self {
             coordinator,
             registry,
+            router,
             llm_client,
             tool_executor,
             context_manager,
+            mcp_client,
             permission_manager,
+            conversation: config.conversation(),
         })
     }
 
     pub async fn execute_task(&self, task: &str) -> Result<AgentResult> {
         info!("Executing task: {}", task);
 
-        let capable_agents = self.registry.find_capable(task);
-
-        if capable_agents.len() == 1 {
-            let agent = &capable_agents[0];
-            info!("Routing to single capable agent: {}", agent.name());
-
-            let mut context = AgentContext::new(".");
-            agent
-                .execute(
-                    task,
-                    &mut context,
-                    self.llm_client.clone(),
-                    self.tool_executor.clone(),
-                    self.context_manager.clone(),
-                )
-                .await
-        } else {
-            info!("Using coordinator for multi-agent orchestration");
-
-            let mut context = AgentContext::new(".");
-            self.coordinator
-                .execute(
-                    task,
-                    &mut context,
-                    self.llm_client.clone(),
-                    self.tool_executor.clone(),
-                    self.context_manager.clone(),
-                )
+        if let Some((name, forced_task)) = parse_agent_mention(task) {
+            return match self.registry.get(name) {
+                Some(agent) => {
+                    info!("Routing to forced agent: {}", agent.name());
+                    self.run_agent(agent.as_ref(), forced_task).await
+                }
+                None => Ok(AgentResult::failure(format!(
+                    "Unknown agent '@{}'. Available agents: {}",
+                    name,
+                    self.registry.names().join(", ")
+                ))),
+            };
+        }
+
+        let decision = self.router.route(task, self.llm_client.clone()).await;
+
+        let single_agent = match decision.top() {
+            Some(choice)
+                if !decision.multi_step && choice.confidence >= self.router.min_confidence() =>
+            {
+                self.registry.get(&choice.agent)
+            }
+            _ => None,
+        };
+
+        match single_agent {
+            Some(agent) => {
+                info!(
+                    "Routing to agent: {} ({:?}, confidence {:.2})",
+                    agent.name(),
+                    decision.source,
+                    decision.top().map(|c| c.confidence).unwrap_or_default()
+                );
+                self.run_agent(agent.as_ref(), task).await
+            }
+            None => {
+                info!("Using coordinator for multi-agent orchestration");
+                self.run_agent(self.coordinator.as_ref(), task).await
+            }
+        }
+    }
+
+    /// Run a task with the named agent, skipping routing
+    pub async fn execute_with_agent(&self, name: &str, task: &str) -> Result<AgentResult> {
+        let agent = self
+            .registry
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Agent '{}' not found", name))?;
+        self.run_agent(agent.as_ref(), task).await
+    }
+
+    pub fn agents(&self) -> Vec<Arc<dyn Agent>> {
+        self.registry.agents()
+    }
+
+    async fn run_agent(&self, agent: &dyn Agent, task: &str) -> Result<AgentResult> {
+        let mut context = self.conversation_context();
+        let start = context.conversation_history.len();
+        let result = agent
+            .execute(
+                task,
+                &mut context,
+                self.llm_client.clone(),
+                self.tool_executor.clone(),
+                self.context_manager.clone(),
+            )
+            .await?;
+        self.remember(task, context, start, &result).await;
+        Ok(result)
+    }
+
+    fn conversation_context(&self) -> AgentContext {
+        AgentContext::new(".").with_history(self.conversation.messages())
+    }
+
+    /// Keep a finished task, with the messages added since `start`, for the tasks after it
+    async fn remember(
+        &self,
+        task: &str,
+        context: AgentContext,
+        start: usize,
+        result: &AgentResult,
+    ) {
+        let notes = context
+            .conversation_history
+            .into_iter()
+            .skip(start)
+            .collect();
+        self.conversation.record_turn(task, notes, &result.output);
+        self.conversation.compact(self.llm_client.clone()).await;
+    }
+
+    /// Decompose a task and ask each agent for its steps, without running anything
+    pub async fn plan_task(&self, task: &str) -> Result<ExecutionPlan> {
+        info!("Planning task: {}", task);
+
+        let subtasks = self
+            .coordinator
+            .decompose_task(task, &self.conversation_context(), self.llm_client.clone())
+            .await?;
+
+        let mut planned = Vec::new();
+        for subtask in subtasks {
+            let (steps, side_effects) = match self.registry.get(&subtask.agent_type) {
+                Some(agent) => {
+                    let steps = agent
+                        .plan(&subtask.description, self.llm_client.clone())
+                        .await
+                        .unwrap_or_else(|_| vec![subtask.description.clone()]);
+                    (steps, plan::side_effects_for(&agent.capabilities()))
+                }
+                None => (
+                    Vec::new(),
+                    vec![format!("unknown agent '{}'", subtask.agent_type)],
+                ),
+            };
+
+            planned.push(PlannedSubtask {
+                description: subtask.description,
+                agent_type: subtask.agent_type,
+                dependencies: subtask.dependencies,
+                steps,
+                side_effects,
+            });
+        }
+
+        Ok(ExecutionPlan::new(task, planned))
+    }
+
+    /// Run a reviewed or previously saved plan
+    pub async fn execute_plan(&self, plan: &ExecutionPlan) -> Result<AgentResult> {
+        info!("Executing plan for: {}", plan.task);
+
+        let mut context = self.conversation_context();
+        let start = context.conversation_history.len();
+        let result = self
+            .coordinator
+            .execute_subtasks(
+                &plan.task,
+                &plan.to_subtasks(),
+                &mut context,
+                self.llm_client.clone(),
+                self.tool_executor.clone(),
+                self.context_manager.clone(),
+            )
+            .await?;
+        self.remember(&plan.task, context, start, &result).await;
+        Ok(result)
+    }
+
+    /// Append the contents of MCP resources to a task so any agent sees them
+    pub async fn attach_resources(&self, task: &str, uris: &[String]) -> Result<String> {
+        if uris.is_empty() {
+            return Ok(task.to_string());
+        }
+        let mcp = self
+            .mcp_client
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No MCP servers are configured"))?;
+
+        let mut attached = format!("{}\n\nAttached resources:", task);
+        for uri in uris {
+            let contents = mcp.read_resource(uri).await?;
+            attached.push_str(&format!("\n\n[{}]\n{}", uri, contents.text()));
+        }
+        Ok(attached)
+    }
+
+    /// MCP prompts usable as slash-commands: the prompt name, or
+    /// `server__name` when several servers have a prompt with that name
+    pub async fn prompt_commands(&self) -> Vec<(String, Prompt)> {
+        let Some(mcp) = &self.mcp_client else {
+            return Vec::new();
+        };
+
+        let all_prompts = mcp.list_all_prompts().await;
+        let mut commands = Vec::new();
+        for (server, prompts) in &all_prompts {
+            for prompt in prompts {
+                let shared = all_prompts
+                    .iter()
+                    .filter(|(_, other)| other.iter().any(|p| p.name == prompt.name))
+                    .count()
+                    > 1;
+                let command = if shared {
+                    qualified_name(server, &prompt.name)
+                } else {
+                    prompt.name.clone()
+                };
+                commands.push((command, prompt.clone()));
+            }
+        }
+        commands.sort_by(|a, b| a.0.cmp(&b.0));
+        commands
+    }
+
+    /// Fetch the MCP prompt behind `/command input` and return its text as a
+    /// task. `None` if no prompt has that command name.
+    pub async fn expand_prompt(&self, command: &str, input: &str) -> Result<Option<String>> {
+        let (Some(mcp), Some((_, prompt))) = (
+            &self.mcp_client,
+            self.prompt_commands()
                 .await
+                .into_iter()
+                .find(|(name, _)| name == command),
+        ) else {
+            return Ok(None);
+        };
+
+        let arguments = prompt
+            .parse_arguments(input)
+            .map_err(|e| anyhow::anyhow!("{} (usage: {})", e, prompt.usage(command)))?;
+        let result = mcp.get_prompt(command, &arguments).await?;
+        Ok(Some(result.text()))
+    }
+
+    /// Stop MCP servers cleanly before the process exits
+    pub async fn shutdown(&self) {
+        if let Some(mcp) = &self.mcp_client {
+            mcp.stop_all().await;
         }
     }
 
-    pub async fn initialize_project(&self, _root_path: &str) -> Result<()> {
-        Ok(())
+    /// Detect the project's language and framework for the project summary
+    pub async fn initialize_project(&self, root_path: &str) -> Result<()> {
+        let root = std::fs::canonicalize(root_path)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| root_path.to_string());
+        self.context_manager.initialize_project(root).await
     }
 
     pub fn get_agent(&self, name: &str) -> Option<Arc<dyn Agent>> {
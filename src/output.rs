@@ -0,0 +1,183 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::agents::{AgentResult, SubtaskReport};
+use crate::error::exit_code;
+use crate::events::Event;
+use crate::llm::types::Usage;
+
+/// How `execute`, `run-plan` and `agent` print their result
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// SUCCESS or FAILED and the output, for people
+    #[default]
+    Text,
+    /// One JSON object once the task is done
+    Json,
+    /// One JSON event per line as the task runs, ending with the result
+    Jsonl,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandRecord {
+    pub command: String,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Denial {
+    pub operation: String,
+    pub target: String,
+}
+
+/// A finished task as `--output json` prints it
+#[derive(Debug, Serialize)]
+pub struct TaskReport {
+    pub success: bool,
+    pub exit_code: u8,
+    pub output: String,
+    pub metadata: HashMap<String, String>,
+    /// The coordinator's per-subtask breakdown, empty for single-agent tasks
+    pub subtasks: Vec<SubtaskReport>,
+    pub files_changed: Vec<String>,
+    pub commands_run: Vec<CommandRecord>,
+    pub permissions_denied: Vec<Denial>,
+    pub usage: Usage,
+}
+
+impl TaskReport {
+    /// `events` are those recorded while the task ran
+    pub fn new(result: &AgentResult, events: &[Event], usage: Usage) -> Self {
+        let mut files_changed: Vec<String> = Vec::new();
+        let mut commands_run = Vec::new();
+        let mut permissions_denied = Vec::new();
+        for event in events {
+            match event {
+                Event::FileChanged { path } if !files_changed.contains(path) => {
+                    files_changed.push(path.clone())
+                }
+                Event::CommandRun { command, success } => commands_run.push(CommandRecord {
+                    command: command.clone(),
+                    success: *success,
+                }),
+                Event::PermissionDenied { operation, target } => permissions_denied.push(Denial {
+                    operation: operation.clone(),
+                    target: target.clone(),
+                }),
+                _ => {}
+            }
+        }
+
+        let exit_code = if result.success {
+            exit_code::SUCCESS
+        } else if !permissions_denied.is_empty() {
+            exit_code::PERMISSION_DENIED
+        } else {
+            exit_code::TASK_FAILED
+        };
+
+        // The subtasks are reported parsed rather than as a JSON string
+        let mut metadata = result.metadata.clone();
+        metadata.remove("subtasks");
+
+        Self {
+            success: result.success,
+            exit_code,
+            output: result.output.clone(),
+            metadata,
+            subtasks: SubtaskReport::from_result(result).unwrap_or_default(),
+            files_changed,
+            commands_run,
+            permissions_denied,
+            usage,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// `body` with an `event` field added, as one `--output jsonl` line
+pub fn event_line(event: &str, body: Value) -> String {
+    let mut line = json!({ "event": event });
+    if let (Some(line), Value::Object(body)) = (line.as_object_mut(), body) {
+        line.extend(body);
+    }
+    line.to_string()
+}
+
+/// Report an error that ended the command, in the chosen format
+pub fn print_error(format: OutputFormat, error: &anyhow::Error, code: u8) {
+    let body = json!({
+        "success": false,
+        "exit_code": code,
+        "error": format!("{:#}", error),
+    });
+    match format {
+        OutputFormat::Text => eprintln!("Error: {:?}", error),
+        OutputFormat::Json => println!("{}", body),
+        OutputFormat::Jsonl => println!("{}", event_line("error", body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_from_result_and_events() {
+        let subtasks = json!([{
+            "index": 0,
+            "description": "Run the tests",
+            "agent_type": "test",
+            "status": "FAILED",
+            "output": "1 failed"
+        }]);
+        let result = AgentResult::failure("The tests fail")
+            .with_metadata("subtasks", subtasks.to_string())
+            .with_metadata("agent", "coordinator");
+        let events = vec![
+            Event::FileChanged {
+                path: "a.rs".into(),
+            },
+            Event::FileChanged {
+                path: "a.rs".into(),
+            },
+            Event::CommandRun {
+                command: "cargo test".into(),
+                success: false,
+            },
+        ];
+
+        let report = TaskReport::new(&result, &events, Usage::default());
+        assert_eq!(report.exit_code, exit_code::TASK_FAILED);
+        assert_eq!(report.files_changed, vec!["a.rs"]);
+        assert_eq!(report.subtasks[0].agent_type, "test");
+        assert!(!report.metadata.contains_key("subtasks"));
+
+        let json = report.to_json();
+        assert_eq!(json["commands_run"][0]["command"], "cargo test");
+        assert_eq!(json["usage"]["requests"], 0);
+
+        let denied = vec![Event::PermissionDenied {
+            operation: "shell".into(),
+            target: "rm -rf build".into(),
+        }];
+        let report = TaskReport::new(&result, &denied, Usage::default());
+        assert_eq!(report.exit_code, exit_code::PERMISSION_DENIED);
+        assert_eq!(
+            TaskReport::new(&AgentResult::success("done"), &denied, Usage::default()).exit_code,
+            exit_code::SUCCESS
+        );
+    }
+
+    #[test]
+    fn test_event_line() {
+        assert_eq!(
+            event_line("task_started", json!({"task": "fix it"})),
+            r#"{"event":"task_started","task":"fix it"}"#
+        );
+    }
+}
@@ -1,33 +1,82 @@
+use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 
-#[derive(Debug, Clone, PartialEq)]
+/// Held while an interactive prompt owns the terminal, so prompts raised by
+/// subtasks running in parallel are shown one at a time
+static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn prompt_lock() -> MutexGuard<'static, ()> {
+    PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum PermissionMode {
+    #[default]
     /// Ask for permission every time
     Ask,
     /// Allow all operations without prompting
     AllowAll,
 }
 
+impl std::str::FromStr for PermissionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ask" => Ok(Self::Ask),
+            "allow-all" | "allowall" => Ok(Self::AllowAll),
+            _ => Err(format!(
+                "Unknown permission mode '{}' (expected ask or allow-all)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for PermissionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ask => write!(f, "ask"),
+            Self::AllowAll => write!(f, "allow-all"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PermissionManager {
     mode: Arc<Mutex<PermissionMode>>,
+    /// False when stdin and stdout belong to someone else (e.g. an MCP
+    /// client); operations that would need a prompt are then denied
+    interactive: bool,
 }
 
 impl PermissionManager {
     pub fn new(mode: PermissionMode) -> Self {
         Self {
             mode: Arc::new(Mutex::new(mode)),
+            interactive: true,
         }
     }
 
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
     /// Request permission for a file write operation
     pub fn request_file_write(&self, path: &str, content_preview: &str) -> bool {
         let current_mode = self.mode.lock().unwrap().clone();
 
         match current_mode {
             PermissionMode::AllowAll => true,
-            PermissionMode::Ask => self.prompt_user_file_write(path, content_preview),
+            PermissionMode::Ask if !self.interactive => false,
+            PermissionMode::Ask => {
+                let _prompt = prompt_lock();
+                // Another prompt may have granted blanket permission while we waited
+                self.is_allow_all() || self.prompt_user_file_write(path, content_preview)
+            }
         }
     }
 
@@ -37,7 +86,46 @@ impl PermissionManager {
 
         match current_mode {
             PermissionMode::AllowAll => true,
-            PermissionMode::Ask => self.prompt_user_shell_execution(command),
+            PermissionMode::Ask if !self.interactive => false,
+            PermissionMode::Ask => {
+                let _prompt = prompt_lock();
+                self.is_allow_all() || self.prompt_user_shell_execution(command)
+            }
+        }
+    }
+
+    /// Request permission to call a tool on an MCP server
+    pub fn request_mcp_tool_call(
+        &self,
+        server: &str,
+        tool: &str,
+        arguments: &str,
+        destructive: bool,
+    ) -> bool {
+        let current_mode = self.mode.lock().unwrap().clone();
+
+        match current_mode {
+            PermissionMode::AllowAll => true,
+            PermissionMode::Ask if !self.interactive => false,
+            PermissionMode::Ask => {
+                let _prompt = prompt_lock();
+                self.is_allow_all()
+                    || self.prompt_user_mcp_tool_call(server, tool, arguments, destructive)
+            }
+        }
+    }
+
+    /// Request permission for an MCP server to use the local model
+    pub fn request_mcp_sampling(&self, server: &str, model: &str, prompt: &str) -> bool {
+        let current_mode = self.mode.lock().unwrap().clone();
+
+        match current_mode {
+            PermissionMode::AllowAll => true,
+            PermissionMode::Ask if !self.interactive => false,
+            PermissionMode::Ask => {
+                let _prompt = prompt_lock();
+                self.is_allow_all() || self.prompt_user_mcp_sampling(server, model, prompt)
+            }
         }
     }
 
@@ -66,39 +154,7 @@ impl PermissionManager {
         println!("    [a] Allow ALL future operations (blanket permission)");
         println!("    [q] Quit/Cancel task");
 
-        loop {
-            print!("\n  Your choice [y/n/a/q]: ");
-            io::stdout().flush().unwrap();
-
-            let mut input = String::new();
-            if io::stdin().read_line(&mut input).is_err() {
-                return false;
-            }
-
-            match input.trim().to_lowercase().as_str() {
-                "y" | "yes" => {
-                    println!("  >> Write allowed\n");
-                    return true;
-                }
-                "n" | "no" => {
-                    println!("  >> Write denied\n");
-                    return false;
-                }
-                "a" | "all" => {
-                    println!("  >> WARNING: Enabling blanket permissions for this session...");
-                    *self.mode.lock().unwrap() = PermissionMode::AllowAll;
-                    println!("  >> All future operations will be allowed\n");
-                    return true;
-                }
-                "q" | "quit" => {
-                    println!("  >> Task cancelled\n");
-                    return false;
-                }
-                _ => {
-                    println!("  Invalid choice. Please enter y, n, a, or q.");
-                }
-            }
-        }
+        self.read_choice("Write allowed", "Write denied")
     }
 
     fn prompt_user_shell_execution(&self, command: &str) -> bool {
@@ -113,6 +169,62 @@ impl PermissionManager {
         println!("    [a] Allow ALL future operations (blanket permission)");
         println!("    [q] Quit/Cancel task");
 
+        self.read_choice("Execution allowed", "Execution denied")
+    }
+
+    fn prompt_user_mcp_tool_call(
+        &self,
+        server: &str,
+        tool: &str,
+        arguments: &str,
+        destructive: bool,
+    ) -> bool {
+        println!("\n┌─────────────────────────────────────────────────────────────┐");
+        println!("│ MCP TOOL CALL PERMISSION REQUESTED                         │");
+        println!("└─────────────────────────────────────────────────────────────┘");
+        println!("  Server: {}", server);
+        println!("  Tool:   {}", tool);
+        if destructive {
+            println!("  WARNING: the server says this tool may modify or delete data");
+        }
+        println!("\n  Arguments:");
+        for line in arguments.lines().take(20) {
+            println!("    {}", line);
+        }
+
+        println!("\n  Options:");
+        println!("    [y] Allow this call");
+        println!("    [n] Deny this call");
+        println!("    [a] Allow ALL future operations (blanket permission)");
+        println!("    [q] Quit/Cancel task");
+
+        self.read_choice("Call allowed", "Call denied")
+    }
+
+    fn prompt_user_mcp_sampling(&self, server: &str, model: &str, prompt: &str) -> bool {
+        println!("\n┌─────────────────────────────────────────────────────────────┐");
+        println!("│ MCP SAMPLING PERMISSION REQUESTED                          │");
+        println!("└─────────────────────────────────────────────────────────────┘");
+        println!(
+            "  Server '{}' wants a completion from model '{}'",
+            server, model
+        );
+        println!("\n  Prompt:");
+        for line in prompt.lines().take(20) {
+            println!("    {}", line);
+        }
+
+        println!("\n  Options:");
+        println!("    [y] Run this completion");
+        println!("    [n] Refuse it");
+        println!("    [a] Allow ALL future operations (blanket permission)");
+        println!("    [q] Quit/Cancel task");
+
+        self.read_choice("Completion allowed", "Completion refused")
+    }
+
+    /// Read y/n/a/q from the terminal; `a` also switches to AllowAll
+    fn read_choice(&self, allowed: &str, denied: &str) -> bool {
         loop {
             print!("\n  Your choice [y/n/a/q]: ");
             io::stdout().flush().unwrap();
@@ -124,11 +236,11 @@ impl PermissionManager {
 
             match input.trim().to_lowercase().as_str() {
                 "y" | "yes" => {
-                    println!("  >> Execution allowed\n");
+                    println!("  >> {}\n", allowed);
                     return true;
                 }
                 "n" | "no" => {
-                    println!("  >> Execution denied\n");
+                    println!("  >> {}\n", denied);
                     return false;
                 }
                 "a" | "all" => {
@@ -148,8 +260,21 @@ impl PermissionManager {
         }
     }
 
+    /// Whether the user can be asked questions on the terminal
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    pub fn mode(&self) -> PermissionMode {
+        self.mode.lock().unwrap().clone()
+    }
+
+    /// Change the mode for the rest of the session
+    pub fn set_mode(&self, mode: PermissionMode) {
+        *self.mode.lock().unwrap() = mode;
+    }
+
     /// Check if currently in AllowAll mode
-    #[allow(dead_code)]
     pub fn is_allow_all(&self) -> bool {
         matches!(*self.mode.lock().unwrap(), PermissionMode::AllowAll)
     }
@@ -164,6 +289,7 @@ mod tests {
         let pm = PermissionManager::new(PermissionMode::AllowAll);
         assert!(pm.request_file_write("/tmp/test.txt", "content"));
         assert!(pm.request_shell_execution("ls -la"));
+        assert!(pm.request_mcp_tool_call("github", "create_issue", "{}", true));
     }
 
     #[test]
@@ -174,4 +300,26 @@ mod tests {
         let pm2 = PermissionManager::new(PermissionMode::Ask);
         assert!(!pm2.is_allow_all());
     }
+
+    #[test]
+    fn test_mode_can_be_changed() {
+        let pm = PermissionManager::new(PermissionMode::Ask);
+        pm.set_mode("allow-all".parse().unwrap());
+        assert!(pm.is_allow_all());
+        assert_eq!(pm.mode().to_string(), "allow-all");
+        assert!("sometimes".parse::<PermissionMode>().is_err());
+    }
+
+    #[test]
+    fn test_non_interactive_mode_denies_without_prompting() {
+        let pm = PermissionManager::new(PermissionMode::Ask).with_interactive(false);
+        assert!(!pm.is_interactive());
+        assert!(!pm.request_file_write("/tmp/test.txt", "content"));
+        assert!(!pm.request_shell_execution("ls -la"));
+        assert!(!pm.request_mcp_tool_call("github", "create_issue", "{}", false));
+        assert!(!pm.request_mcp_sampling("github", "default", "Summarize"));
+
+        let pm = PermissionManager::new(PermissionMode::AllowAll).with_interactive(false);
+        assert!(pm.request_shell_execution("ls -la"));
+    }
 }
@@ -4,13 +4,18 @@ use std::sync::Arc;
 use tokio::fs;
 use tokio::process::Command;
 use tracing::{debug, info, warn};
-use walkdir::WalkDir;
 
+use super::search::{self, SearchMatch, SearchOptions};
+use super::undo::UndoStack;
+use crate::error::LcaError;
+use crate::events::{Activity, Event};
 use crate::permissions::PermissionManager;
 
 pub struct ToolExecutor {
     base_path: PathBuf,
     permission_manager: Option<Arc<PermissionManager>>,
+    undo: UndoStack,
+    activity: Activity,
 }
 
 impl ToolExecutor {
@@ -18,6 +23,8 @@ impl ToolExecutor {
         Self {
             base_path: base_path.into(),
             permission_manager: None,
+            undo: UndoStack::default(),
+            activity: Activity::default(),
         }
     }
 
@@ -26,7 +33,24 @@ impl ToolExecutor {
         self
     }
 
-    fn resolve_path(&self, path: &str) -> PathBuf {
+    /// File changes made through this executor, for `/undo`
+    pub fn undo_stack(&self) -> &UndoStack {
+        &self.undo
+    }
+
+    /// Files changed, commands run and permissions denied, for reports
+    pub fn activity(&self) -> &Activity {
+        &self.activity
+    }
+
+    /// Whether agents may ask the user questions on the terminal
+    pub fn is_interactive(&self) -> bool {
+        self.permission_manager
+            .as_ref()
+            .is_none_or(|pm| pm.is_interactive())
+    }
+
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
         let path = Path::new(path);
         if path.is_absolute() {
             path.to_path_buf()
@@ -56,12 +80,22 @@ impl ToolExecutor {
             };
 
             if !pm.request_file_write(path, preview) {
-                warn!("File write denied by user: {:?}", full_path);
-                anyhow::bail!("File write permission denied by user");
+                warn!("File write denied: {:?}", full_path);
+                self.activity.emit(Event::PermissionDenied {
+                    operation: "file_write".to_string(),
+                    target: path.to_string(),
+                });
+                let message = if pm.is_interactive() {
+                    "File write permission denied by user"
+                } else {
+                    "File writes are not allowed without --allow-all in this mode"
+                };
+                return Err(LcaError::PermissionDenied(message.to_string()).into());
             }
         }
 
         debug!("Writing file: {:?}", full_path);
+        self.undo.record(&full_path).await?;
 
         if let Some(parent) = full_path.parent() {
             fs::create_dir_all(parent).await?;
@@ -69,7 +103,11 @@ impl ToolExecutor {
 
         fs::write(&full_path, content)
             .await
-            .with_context(|| format!("Failed to write file: {:?}", full_path))
+            .with_context(|| format!("Failed to write file: {:?}", full_path))?;
+        self.activity.emit(Event::FileChanged {
+            path: path.to_string(),
+        });
+        Ok(())
     }
 
     pub async fn list_files(&self, path: &str) -> Result<Vec<String>> {
@@ -93,29 +131,35 @@ impl ToolExecutor {
         Ok(files)
     }
 
-    pub async fn search_files(&self, base_path: &str, pattern: &str) -> Result<Vec<String>> {
+    /// Search files under `base_path` for `options.pattern`, honouring ignore files
+    pub async fn search(
+        &self,
+        base_path: &str,
+        options: SearchOptions,
+    ) -> Result<Vec<SearchMatch>> {
         let full_path = self.resolve_path(base_path);
-        debug!("Searching for pattern '{}' in: {:?}", pattern, full_path);
+        debug!(
+            "Searching for pattern '{}' in: {:?}",
+            options.pattern, full_path
+        );
 
-        let mut matches = Vec::new();
+        tokio::task::spawn_blocking(move || search::search(&full_path, &options))
+            .await
+            .context("Search task panicked")?
+    }
 
-        for entry in WalkDir::new(&full_path)
-            .follow_links(true)
+    /// Return the paths of files containing `pattern` as a literal string
+    pub async fn search_files(&self, base_path: &str, pattern: &str) -> Result<Vec<String>> {
+        let options = SearchOptions::new(pattern).with_max_results(None);
+        let mut paths: Vec<String> = self
+            .search(base_path, options)
+            .await?
             .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            if entry.file_type().is_file() {
-                if let Ok(content) = fs::read_to_string(entry.path()).await {
-                    if content.contains(pattern) {
-                        if let Some(path_str) = entry.path().to_str() {
-                            matches.push(path_str.to_string());
-                        }
-                    }
-                }
-            }
-        }
+            .map(|m| m.path)
+            .collect();
+        paths.dedup();
 
-        Ok(matches)
+        Ok(paths)
     }
 
     pub async fn execute_shell(&self, command: &str, working_dir: &str) -> Result<String> {
@@ -124,8 +168,17 @@ impl ToolExecutor {
         // Check permissions if manager is available
         if let Some(ref pm) = self.permission_manager {
             if !pm.request_shell_execution(command) {
-                warn!("Shell execution denied by user: {}", command);
-                anyhow::bail!("Shell execution permission denied by user");
+                warn!("Shell execution denied: {}", command);
+                self.activity.emit(Event::PermissionDenied {
+                    operation: "shell".to_string(),
+                    target: command.to_string(),
+                });
+                let message = if pm.is_interactive() {
+                    "Shell execution permission denied by user"
+                } else {
+                    "Shell commands are not allowed without --allow-all in this mode"
+                };
+                return Err(LcaError::PermissionDenied(message.to_string()).into());
             }
         }
 
@@ -149,6 +202,11 @@ impl ToolExecutor {
                 .await?
         };
 
+        self.activity.emit(Event::CommandRun {
+            command: command.to_string(),
+            success: output.status.success(),
+        });
+
         let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
 
@@ -161,6 +219,31 @@ impl ToolExecutor {
         Ok(result)
     }
 
+    /// Run a read-only git query (diff, show, ...) in the base directory.
+    ///
+    /// Unlike `execute_shell` this does not prompt, so callers must only pass
+    /// subcommands that cannot modify the repository.
+    pub async fn git_query(&self, args: &[&str]) -> Result<String> {
+        debug!("Running git {:?} in {:?}", args, self.base_path);
+
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&self.base_path)
+            .output()
+            .await
+            .context("Failed to run git")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
     #[allow(dead_code)]
     pub async fn file_exists(&self, path: &str) -> bool {
         let full_path = self.resolve_path(path);
@@ -171,10 +254,15 @@ impl ToolExecutor {
     pub async fn delete_file(&self, path: &str) -> Result<()> {
         let full_path = self.resolve_path(path);
         debug!("Deleting file: {:?}", full_path);
+        self.undo.record(&full_path).await?;
 
         fs::remove_file(&full_path)
             .await
-            .with_context(|| format!("Failed to delete file: {:?}", full_path))
+            .with_context(|| format!("Failed to delete file: {:?}", full_path))?;
+        self.activity.emit(Event::FileChanged {
+            path: path.to_string(),
+        });
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -182,6 +270,7 @@ impl ToolExecutor {
         let from_path = self.resolve_path(from);
         let to_path = self.resolve_path(to);
         debug!("Copying file from {:?} to {:?}", from_path, to_path);
+        self.undo.record(&to_path).await?;
 
         if let Some(parent) = to_path.parent() {
             fs::create_dir_all(parent).await?;
@@ -190,6 +279,9 @@ impl ToolExecutor {
         fs::copy(&from_path, &to_path).await.with_context(|| {
             format!("Failed to copy file from {:?} to {:?}", from_path, to_path)
         })?;
+        self.activity.emit(Event::FileChanged {
+            path: to.to_string(),
+        });
 
         Ok(())
     }
@@ -223,4 +315,53 @@ mod tests {
         let files = executor.list_files(".").await.unwrap();
         assert_eq!(files.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_writes_can_be_undone() {
+        let dir = tempdir().unwrap();
+        let executor = ToolExecutor::new(dir.path());
+        executor.write_file("notes.txt", "before").await.unwrap();
+
+        executor.undo_stack().begin("edit notes");
+        executor.write_file("notes.txt", "after").await.unwrap();
+        executor.write_file("extra.txt", "new").await.unwrap();
+
+        let (_, restored) = executor.undo_stack().undo().await.unwrap().unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(executor.read_file("notes.txt").await.unwrap(), "before");
+        assert!(!executor.file_exists("extra.txt").await);
+    }
+
+    #[tokio::test]
+    async fn test_activity_records_changes_and_denials() {
+        use crate::permissions::PermissionMode;
+
+        let dir = tempdir().unwrap();
+        let executor = ToolExecutor::new(dir.path()).with_permissions(Arc::new(
+            PermissionManager::new(PermissionMode::Ask).with_interactive(false),
+        ));
+
+        let error = executor.write_file("a.txt", "x").await.unwrap_err();
+        assert_eq!(
+            crate::error::exit_code_for(&error),
+            crate::error::exit_code::PERMISSION_DENIED
+        );
+        assert!(executor.execute_shell("true", ".").await.is_err());
+
+        let executor = ToolExecutor::new(dir.path());
+        executor.write_file("a.txt", "x").await.unwrap();
+        executor.execute_shell("exit 3", ".").await.unwrap();
+        assert_eq!(
+            executor.activity().events(),
+            vec![
+                Event::FileChanged {
+                    path: "a.txt".into()
+                },
+                Event::CommandRun {
+                    command: "exit 3".into(),
+                    success: false
+                },
+            ]
+        );
+    }
 }
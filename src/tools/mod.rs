@@ -1,3 +1,6 @@
 pub mod executor;
+pub mod search;
+pub mod undo;
 
 pub use executor::ToolExecutor;
+pub use search::SearchOptions;
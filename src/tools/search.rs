@@ -0,0 +1,285 @@
+use anyhow::{Context, Result};
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use tracing::debug;
+
+/// Bytes inspected when deciding whether a file is binary
+const BINARY_SNIFF_LEN: usize = 8192;
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression instead of a literal string
+    pub regex: bool,
+    pub case_insensitive: bool,
+    /// Include/exclude globs, e.g. `*.rs` or `!tests/**`
+    pub globs: Vec<String>,
+    /// Skip files larger than this many bytes
+    pub max_filesize: Option<u64>,
+    /// Lines of context before and after each match
+    pub context_lines: usize,
+    pub max_results: Option<usize>,
+    pub include_hidden: bool,
+}
+
+impl SearchOptions {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            regex: false,
+            case_insensitive: false,
+            globs: Vec::new(),
+            max_filesize: Some(1024 * 1024),
+            context_lines: 0,
+            max_results: Some(500),
+            include_hidden: false,
+        }
+    }
+
+    pub fn with_regex(mut self, regex: bool) -> Self {
+        self.regex = regex;
+        self
+    }
+
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    pub fn with_glob(mut self, glob: impl Into<String>) -> Self {
+        self.globs.push(glob.into());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_max_filesize(mut self, max_filesize: Option<u64>) -> Self {
+        self.max_filesize = max_filesize;
+        self
+    }
+
+    pub fn with_context_lines(mut self, context_lines: usize) -> Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    pub fn with_max_results(mut self, max_results: Option<usize>) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    fn build_matcher(&self) -> Result<Regex> {
+        let pattern = if self.regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.case_insensitive)
+            .build()
+            .with_context(|| format!("Invalid search pattern: {}", self.pattern))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub path: String,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column (in characters) of the first match on the line
+    pub column: usize,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+}
+
+impl SearchMatch {
+    /// Format in grep style: context lines use `-`, the matching line uses `:`
+    pub fn to_grep_string(&self) -> String {
+        let mut out = String::new();
+        let first_before = self.line - self.before.len();
+
+        for (idx, line) in self.before.iter().enumerate() {
+            out.push_str(&format!("{}-{}- {}\n", self.path, first_before + idx, line));
+        }
+        out.push_str(&format!(
+            "{}:{}:{}: {}",
+            self.path, self.line, self.column, self.text
+        ));
+        for (idx, line) in self.after.iter().enumerate() {
+            out.push_str(&format!(
+                "\n{}-{}- {}",
+                self.path,
+                self.line + 1 + idx,
+                line
+            ));
+        }
+
+        out
+    }
+}
+
+/// Search files under `root`, honouring `.gitignore`, `.ignore` and hidden-file rules
+pub fn search(root: &Path, options: &SearchOptions) -> Result<Vec<SearchMatch>> {
+    let matcher = options.build_matcher()?;
+
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .hidden(!options.include_hidden)
+        .follow_links(false)
+        .require_git(false)
+        .max_filesize(options.max_filesize)
+        .sort_by_file_name(|a, b| a.cmp(b));
+
+    if !options.globs.is_empty() {
+        let mut overrides = OverrideBuilder::new(root);
+        for glob in &options.globs {
+            overrides
+                .add(glob)
+                .with_context(|| format!("Invalid glob: {}", glob))?;
+        }
+        builder.overrides(overrides.build()?);
+    }
+
+    let mut matches = Vec::new();
+
+    for entry in builder.build().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+
+        let Some(content) = read_text_file(entry.path()) else {
+            continue;
+        };
+
+        let display_path = entry
+            .path()
+            .strip_prefix(root)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .to_string();
+        let display_path = if display_path.is_empty() {
+            entry.path().to_string_lossy().to_string()
+        } else {
+            display_path
+        };
+
+        let lines: Vec<&str> = content.lines().collect();
+        for (idx, line) in lines.iter().enumerate() {
+            let Some(found) = matcher.find(line) else {
+                continue;
+            };
+
+            let before_start = idx.saturating_sub(options.context_lines);
+            let after_end = (idx + 1 + options.context_lines).min(lines.len());
+
+            matches.push(SearchMatch {
+                path: display_path.clone(),
+                line: idx + 1,
+                column: line[..found.start()].chars().count() + 1,
+                text: line.to_string(),
+                before: lines[before_start..idx]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect(),
+                after: lines[idx + 1..after_end]
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect(),
+            });
+
+            if options.max_results.is_some_and(|max| matches.len() >= max) {
+                debug!("Search hit result limit of {}", matches.len());
+                return Ok(matches);
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Read a file as text, returning `None` for unreadable or binary files
+fn read_text_file(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).ok()?;
+
+    let sniff = &bytes[..bytes.len().min(BINARY_SNIFF_LEN)];
+    if sniff.contains(&0) {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("target")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "fn main() {\n    let Value = 1;\n    println!(\"{}\", value);\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("src/notes.txt"), "value in notes\n").unwrap();
+        std::fs::write(dir.path().join("target/out.rs"), "let value = 2;\n").unwrap();
+        std::fs::write(dir.path().join("src/blob.bin"), b"value\0\x01\x02").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_search_respects_gitignore_and_skips_binary() {
+        let dir = fixture();
+        let matches = search(dir.path(), &SearchOptions::new("value")).unwrap();
+
+        let mut paths: Vec<&str> = matches.iter().map(|m| m.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["src/lib.rs", "src/notes.txt"]);
+    }
+
+    #[test]
+    fn test_search_regex_case_insensitive_with_context() {
+        let dir = fixture();
+        let options = SearchOptions::new(r"let \w+ =")
+            .with_regex(true)
+            .with_case_insensitive(true)
+            .with_glob("*.rs")
+            .with_context_lines(1);
+        let matches = search(dir.path(), &options).unwrap();
+
+        assert_eq!(matches.len(), 1);
+        let m = &matches[0];
+        assert_eq!((m.line, m.column), (2, 5));
+        assert_eq!(m.before, vec!["fn main() {"]);
+        assert_eq!(m.after, vec!["    println!(\"{}\", value);"]);
+        assert_eq!(
+            m.to_grep_string().lines().nth(1),
+            Some("src/lib.rs:2:5:     let Value = 1;")
+        );
+    }
+
+    #[test]
+    fn test_search_literal_escapes_pattern() {
+        let dir = fixture();
+        let matches = search(dir.path(), &SearchOptions::new("println!(")).unwrap();
+        assert_eq!(matches.len(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::fs;
+use tracing::debug;
+
+/// A file as it was before a task first changed it; `None` if it did not exist
+struct Backup {
+    path: PathBuf,
+    previous: Option<Vec<u8>>,
+}
+
+/// The files one task changed
+struct Group {
+    label: String,
+    backups: Vec<Backup>,
+}
+
+/// Files changed through the tool executor, grouped by task so the last
+/// task's changes can be reverted. Nothing is recorded until a group begins.
+#[derive(Default)]
+pub struct UndoStack {
+    groups: Mutex<Vec<Group>>,
+}
+
+impl UndoStack {
+    /// Start recording the changes of a new task
+    pub fn begin(&self, label: impl Into<String>) {
+        let mut groups = self.groups.lock().unwrap();
+        // A group that changed nothing has nothing to undo
+        if groups.last().is_some_and(|g| g.backups.is_empty()) {
+            groups.pop();
+        }
+        groups.push(Group {
+            label: label.into(),
+            backups: Vec::new(),
+        });
+    }
+
+    /// Remember `path` as it is now, unless the current group already has it
+    pub async fn record(&self, path: &Path) -> Result<()> {
+        let recorded = {
+            let groups = self.groups.lock().unwrap();
+            match groups.last() {
+                Some(group) => group.backups.iter().any(|b| b.path == path),
+                None => return Ok(()),
+            }
+        };
+        if recorded {
+            return Ok(());
+        }
+
+        let previous = match fs::read(path).await {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to back up {:?} for undo", path))
+            }
+        };
+        if let Some(group) = self.groups.lock().unwrap().last_mut() {
+            group.backups.push(Backup {
+                path: path.to_path_buf(),
+                previous,
+            });
+        }
+        Ok(())
+    }
+
+    /// Revert the most recent task that changed files. Returns its label and
+    /// the restored paths, or `None` if there is nothing to undo.
+    pub async fn undo(&self) -> Result<Option<(String, Vec<PathBuf>)>> {
+        let group = {
+            let mut groups = self.groups.lock().unwrap();
+            loop {
+                match groups.pop() {
+                    Some(group) if group.backups.is_empty() => continue,
+                    other => break other,
+                }
+            }
+        };
+        let Some(group) = group else {
+            return Ok(None);
+        };
+
+        let mut restored = Vec::new();
+        for backup in group.backups.into_iter().rev() {
+            debug!("Undoing change to {:?}", backup.path);
+            match &backup.previous {
+                Some(bytes) => fs::write(&backup.path, bytes).await,
+                None => match fs::remove_file(&backup.path).await {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    other => other,
+                },
+            }
+            .with_context(|| format!("Failed to restore {:?}", backup.path))?;
+            restored.push(backup.path);
+        }
+        restored.reverse();
+        Ok(Some((group.label, restored)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_undo_restores_the_last_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("main.rs");
+        let created = dir.path().join("new.rs");
+        std::fs::write(&existing, "v1").unwrap();
+
+        let stack = UndoStack::default();
+        stack.record(&existing).await.unwrap();
+        std::fs::write(&existing, "ignored").unwrap();
+
+        stack.begin("first task");
+        stack.record(&existing).await.unwrap();
+        std::fs::write(&existing, "v2").unwrap();
+
+        stack.begin("second task");
+        stack.record(&existing).await.unwrap();
+        std::fs::write(&existing, "v3").unwrap();
+        stack.record(&created).await.unwrap();
+        std::fs::write(&created, "new").unwrap();
+        stack.record(&existing).await.unwrap();
+        std::fs::write(&existing, "v4").unwrap();
+        stack.begin("read-only task");
+
+        let (label, restored) = stack.undo().await.unwrap().unwrap();
+        assert_eq!(label, "second task");
+        assert_eq!(restored, vec![existing.clone(), created.clone()]);
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "v2");
+        assert!(!created.exists());
+
+        let (label, _) = stack.undo().await.unwrap().unwrap();
+        assert_eq!(label, "first task");
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "ignored");
+        assert!(stack.undo().await.unwrap().is_none());
+    }
+}